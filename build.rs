@@ -1,5 +1,6 @@
+use std::collections::HashMap;
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs::{self, DirEntry, File};
 use std::io::{BufReader, Result};
 use std::path::{Path, PathBuf};
@@ -18,12 +19,178 @@ macro_rules! env {
 }
 
 const EXTRAS: &str = "extra";
-
+/// Bindings that need the `frontend` feature.
+const EXTRAS_FRONTEND: &str = "extra/frontend";
+/// The C++ half of the `cxx` bridge (`src/bridge.rs`); only built with the
+/// `cxx-bridge` feature.
+const BRIDGE: &str = "extra/bridge";
+
+/// The vendored `LC3Tools` sources (a submodule). `LC3TOOLS_DIR` points us at
+/// another copy instead; see `resolve`.
+const LC3TOOLS: &str = "lc3tools";
 const BACKEND: &str = "lc3tools/backend";
 const FRONTEND: &str = "lc3tools/frontend/common";
 const GRADER: &str = "lc3tools/frontend/grader";
 
-#[cfg(feature = "generate-fresh")]
+/// A set of directories whose headers and sources are compiled (and bound)
+/// together behind a cargo feature.
+struct Component {
+    /// The directories with the component's headers and sources.
+    dirs: &'static [&'static str],
+    /// The feature that enables the component; `None` if it's always built.
+    feature: Option<&'static str>,
+    /// The features of the other components this one needs (all of them, not
+    /// just the direct ones). These should match the feature's dependencies
+    /// in `Cargo.toml`.
+    requires: &'static [&'static str],
+}
+
+/// Everything we compile. Items in the bindings that only show up once a
+/// component's headers are included get tagged with its feature.
+///
+/// To add another `LC3Tools` directory (e.g. the CLI frontends), add a feature
+/// for it to `Cargo.toml`, an entry here and a place for its headers in
+/// `INCLUDE_LAYOUT`.
+const COMPONENTS: &[Component] = &[
+    Component {
+        dirs: &[BACKEND],
+        feature: None,
+        requires: &[],
+    },
+    Component {
+        dirs: &[EXTRAS],
+        feature: None,
+        requires: &[],
+    },
+    Component {
+        dirs: &[FRONTEND, EXTRAS_FRONTEND],
+        feature: Some("frontend"),
+        requires: &[],
+    },
+    Component {
+        dirs: &[GRADER],
+        feature: Some("grader"),
+        requires: &["frontend"],
+    },
+];
+
+impl Component {
+    /// The directories the component's headers and sources are built from.
+    fn paths(&self) -> impl Iterator<Item = PathBuf> {
+        self.dirs.iter().map(|d| source_dir(d))
+    }
+
+    fn enabled(&self) -> bool {
+        let enabled = self.feature.map_or(true, feature_enabled);
+
+        if enabled {
+            for feature in self.requires {
+                assert!(
+                    feature_enabled(feature),
+                    "`{}` requires `{}`; is `Cargo.toml` out of sync?",
+                    self.feature.unwrap(),
+                    feature,
+                );
+            }
+        }
+
+        enabled
+    }
+
+    /// The directories this component needs to be compiled or bound: its own,
+    /// those of the components it requires and those of the components that
+    /// are always built.
+    #[cfg(feature = "generate-fresh")]
+    fn closure(&self) -> Vec<PathBuf> {
+        COMPONENTS
+            .iter()
+            .filter(|c| {
+                c.feature.map_or(true, |f| {
+                    self.requires.contains(&f) || self.feature == Some(f)
+                })
+            })
+            .flat_map(|c| c.paths())
+            .collect()
+    }
+}
+
+/// Where a directory actually is: directories in the vendored `LC3Tools`
+/// sources are looked for under `LC3TOOLS_DIR` instead, if it's set.
+fn resolve(dir: &str) -> PathBuf {
+    let lc3tools = env::var_os("LC3TOOLS_DIR");
+    match (lc3tools, Path::new(dir).strip_prefix(LC3TOOLS)) {
+        (Some(root), Ok(rest)) => PathBuf::from(root).join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
+
+/// Where a directory's sources are built from: `LC3Tools` directories are
+/// copied into `OUT_DIR` and patched (see `PATCHES`).
+fn source_dir(dir: &str) -> PathBuf {
+    match Path::new(dir).strip_prefix(LC3TOOLS) {
+        Ok(rest) => PathBuf::from(env::var_os("OUT_DIR").unwrap())
+            .join(LC3TOOLS)
+            .join(rest),
+        Err(_) => PathBuf::from(dir),
+    }
+}
+
+/// Whether a directory has `LC3Tools` sources (as opposed to our own).
+fn in_lc3tools(dir: &str) -> bool {
+    Path::new(dir).starts_with(LC3TOOLS)
+}
+
+/// Whether we're building for MSVC. (This is about the target; `cfg!` in
+/// here would tell us about the host.)
+fn msvc() -> bool {
+    env!("CARGO_CFG_TARGET_ENV") == "msvc"
+}
+
+/// The file name of a static library made by `cc`.
+fn static_lib(name: &str) -> String {
+    if msvc() {
+        format!("{}.lib", name)
+    } else {
+        format!("lib{}.a", name)
+    }
+}
+
+/// Whether a feature of this crate is enabled.
+///
+/// `cfg!` only knows about the features `build.rs` was compiled with, which
+/// is the same thing, but this lets us look features up by name.
+fn feature_enabled(feature: &str) -> bool {
+    let var =
+        format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+    env::var_os(var).is_some()
+}
+
+/// Where each directory's headers go in the include tree we export (relative
+/// to its root); see `IncludeTree`. This layout is meant to be stable so please
+/// don't move things around.
+const INCLUDE_LAYOUT: &[(&str, &str)] = &[
+    (BACKEND, "lc3tools/backend"),
+    (FRONTEND, "lc3tools/frontend"),
+    (GRADER, "lc3tools/grader"),
+    (EXTRAS, "lc3tools-sys"),
+    (EXTRAS_FRONTEND, "lc3tools-sys/frontend"),
+    (BRIDGE, "lc3tools-sys/bridge"),
+];
+
+/// Unified diffs (relative to the root of the `LC3Tools` sources) that are
+/// applied, in order, to the copy of `LC3Tools` we build. `LC3TOOLS_PATCH_DIR`
+/// can point at a directory of more.
+const PATCHES: &str = "patches";
+
+/// The headers with the C API (and the directories they're in) that the
+/// `shared-lib` feature exports.
+#[cfg(feature = "shared-lib")]
+const C_API: &[(&str, &str)] = &[
+    (EXTRAS, "bindings.h"),
+    (EXTRAS_FRONTEND, "frontend_bindings.h"),
+];
+
+#[cfg(any(feature = "generate-fresh", feature = "verify-bindings"))]
 const BINDINGS_PATH: &str = "generated/bindings.rs";
 
 fn in_dir_with_ext<'s, D>(
@@ -39,28 +206,82 @@ where
         .filter(|d|
             // This file is not used and is broken.
             d.path().file_name().unwrap().to_str().unwrap() != "device.h")
-        .filter(move |de| {
-            de.path().extension().unwrap().to_str().unwrap() == ext
-        }))
+        .filter(move |de| de.path().extension().map_or(false, |e| e == ext)))
 }
 
-fn copy_headers<I>(inc_dir: &I, cpy_dir: &Path) -> Result<()>
-where
-    I: AsRef<OsStr> + ?Sized,
-{
-    fs::create_dir_all(&cpy_dir)?;
+/// The include tree we export (`DEP_LC3CORE_INCLUDE`).
+///
+/// Each directory's headers go in their place in `INCLUDE_LAYOUT` and are also
+/// copied to the root of the tree (the flat view: what the `LC3Tools` headers
+/// themselves and existing users expect). Since the flat view can only have
+/// one header with a given name, two with the same name is an error.
+struct IncludeTree {
+    root: PathBuf,
+    /// Header name → the directory it came from.
+    flat: HashMap<OsString, &'static str>,
+}
 
-    let inc_dir_str = inc_dir.as_ref().to_str().unwrap();
-    println!("cargo:rerun-if-changed={}", inc_dir_str);
+impl IncludeTree {
+    fn new(root: PathBuf) -> Result<Self> {
+        // Don't leave headers from a previous build (i.e. with other features)
+        // lying around.
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(&root)?;
 
-    for header in in_dir_with_ext(inc_dir, "h").unwrap_or_else(|e| {
-        panic!("{}: expected header files in `{}`", e, inc_dir_str)
-    }) {
-        let path = header.path();
-        println!("cargo:rerun-if-changed={}", path.display());
+        Ok(Self {
+            root,
+            flat: HashMap::new(),
+        })
+    }
 
-        let to = cpy_dir.join(path.file_name().unwrap());
-        fs::copy(&path, &to).expect("Header file copy to succeed");
+    fn add(&mut self, dir: &'static str) -> Result<()> {
+        let place = INCLUDE_LAYOUT
+            .iter()
+            .find(|(d, _)| *d == dir)
+            .map(|(_, place)| self.root.join(place))
+            .unwrap_or_else(|| {
+                panic!("`{}` has no place in `INCLUDE_LAYOUT`", dir)
+            });
+        fs::create_dir_all(&place)?;
+
+        let from = source_dir(dir);
+        for header in in_dir_with_ext(&from, "h").unwrap_or_else(|e| {
+            panic!("{}: expected header files in `{}`", e, from.display())
+        }) {
+            let name = header.file_name();
+
+            if let Some(other) = self.flat.insert(name.clone(), dir) {
+                panic!(
+                    "There's a `{}` in both `{}` and `{}`; they can't both go \
+                    in the flat include directory.",
+                    name.to_string_lossy(),
+                    other,
+                    dir,
+                );
+            }
+
+            fs::copy(header.path(), place.join(&name))?;
+            fs::copy(header.path(), self.root.join(&name))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Asks cargo to rerun us when a directory or any of the files in it change.
+///
+/// This should only be used on the original sources and not on the copies in
+/// `OUT_DIR`; those are rewritten every time we run.
+fn watch(dir: &Path) -> Result<()> {
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            println!("cargo:rerun-if-changed={}", entry.path().display());
+        }
     }
 
     Ok(())
@@ -92,60 +313,156 @@ where
     Ok(())
 }
 
+/// The names of the global namespace items (classes, structs, functions and
+/// `extern` variables) a header declares; these are the `extern "C"` bindings in
+/// `extra` and the grader's API.
+///
+/// This is a rough scan (no preprocessing, no templates) but it's only used to
+/// build `bindgen`'s allowlist so picking up a stray name is harmless.
 #[cfg(feature = "generate-fresh")]
-fn make_bindings<I>(
-    inc_dirs: &[&I],
-) -> std::result::Result<syn::File, Box<dyn std::error::Error>>
-where
-    I: AsRef<OsStr> + std::fmt::Display + ?Sized,
-{
+fn global_names(header: &str) -> Vec<String> {
+    const KEYWORDS: &[&str] = &[
+        "void",
+        "return",
+        "if",
+        "for",
+        "while",
+        "switch",
+        "sizeof",
+        "decltype",
+        "operator",
+        "static_cast",
+        "reinterpret_cast",
+    ];
+
+    let src = header_support::strip_comments(header);
+    let src = src
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut names = Vec::new();
+    let mut token = String::new();
+    let mut prev = String::new();
+    let mut statement = Vec::<String>::new();
+    // Braces that aren't `namespace` or `extern "C"` blocks.
+    let mut scopes = Vec::new();
+    let mut parens = 0;
+
+    for c in src.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            token.push(c);
+            continue;
+        }
+
+        if !token.is_empty() {
+            prev = std::mem::replace(&mut token, String::new());
+            statement.push(prev.clone());
+        }
+
+        let top = scopes.iter().all(|transparent| *transparent);
+        match c {
+            '(' => {
+                if top && parens == 0 && !prev.is_empty() {
+                    if !KEYWORDS.contains(&&*prev) {
+                        names.push(prev.clone());
+                    }
+                }
+                parens += 1;
+            }
+            ')' => parens -= 1,
+            '{' => {
+                let first = statement.first().map(|s| &**s);
+                let transparent =
+                    matches!(first, Some("namespace") | Some("extern"));
+
+                if top && !transparent {
+                    if let [kind, name, ..] = &statement[..] {
+                        if kind == "class" || kind == "struct" {
+                            names.push(name.clone());
+                        }
+                    }
+                }
+
+                scopes.push(transparent);
+                statement.clear();
+            }
+            '}' => {
+                let _ = scopes.pop();
+                statement.clear();
+            }
+            ';' => {
+                if top
+                    && parens == 0
+                    && statement.first().map(|s| &**s) == Some("extern")
+                {
+                    names.extend(statement.last().cloned());
+                }
+                statement.clear();
+            }
+            _ => {}
+        }
+
+        if !c.is_whitespace() {
+            prev.clear();
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(feature = "generate-fresh")]
+fn make_bindings(
+    inc_dirs: &[PathBuf],
+) -> std::result::Result<syn::File, Box<dyn std::error::Error>> {
     let mut builder: Builder = builder();
+    let mut globals = Vec::new();
 
     for dir in inc_dirs {
         for header in in_dir_with_ext(dir, "h").unwrap_or_else(|e| {
-            panic!("{}: expected header files in `{}`", e, dir)
+            panic!("{}: expected header files in `{}`", e, dir.display())
         }) {
+            globals.extend(global_names(&fs::read_to_string(header.path())?));
+
+            // No `bindgen::CargoCallbacks`: these are the copies in `OUT_DIR`
+            // (which we rewrite every time we run); `main` watches the
+            // originals.
             builder = builder
-                .header::<String>(header.path().to_str().unwrap().into())
-                .parse_callbacks(Box::new(bindgen::CargoCallbacks));
+                .header::<String>(header.path().to_str().unwrap().into());
         }
     }
 
+    // We only want `LC3Tools` items (everything under `lc3::`) and the items
+    // declared in the global namespace by our headers and the grader's.
+    // Whatever they use from libc and the C++ standard library is pulled in
+    // too but the standard library types are made opaque: their layouts are
+    // all we need and their internals are what used to make the bindings huge
+    // and system specific.
+    for pattern in Some("lc3::.*".to_string()).into_iter().chain(globals) {
+        builder = builder
+            .whitelist_type(&pattern)
+            .whitelist_function(&pattern)
+            .whitelist_var(&pattern);
+    }
+
     #[rustfmt::skip]
     let res = builder
         .enable_cxx_namespaces()
         .clang_arg("-xc++")
         .clang_arg("-std=c++14")
-        .clang_arg("-Ilc3tools/backend")
+        .clang_arg(format!("-I{}", source_dir(BACKEND).display()))
 
         .derive_debug(true)
         .derive_default(true)
         .generate_comments(true)
         .rustfmt_bindings(false) // We'll run this ourselves after processing.
 
-        // .blacklist_item("std::.*")
-        // .blacklist_item(".*ignore.*")
-
-        .blacklist_item("std::value")
-        .blacklist_item("__gnu_cxx::__max")
-        .blacklist_item("__gnu_cxx::__min")
-
-        .blacklist_item("std::collate_string_type")
-        .blacklist_item("std::collate_byname_string_type")
-        .blacklist_item("std::numpunct_string_type")
-        .blacklist_item("std::numpunct_byname_string_type")
-        .blacklist_item("size_type")
-        .blacklist_item("std::size_type")
-        .blacklist_item("int_type")
-        .blacklist_item("char_type")
-        .blacklist_item("__atomic_val_t")
-        .blacklist_item("__atomic_diff_t")
-        .blacklist_item("std::__atomic_val_t")
-        .blacklist_item("std::__atomic_diff_t")
-        .blacklist_item("std::basic_ostream_sentry")
-        .blacklist_item("std::basic_istream_sentry___istream_type")
-        .blacklist_item("std::basic_istream_sentry_traits_type")
-        .blacklist_item("std::basic_istream_sentry___streambuf_type")
+        .opaque_type("std::.*")
+        .opaque_type("__gnu_cxx::.*")
+        .opaque_type("__cxxabiv1::.*")
 
         .generate()
         .expect("Unable to generate bindings!")
@@ -167,58 +484,41 @@ where
 pub mod binding_support {
     use std::collections::{HashMap, HashSet};
 
+    use quote::quote;
     pub use quote::ToTokens;
     use syn::{
         punctuated::Punctuated, token::Colon2, visit::Visit,
         visit_mut::VisitMut, Attribute, File, Ident, Item, PathSegment,
     };
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum Feature {
-        Frontend,
-        Grader,
-    }
+    /// The features an item is gated on; it's available if any of them are
+    /// enabled.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+    pub struct Feature(pub Vec<&'static str>);
 
     impl Feature {
         pub fn attrs(&self) -> Vec<Attribute> {
-            match self {
-                Feature::Grader => {
-                    let r#struct: Item = syn::parse_quote!(
-                        #[cfg(feature = "grader")]
-                        #[cfg_attr(
-                            all(docs, not(doctest)),
-                            doc(cfg(feature = "grader"))
-                        )]
-                        struct Null;
-                    );
+            let cfg = match &*self.0 {
+                [feature] => quote!(feature = #feature),
+                features => quote!(any(#(feature = #features),*)),
+            };
 
-                    match r#struct {
-                        Item::Struct(s) => s.attrs,
-                        _ => unreachable!(),
-                    }
-                }
-                Feature::Frontend => {
-                    let r#struct: Item = syn::parse_quote!(
-                        #[cfg(feature = "frontend")]
-                        #[cfg_attr(
-                            all(docs, not(doctest)),
-                            doc(cfg(feature = "frontend"))
-                        )]
-                        struct Null;
-                    );
+            let r#struct: Item = syn::parse_quote!(
+                #[cfg(#cfg)]
+                #[cfg_attr(all(docs, not(doctest)), doc(cfg(#cfg)))]
+                struct Null;
+            );
 
-                    match r#struct {
-                        Item::Struct(s) => s.attrs,
-                        _ => unreachable!(),
-                    }
-                }
+            match r#struct {
+                Item::Struct(s) => s.attrs,
+                _ => unreachable!(),
             }
         }
     }
 
     pub type Path = Punctuated<PathSegment, Colon2>;
 
-    #[derive(Debug, Eq, PartialEq, Hash)]
+    #[derive(Debug, Clone, Eq, PartialEq, Hash)]
     pub enum Element<'ast> {
         PathBased(Path),
         ValueBased(Path, &'ast Item),
@@ -365,7 +665,7 @@ pub mod binding_support {
                 | Use(syn::ItemUse { attrs, .. }) => {
                     // We want to panic if we manage to look up an item that
                     // isn't in the map.
-                    if let Some(feature) = self.map[&self.path.item(&item)] {
+                    if let Some(feature) = &self.map[&self.path.item(&item)] {
                         attrs.extend(feature.attrs())
                     }
                 }
@@ -382,7 +682,7 @@ pub mod binding_support {
                     // specific feature than their parent module have
                     // incorrect doc feature tag).
                     if let Some(feature) =
-                        self.map[&self.path.module(ident.clone())]
+                        &self.map[&self.path.module(ident.clone())]
                     {
                         attrs.extend(feature.attrs());
                     }
@@ -405,10 +705,391 @@ pub mod binding_support {
     }
 }
 
+// Rather than editing the `LC3Tools` sources in place we build a copy of them
+// (in `OUT_DIR`) with the patches in `PATCHES` applied. This is a small subset
+// of `patch -p1`: hunks have to match exactly but, like `patch`, can be found
+// at an offset from where their headers say they go.
+//
+// This lives in its own file so that `tests/patch_support.rs` can test it;
+// cargo doesn't run tests in build scripts.
+#[path = "build/patch_support.rs"]
+mod patch_support;
+
+// Helpers for the C and C++ headers we read.
+//
+// This lives in its own file so that `tests/capi_support.rs` can use it too.
+#[path = "build/header_support.rs"]
+mod header_support;
+
+// We generate typed Rust versions of some of the enums in `LC3Tools` (see
+// `src/enums.rs`). The variant names come straight from the headers and the
+// discriminants come from the `bindgen` constants so that the typed enums
+// can't silently drift from either.
+mod enum_support {
+    use std::fs;
+    use std::io::{Result, Write};
+    use std::path::Path;
+
+    use super::header_support::strip_comments;
+
+    pub struct FfiEnum {
+        /// The name of the enum in C++ (without any enclosing scopes).
+        pub cpp: &'static str,
+        /// The fully qualified name of the enum in C++.
+        pub qualified: &'static str,
+        /// The path to the type alias `bindgen` produces for the enum,
+        /// relative to `crate::root`.
+        pub binding: &'static str,
+        /// A prefix to strip off of the enumerators when naming the Rust
+        /// variants (if they have it).
+        pub strip: &'static str,
+    }
+
+    pub const ENUMS: &[FfiEnum] = &[
+        FfiEnum {
+            cpp: "PrintType",
+            qualified: "lc3::utils::PrintType",
+            binding: "lc3::utils::PrintType",
+            strip: "P_",
+        },
+        FfiEnum {
+            cpp: "PrintColor",
+            qualified: "lc3::utils::PrintColor",
+            binding: "lc3::utils::PrintColor",
+            strip: "",
+        },
+        FfiEnum {
+            cpp: "RunType",
+            qualified: "lc3::sim::RunType",
+            binding: "lc3::sim_RunType",
+            strip: "",
+        },
+        FfiEnum {
+            cpp: "EventType",
+            qualified: "lc3::core::EventType",
+            binding: "lc3::core::EventType",
+            strip: "EVENT_",
+        },
+        FfiEnum {
+            cpp: "SysCallType",
+            qualified: "lc3::core::MachineState::SysCallType",
+            binding: "lc3::core::MachineState_SysCallType",
+            strip: "",
+        },
+    ];
+
+    fn is_ident_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+
+    /// Looks for the definition of `enum (class)? <name>` in the given source
+    /// and returns its enumerators, if found.
+    fn find_enum(src: &str, name: &str) -> Option<Vec<String>> {
+        // We skip over forward declarations (i.e. `enum class Foo;`).
+        let mut rest = src;
+        while let Some(idx) = rest.find("enum") {
+            rest = &rest[idx + 4..];
+
+            let decl_end = match rest.find(|c| c == '{' || c == ';') {
+                Some(e) => e,
+                None => break,
+            };
+            let decl = &rest[..decl_end];
+            let named = decl
+                .split(|c: char| !is_ident_char(c))
+                .filter(|t| !t.is_empty())
+                .take(2)
+                .any(|t| t == name);
+
+            if !named || rest[decl_end..].starts_with(';') {
+                continue;
+            }
+
+            let body = &rest[decl_end + 1..];
+            let body = &body[..body.find('}')?];
+
+            return Some(
+                body.split(',')
+                    .map(|e| e.split('=').next().unwrap().trim())
+                    .filter(|e| !e.is_empty())
+                    .map(String::from)
+                    .collect(),
+            );
+        }
+
+        None
+    }
+
+    /// `SIM_OUTPUT` -> `SimOutput`
+    fn to_camel_case(name: &str) -> String {
+        name.split('_')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                let mut chars = s.chars();
+                let first = chars.next().unwrap().to_ascii_uppercase();
+                let rest = chars.as_str().to_ascii_lowercase();
+                format!("{}{}", first, rest)
+            })
+            .collect()
+    }
+
+    /// The enumerators of `e`, as they're spelled in the headers in
+    /// `header_dir`.
+    #[cfg(feature = "shared-lib")]
+    pub fn enumerators(header_dir: &Path, e: &FfiEnum) -> Result<Vec<String>> {
+        for header in super::in_dir_with_ext(header_dir, "h")? {
+            let src = strip_comments(&fs::read_to_string(header.path())?);
+            if let Some(variants) = find_enum(&src, e.cpp) {
+                return Ok(variants);
+            }
+        }
+
+        panic!(
+            "Couldn't find `{}` in `{}`.",
+            e.qualified,
+            header_dir.display()
+        )
+    }
+
+    pub fn generate(header_dir: &Path, out: &Path) -> Result<()> {
+        let headers = super::in_dir_with_ext(header_dir, "h")?
+            .map(|h| {
+                let path = h.path();
+                fs::read_to_string(&path).map(|s| (path, strip_comments(&s)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut f = fs::File::create(out)?;
+        writeln!(f, "// Generated by `build.rs`; do not edit!\n")?;
+        writeln!(f, "ffi_enum! {{")?;
+
+        for e in ENUMS {
+            let mut defs = headers
+                .iter()
+                .filter_map(|(p, src)| find_enum(src, e.cpp).map(|v| (p, v)));
+
+            let (path, variants) = defs.next().unwrap_or_else(|| {
+                panic!(
+                    "Couldn't find `{}` in `{}`.",
+                    e.qualified,
+                    header_dir.display()
+                )
+            });
+
+            if let Some((other, _)) = defs.next() {
+                panic!(
+                    "Found multiple definitions for `{}`: `{}` and `{}`.",
+                    e.qualified,
+                    path.display(),
+                    other.display(),
+                );
+            }
+
+            writeln!(f, "    /// Mirrors `{}`.", e.qualified)?;
+            writeln!(f, "    ///")?;
+            writeln!(
+                f,
+                "    /// Converts to and from [`{0}`](crate::root::{0}).",
+                e.binding
+            )?;
+            writeln!(
+                f,
+                "    {} ({:?}): crate::root::{} {{",
+                e.cpp, e.qualified, e.binding
+            )?;
+
+            for v in variants {
+                let stripped = if v.starts_with(e.strip) {
+                    &v[e.strip.len()..]
+                } else {
+                    &v
+                };
+
+                writeln!(f, "        /// `{}::{}`", e.qualified, v)?;
+                writeln!(
+                    f,
+                    "        {} ({:?}) = crate::root::{}_{},",
+                    to_camel_case(stripped),
+                    v,
+                    e.binding,
+                    v
+                )?;
+            }
+
+            writeln!(f, "    }}\n")?;
+        }
+
+        writeln!(f, "}}")?;
+
+        Ok(())
+    }
+}
+
+// The bindings refer to `LC3Tools` functions by (mangled) name and the linker
+// won't complain about ones that don't exist until something actually uses
+// them. With the `verify-bindings` feature we check every function in the
+// bindings against the symbols `lc3core` defines and report the ones that are
+// missing by their Rust name.
+//
+// This lives in its own file so that `tests/symbol_support.rs` can test it.
+#[cfg(feature = "verify-bindings")]
+#[path = "build/symbol_support.rs"]
+mod symbol_support;
+
+// So that C++ projects (and other `-sys` crates) can build against `lc3core`
+// with the same configuration we did, we describe it with a `pkg-config` file
+// and a CMake package.
+mod package_support {
+    use std::fmt::Write;
+    use std::fs;
+    use std::io::Result;
+    use std::path::{Path, PathBuf};
+
+    /// How to build against what we built.
+    pub struct Package {
+        pub version: String,
+        /// The root of the include tree.
+        pub include: PathBuf,
+        /// The libraries to link against, in link order, as `(directory,
+        /// name)`. The first one is always a static library we built.
+        pub libs: Vec<(PathBuf, &'static str)>,
+        /// Libraries from the system that the static libraries need.
+        pub system_libs: Vec<String>,
+        /// Compiler flags (other than defines and include directories).
+        pub flags: Vec<&'static str>,
+        pub defines: Vec<&'static str>,
+        /// The features `lc3core` was built with.
+        pub features: Vec<&'static str>,
+    }
+
+    /// CMake wants forward slashes, even on Windows.
+    fn cmake_path(path: &Path) -> String {
+        path.display().to_string().replace('\\', "/")
+    }
+
+    fn pkg_config(package: &Package) -> String {
+        let mut cflags = format!("-I{}", package.include.display());
+        for flag in &package.flags {
+            write!(cflags, " {}", flag).unwrap();
+        }
+        for define in &package.defines {
+            write!(cflags, " -D{}", define).unwrap();
+        }
+
+        let libs = package
+            .libs
+            .iter()
+            .map(|(dir, name)| format!("-L{} -l{}", dir.display(), name))
+            .collect::<Vec<_>>();
+        let system_libs = package
+            .system_libs
+            .iter()
+            .map(|l| format!("-l{}", l))
+            .collect::<Vec<_>>();
+
+        let mut pc = String::new();
+        writeln!(pc, "# Generated by `build.rs` in `lc3tools-sys`.\n").unwrap();
+        writeln!(pc, "features={}\n", package.features.join(" ")).unwrap();
+        writeln!(pc, "Name: lc3core").unwrap();
+        writeln!(pc, "Description: LC3Tools, as built by lc3tools-sys")
+            .unwrap();
+        writeln!(pc, "Version: {}", package.version).unwrap();
+        writeln!(pc, "Cflags: {}", cflags).unwrap();
+        writeln!(pc, "Libs: {}", libs.join(" ")).unwrap();
+        writeln!(pc, "Libs.private: {}", system_libs.join(" ")).unwrap();
+        pc
+    }
+
+    fn cmake(package: &Package) -> String {
+        // `lc3core::lc3core` is the first library; the rest (a prebuilt
+        // `lc3core` when the first one is just our sources) and the system
+        // libraries are its dependencies.
+        let (dir, name) = &package.libs[0];
+        let location = dir.join(super::static_lib(name));
+
+        let mut link = Vec::new();
+        for (dir, name) in &package.libs[1..] {
+            link.push(format!("-L{}", cmake_path(dir)));
+            link.push(name.to_string());
+        }
+        link.extend(package.system_libs.iter().cloned());
+        link.push("Threads::Threads".to_string());
+
+        let mut c = String::new();
+        writeln!(c, "# Generated by `build.rs` in `lc3tools-sys`.\n").unwrap();
+        writeln!(c, "include(CMakeFindDependencyMacro)").unwrap();
+        writeln!(c, "find_dependency(Threads)\n").unwrap();
+        writeln!(c, "set(lc3core_VERSION \"{}\")", package.version).unwrap();
+        writeln!(
+            c,
+            "set(lc3core_FEATURES \"{}\")\n",
+            package.features.join(";")
+        )
+        .unwrap();
+        writeln!(c, "if(NOT TARGET lc3core::lc3core)").unwrap();
+        writeln!(c, "    add_library(lc3core::lc3core STATIC IMPORTED)")
+            .unwrap();
+        writeln!(c, "    set_target_properties(lc3core::lc3core PROPERTIES")
+            .unwrap();
+        for (property, value) in &[
+            ("IMPORTED_LOCATION", cmake_path(&location)),
+            (
+                "INTERFACE_INCLUDE_DIRECTORIES",
+                cmake_path(&package.include),
+            ),
+            ("INTERFACE_COMPILE_DEFINITIONS", package.defines.join(";")),
+            ("INTERFACE_COMPILE_OPTIONS", package.flags.join(";")),
+            ("INTERFACE_LINK_LIBRARIES", link.join(";")),
+        ] {
+            writeln!(c, "        {} \"{}\"", property, value).unwrap();
+        }
+        writeln!(c, "    )").unwrap();
+        writeln!(c, "endif()").unwrap();
+        c
+    }
+
+    /// Writes `lc3core.pc` to `pkg_config_dir` and `lc3coreConfig.cmake` to
+    /// `cmake_dir`.
+    pub fn write(
+        package: &Package,
+        pkg_config_dir: &Path,
+        cmake_dir: &Path,
+    ) -> Result<()> {
+        fs::create_dir_all(pkg_config_dir)?;
+        fs::create_dir_all(cmake_dir)?;
+
+        fs::write(pkg_config_dir.join("lc3core.pc"), pkg_config(package))?;
+        fs::write(cmake_dir.join("lc3coreConfig.cmake"), cmake(package))?;
+
+        Ok(())
+    }
+}
+
+// With the `shared-lib` feature we also build a shared library for non-Rust
+// hosts. It exports the C API (`extra/bindings.h` and friends) and nothing
+// else, under versioned names (`lc3core_v1_new_sim`) that won't collide with
+// anything and that let an incompatible version of a function sit alongside
+// the old one. `lc3core.h` is generated from the same headers so that it
+// can't drift from the library.
+//
+// This lives in its own file so that `tests/capi_support.rs` can test it.
+#[cfg(feature = "shared-lib")]
+#[path = "build/capi_support.rs"]
+mod capi_support;
+
 fn main() -> Result<()> {
+    // `LC3TOOLS_DIR` points at an `LC3Tools` source tree to use instead of the
+    // vendored one (i.e. a fork) and `LC3TOOLS_LIB_DIR` at a directory with a
+    // prebuilt `lc3core` library to link against instead of compiling the
+    // `LC3Tools` sources ourselves.
+    println!("cargo:rerun-if-env-changed=LC3TOOLS_DIR");
+    println!("cargo:rerun-if-env-changed=LC3TOOLS_LIB_DIR");
+    let external = env::var_os("LC3TOOLS_DIR").is_some();
+    let lib_dir = env::var_os("LC3TOOLS_LIB_DIR").map(PathBuf::from);
+
     // For path/git deps (when grabbing from crates.io lc3tools will be rolled
     // into the package).
-    if !Path::new(BACKEND).exists() {
+    if !external && !Path::new(BACKEND).exists() {
         let exit_code = Command::new("git")
             .args(&["submodule", "update", "--init"])
             .status()?;
@@ -433,69 +1114,193 @@ fn main() -> Result<()> {
         env!("CARGO_PKG_VERSION_PATCH"),
     );
 
-    let lc3tools_package_json = Path::new("lc3tools/frontend/gui/package.json");
-    let lc3tools_package_json = File::open(lc3tools_package_json)
-        .expect("`package.json` in lc3tools/frontend/gui");
-    let reader = BufReader::new(lc3tools_package_json);
-    let package_json = serde_json::from_reader::<_, Value>(reader)
-        .expect("A valid `package.json`");
-    let lc3tools_version = &package_json["version"];
-
-    eq!(crate_version, lc3tools_version.as_str().unwrap());
+    let lc3tools_package_json = resolve("lc3tools/frontend/gui/package.json");
+    if external {
+        // Forks get some leeway: we'll try to build them regardless.
+        let lc3tools_version = File::open(&lc3tools_package_json)
+            .ok()
+            .and_then(|f| {
+                serde_json::from_reader::<_, Value>(BufReader::new(f)).ok()
+            })
+            .and_then(|json| json["version"].as_str().map(String::from));
+
+        match lc3tools_version {
+            Some(v) if v == crate_version => {}
+            Some(v) => println!(
+                "cargo:warning=`LC3TOOLS_DIR` has `LC3Tools` {}; this crate \
+                expects {}.",
+                v, crate_version,
+            ),
+            None => println!(
+                "cargo:warning=Couldn't find the `LC3Tools` version in `{}`; \
+                this crate expects {}.",
+                lc3tools_package_json.display(),
+                crate_version,
+            ),
+        }
+    } else {
+        let lc3tools_package_json = File::open(lc3tools_package_json)
+            .expect("`package.json` in lc3tools/frontend/gui");
+        let reader = BufReader::new(lc3tools_package_json);
+        let package_json = serde_json::from_reader::<_, Value>(reader)
+            .expect("A valid `package.json`");
+        let lc3tools_version = &package_json["version"];
+
+        eq!(crate_version, lc3tools_version.as_str().unwrap());
+    }
 
     // First, lets gather and copy over the header files.
     let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
     let include = out.join("include");
 
-    // TODO: right now this does not check if there are two header files with
-    // the same name. As of this writing, all the header files in lc3tools have
-    // unique names but if this were to change, we'd lose header files in the
-    // generated output without any warning.
-    copy_headers(BACKEND, &include)?;
-    copy_headers(EXTRAS, &include)?;
-    if cfg!(feature = "grader") {
-        copy_headers(GRADER, &include)?
+    let components = COMPONENTS
+        .iter()
+        .filter(|c| c.enabled())
+        .collect::<Vec<_>>();
+    let dirs = || components.iter().flat_map(|c| c.paths());
+
+    // Before that, we copy the `LC3Tools` sources into `OUT_DIR` and patch
+    // them:
+    let lc3tools = out.join(LC3TOOLS);
+    if lc3tools.exists() {
+        fs::remove_dir_all(&lc3tools)?;
     }
-    if cfg!(feature = "frontend") {
-        copy_headers(FRONTEND, &include)?
+
+    for dir in components.iter().flat_map(|c| c.dirs.iter()) {
+        watch(&resolve(dir))?;
+        if !in_lc3tools(dir) {
+            continue;
+        }
+
+        let copy = source_dir(dir);
+        fs::create_dir_all(&copy)?;
+        for entry in fs::read_dir(resolve(dir))? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::copy(entry.path(), copy.join(entry.file_name()))?;
+            }
+        }
+    }
+
+    println!("cargo:rerun-if-changed={}", PATCHES);
+    println!("cargo:rerun-if-env-changed=LC3TOOLS_PATCH_DIR");
+    let patch_dirs = Some(PathBuf::from(PATCHES))
+        .into_iter()
+        .chain(env::var_os("LC3TOOLS_PATCH_DIR").map(PathBuf::from));
+
+    for patch_dir in patch_dirs {
+        let mut patches = in_dir_with_ext(&patch_dir, "patch")?
+            .map(|p| p.path())
+            .collect::<Vec<_>>();
+        patches.sort();
+
+        for patch in patches {
+            println!("cargo:rerun-if-changed={}", patch.display());
+
+            let diff = fs::read_to_string(&patch)?;
+            let files = patch_support::parse(&diff)
+                .unwrap_or_else(|e| panic!("`{}`: {}", patch.display(), e));
+
+            for file in files {
+                let target = lc3tools.join(&file.path);
+
+                // Patches to components we aren't building are skipped but
+                // patches to files that don't exist at all are an error.
+                if !target.exists() {
+                    assert!(
+                        resolve(LC3TOOLS).join(&file.path).exists(),
+                        "`{}` patches `{}`, which doesn't exist",
+                        patch.display(),
+                        file.path.display(),
+                    );
+                    continue;
+                }
+
+                match patch_support::apply(&fs::read_to_string(&target)?, &file)
+                {
+                    Ok(Some(patched)) => fs::write(&target, patched)?,
+                    Ok(None) => println!(
+                        "cargo:warning=`{}` already has `{}`; skipping it",
+                        file.path.display(),
+                        patch.display(),
+                    ),
+                    Err(e) => panic!(
+                        "`{}` doesn't apply to `{}`: {}",
+                        patch.display(),
+                        file.path.display(),
+                        e,
+                    ),
+                }
+            }
+        }
+    }
+
+    let mut include_tree = IncludeTree::new(include.clone())?;
+    for dir in components.iter().flat_map(|c| c.dirs.iter()) {
+        include_tree.add(dir)?;
+    }
+    if cfg!(feature = "cxx-bridge") {
+        watch(Path::new(BRIDGE))?;
+        include_tree.add(BRIDGE)?
     }
 
     // TODO: is `canonicalize` actually broken? (rust#42869)
     println!("cargo:include={}", include.canonicalize()?.display());
 
+    // Typed versions of some of the `LC3Tools` enums (see `src/enums.rs`):
+    enum_support::generate(&source_dir(BACKEND), &out.join("enums.rs"))?;
+
     // Next, let's do bindgen, if we're asked to.
     #[cfg(feature = "generate-fresh")]
     {
+        use std::collections::HashSet;
         use std::io::Write;
 
         use binding_support::{elements, tag, Feature, Map, ToTokens};
 
-        // First we want to get the baseline bindings — just the backend, no
-        // other features — and record what items this has.
-        let backend = make_bindings(&[BACKEND, EXTRAS]).unwrap();
-
-        // Next, the bindings for the frontend and then for the grader + the
-        // frontend (the grader requires the frontend).
-        let frontend = make_bindings(&[BACKEND, EXTRAS, FRONTEND]).unwrap();
-        let grader =
-            make_bindings(&[BACKEND, EXTRAS, FRONTEND, GRADER]).unwrap();
+        // First we want to get the baseline bindings — just the components
+        // that are always built — and record what items this has.
+        let base_dirs = COMPONENTS
+            .iter()
+            .filter(|c| c.feature.is_none())
+            .flat_map(|c| c.paths())
+            .collect::<Vec<_>>();
+        let base = make_bindings(&base_dirs).unwrap();
+
+        // Next, the bindings for each of the other components (along with the
+        // components they require).
+        let gated = COMPONENTS
+            .iter()
+            .filter(|c| c.feature.is_some())
+            .collect::<Vec<_>>();
+        let bindings = gated
+            .iter()
+            .map(|c| make_bindings(&c.closure()).unwrap())
+            .collect::<Vec<_>>();
+
+        // And all of them together; this is what we actually emit. (If one of
+        // the components needs all the others we've already got these.)
+        let all_dirs = COMPONENTS
+            .iter()
+            .flat_map(|c| c.paths())
+            .collect::<Vec<_>>();
+        let full = match gated.iter().position(|c| c.closure() == all_dirs) {
+            Some(idx) => bindings[idx].clone(),
+            None => make_bindings(&all_dirs).unwrap(),
+        };
 
         // For each of the above configurations, get the set of elements:
-        let mut backend_elements = elements(&backend);
-        let mut frontend_elements = elements(&frontend);
-        let mut grader_elements = elements(&grader);
-
-        // And then assemble the attribute map.
-        let mut map = Map::with_capacity(grader_elements.len());
-        map.extend(backend_elements.drain().map(|k| (k, None)));
+        let base_elements = elements(&base);
+        let gated_elements =
+            bindings.iter().map(|b| elements(b)).collect::<Vec<_>>();
+        let full_elements = elements(&full);
 
         macro_rules! superset {
-            ($baseset:ident is within $superset:ident) => {
+            ($baseset:expr, is within $superset:expr, $what:expr) => {
                 if !$superset.is_superset(&$baseset) {
                     panic!(
-                        "{} is not a superset of {}: {}",
-                        core::stringify!($superset),
-                        core::stringify!($baseset),
+                        "{} is missing items: {}",
+                        $what,
                         $baseset
                             .difference(&$superset)
                             .map(|e| {
@@ -511,20 +1316,52 @@ fn main() -> Result<()> {
             };
         }
 
-        superset!(backend_elements is within frontend_elements);
-        superset!(frontend_elements is within grader_elements);
+        // And then assemble the attribute map: items in the base bindings
+        // don't need a feature and the rest are gated on the features of the
+        // components that introduce them (i.e. whose bindings have them but
+        // whose requirements' bindings don't).
+        let mut map = Map::with_capacity(full_elements.len());
+        map.extend(base_elements.iter().cloned().map(|k| (k, None)));
+
+        for (component, elements) in gated.iter().zip(&gated_elements) {
+            let feature = component.feature.unwrap();
+            superset!(base_elements, is within elements, feature);
+
+            let required = gated
+                .iter()
+                .zip(&gated_elements)
+                .filter(|(c, _)| {
+                    component.requires.contains(&c.feature.unwrap())
+                })
+                .map(|(_, e)| e)
+                .collect::<Vec<_>>();
+
+            for r in &required {
+                superset!(r, is within elements, feature);
+            }
 
-        for element in frontend_elements.drain() {
-            map.entry(element).or_insert(Some(Feature::Frontend));
-        }
+            for element in elements {
+                if base_elements.contains(element)
+                    || required.iter().any(|r| r.contains(element))
+                {
+                    continue;
+                }
 
-        for element in grader_elements.drain() {
-            map.entry(element).or_insert(Some(Feature::Grader));
+                map.entry(element.clone())
+                    .or_insert_with(|| Some(Feature::default()))
+                    .as_mut()
+                    .unwrap()
+                    .0
+                    .push(feature);
+            }
         }
 
+        let tagged: HashSet<_> = map.keys().cloned().collect();
+        superset!(full_elements, is within tagged, "the component bindings");
+
         // Finally, tag the full binding set with the appropriate attrs, fmt,
         // and emit.
-        let mut full = grader.clone();
+        let mut full = full.clone();
         tag(&mut full, map);
 
         let ts = full.into_token_stream();
@@ -541,7 +1378,7 @@ fn main() -> Result<()> {
     // `cc` automatically handles `OPT_LEVEL` and `DEBUG`.
     // `cc` also handles `fPIC`
 
-    if cfg!(windows) {
+    if msvc() {
         build.flag("/EHsc").flag("/std:c++latest");
     }
 
@@ -577,105 +1414,369 @@ fn main() -> Result<()> {
         build.define("_ENABLE_DEBUG", None);
     }
 
-    // Patch the load memory logic to actually free the memory it allocates:
+    // Includes:
+    for dir in dirs() {
+        build.include(dir);
+    }
+
+    // Collecting files (with `LC3TOOLS_LIB_DIR`, just ours):
+    let cpp_dir_iter = |dir: PathBuf| {
+        in_dir_with_ext(&dir, "cpp").unwrap_or_else(|e| {
+            panic!("{}: expected source files in `{}`", e, dir.display())
+        })
+    };
+
+    let sources = components
+        .iter()
+        .flat_map(|c| c.dirs.iter())
+        .filter(|d| lib_dir.is_none() || !in_lc3tools(d))
+        .map(|d| source_dir(d));
+
+    for source_file in sources.flat_map(cpp_dir_iter) {
+        build.file(source_file.path());
+    }
+
+    // The `cxx` bridge gets its own `Build` (`cxx_build` makes one for the code
+    // it generates) and its own library. This has to be compiled before
+    // `lc3core` so that it comes first on the link line; it depends on
+    // `lc3core`, not the other way around.
+    #[cfg(feature = "cxx-bridge")]
+    {
+        let bridge_source = Path::new(BRIDGE).join("bridge.cpp");
+        let mut bridge = cxx_build::bridge("src/bridge.rs");
+
+        if msvc() {
+            bridge.flag("/EHsc").flag("/std:c++latest");
+        }
+        if cfg!(feature = "lto") {
+            bridge.flag_if_supported("-flto=thin");
+        }
+        // Has to match `lc3core`; some of the `LC3Tools` classes look
+        // different in debug builds.
+        if env!("PROFILE") == "debug" {
+            bridge.define("_ENABLE_DEBUG", None);
+        }
+
+        bridge
+            .flag_if_supported("-std=c++14")
+            .warnings(true)
+            .include(source_dir(BACKEND))
+            .include(EXTRAS)
+            .include(BRIDGE)
+            .file(&bridge_source)
+            .out_dir(out.join("bridge"))
+            .compile("lc3bridge");
+
+        println!("cargo:rerun-if-changed=src/bridge.rs");
+        println!("cargo:rerun-if-changed={}", bridge_source.display());
+    }
+
+    // And finally, the build:
+    // `cc` automatically tells cargo to link to this statically.
     //
-    // TODO: Since chiragsakhuja/lc3tools#12 was merged, remove this once
-    // there's a new release.
+    // When we're using a prebuilt `lc3core` our sources go in their own
+    // library which has to come first on the link line.
+    let lib = if lib_dir.is_some() {
+        "lc3extra"
+    } else {
+        "lc3core"
+    };
+    build.out_dir(out.join("build")).compile(lib);
+    println!("cargo:root={}", out.display());
+
+    if let Some(lib_dir) = &lib_dir {
+        let kind = if lib_dir.join(static_lib("lc3core")).exists() {
+            "static="
+        } else {
+            ""
+        };
+
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        println!("cargo:rustc-link-lib={}lc3core", kind);
+    }
+
+    // The shared library for non-Rust hosts (`DEP_LC3CORE_SHARED`); see
+    // `capi_support`. The exported functions are compiled on their own and
+    // linked, along with all of `lc3core`, by hand since `cc` only makes
+    // static libraries.
+    #[cfg(feature = "shared-lib")]
     {
-        use std::io::{Read, Write};
-        let path: PathBuf = PathBuf::from(&BACKEND).join("mem.cpp");
-
-        if let Ok(mut file) = File::open(&path) {
-            let mut contents =
-                String::with_capacity(file.metadata().unwrap().len() as usize);
-            file.read_to_string(&mut contents).unwrap();
-
-            if contents.contains("#endif\n        }")
-                || contents.contains("#endif\r\n        }")
-            {
-                drop(file);
-                let mut file = File::create(path).unwrap();
-
-                write!(
-                    file,
-                    "{}",
-                    contents
-                        .replace(
-                            "#endif\n        }",
-                            "#endif\n            delete[] chars;\n        }"
-                        )
-                        .replace(
-                            "#endif\r\n        }",
-                            "#endif\r\n            delete[] chars;\r\n        }"
-                        )
-                )
-                .unwrap()
+        use capi_support::Function;
+
+        let target = env!("TARGET");
+        let shared = out.join("shared");
+        fs::create_dir_all(&shared)?;
+
+        let mut headers = Vec::new();
+        let mut includes = Vec::new();
+        for (dir, header) in C_API {
+            if components.iter().any(|c| c.dirs.contains(dir)) {
+                headers.push(fs::read_to_string(Path::new(dir).join(header))?);
+                includes.push(*header);
             }
         }
+        let functions = headers
+            .iter()
+            .flat_map(|h| capi_support::functions(h))
+            .collect::<Vec<Function>>();
+        let print_types = enum_support::enumerators(
+            &source_dir(BACKEND),
+            &enum_support::ENUMS[0],
+        )?;
+
+        fs::write(
+            shared.join("lc3core.h"),
+            capi_support::header(&headers, &functions, &print_types),
+        )?;
+        let wrappers = out.join("capi.cpp");
+        fs::write(
+            &wrappers,
+            capi_support::wrappers(&includes, &functions, &print_types),
+        )?;
+
+        let mut capi = Build::new();
+        if msvc() {
+            capi.flag("/EHsc").flag("/std:c++latest");
+        }
+        if cfg!(feature = "lto") {
+            capi.flag_if_supported("-flto=thin");
+        }
+        if env!("PROFILE") == "debug" {
+            capi.define("_ENABLE_DEBUG", None);
+        }
+        for dir in dirs() {
+            capi.include(dir);
+        }
+        capi.flag_if_supported("-std=c++14")
+            .cargo_metadata(false)
+            .warnings(true)
+            .cpp(true)
+            .file(&wrappers)
+            .out_dir(out.join("capi"))
+            .compile("lc3capi");
+
+        // Everything in `lc3core` goes into the library; the exported
+        // functions are the only things that are visible.
+        let capi_lib = out.join("capi").join(static_lib("lc3capi"));
+        let mut archives = vec![out.join("build").join(static_lib(lib))];
+        if let Some(lib_dir) = &lib_dir {
+            let prebuilt = lib_dir.join(static_lib("lc3core"));
+            assert!(
+                prebuilt.exists(),
+                "`shared-lib` needs a static `lc3core` in `LC3TOOLS_LIB_DIR`",
+            );
+            archives.push(prebuilt);
+        }
+
+        let mut link = capi.get_compiler().to_command();
+        let library = if target.contains("msvc") {
+            let def = out.join("lc3core.def");
+            fs::write(&def, capi_support::def_file(&functions))?;
+
+            let dll = shared.join("lc3core.dll");
+            link.arg("/LD")
+                .arg(format!("/Fe{}", dll.display()))
+                .arg(&capi_lib)
+                .args(&archives)
+                .arg("/link")
+                .arg(format!("/DEF:{}", def.display()))
+                .arg(format!("/WHOLEARCHIVE:{}", capi_lib.display()));
+            dll
+        } else if target.contains("apple") {
+            let list = out.join("lc3core.exports");
+            fs::write(&list, capi_support::exported_symbols_list(&functions))?;
+
+            let dylib = shared.join("liblc3core.dylib");
+            link.arg("-dynamiclib")
+                .arg("-o")
+                .arg(&dylib)
+                .arg("-Wl,-install_name,@rpath/liblc3core.dylib")
+                .arg(format!("-Wl,-exported_symbols_list,{}", list.display()))
+                .arg(format!("-Wl,-force_load,{}", capi_lib.display()))
+                .args(&archives);
+            dylib
+        } else {
+            // (On Windows `dllexport` already limits what's exported.)
+            let library = if target.contains("windows") {
+                shared.join("lc3core.dll")
+            } else {
+                let script = out.join("lc3core.map");
+                fs::write(&script, capi_support::version_script(&functions))?;
+                link.arg(format!("-Wl,--version-script,{}", script.display()))
+                    .arg("-pthread");
+
+                shared.join("liblc3core.so")
+            };
+
+            link.arg("-shared")
+                .arg("-o")
+                .arg(&library)
+                .arg("-Wl,--whole-archive")
+                .arg(&capi_lib)
+                .arg("-Wl,--no-whole-archive")
+                .args(&archives);
+            library
+        };
+        if cfg!(feature = "lto") && !target.contains("msvc") {
+            link.arg("-flto=thin").arg("-fuse-ld=lld");
+        }
+
+        let status = link.status()?;
+        assert!(
+            status.success(),
+            "Failed to link `{}`: {:?}",
+            library.display(),
+            link,
+        );
+
+        println!("cargo:shared={}", shared.display());
+        // For `tests/shared_lib.rs`.
+        println!("cargo:rustc-env=LC3CORE_SHARED_DIR={}", shared.display());
     }
 
-    // Remove `main` from framework.cpp if it's there and we're building with
-    // the grader feature.
-    if cfg!(feature = "grader") {
-        use std::io::{Read, Write};
-        let path = PathBuf::from(&GRADER).join("framework.cpp");
-
-        if let Ok(mut file) = File::open(&path) {
-            let mut contents =
-                String::with_capacity(file.metadata().unwrap().len() as usize);
-            file.read_to_string(&mut contents).unwrap();
-
-            if contents.contains("int main(") {
-                drop(file);
-                let mut file = File::create(path).unwrap();
-
-                write!(
-                    file,
-                    "{}",
-                    contents
-                        .replace("int main(", "/* int framework_main(")
-                        .replace("return 0;\n}", "return 0;\n} */")
-                        // Windows..
-                        // Even though the original file uses Unix line endings
-                        // windows git will (by default, I think) convert them.
-                        .replace("return 0;\r\n}", "return 0;\r\n} */")
-                )
-                .unwrap()
+    // Describe what we built for C++ projects and other `-sys` crates
+    // (`DEP_LC3CORE_PKGCONFIG` and `DEP_LC3CORE_CMAKE`). The flags and defines
+    // mirror the ones `lc3core` was built with above.
+    {
+        let target = env!("TARGET");
+
+        let mut flags = if msvc() {
+            vec!["/EHsc", "/std:c++latest"]
+        } else {
+            vec!["-std=c++14"]
+        };
+        if cfg!(feature = "lto") {
+            flags.push("-flto=thin");
+        }
+
+        let mut defines = Vec::new();
+        if env!("PROFILE") == "debug" {
+            defines.push("_ENABLE_DEBUG");
+        }
+
+        let mut libs = vec![(out.join("build"), lib)];
+        if let Some(lib_dir) = &lib_dir {
+            libs.push((lib_dir.clone(), "lc3core"));
+        }
+
+        // The same C++ standard library `cc` links against.
+        let mut system_libs = Vec::new();
+        if let Ok(stdlib) = env::var("CXXSTDLIB") {
+            if !stdlib.is_empty() {
+                system_libs.push(stdlib);
             }
+        } else if target.contains("apple")
+            || target.contains("freebsd")
+            || target.contains("openbsd")
+        {
+            system_libs.push("c++".to_string());
+        } else if target.contains("android") {
+            system_libs.push("c++_shared".to_string());
+        } else if !target.contains("msvc") {
+            system_libs.push("stdc++".to_string());
+        }
+        // The simulator can take input on a separate thread.
+        if target.contains("linux") || target.contains("bsd") {
+            system_libs.push("pthread".to_string());
         }
-    }
 
-    // Includes:
-    build.include(BACKEND).include(EXTRAS);
-    if cfg!(feature = "grader") {
-        build.include(GRADER);
-    }
-    if cfg!(feature = "frontend") {
-        build.include(FRONTEND);
+        let package = package_support::Package {
+            version: env!("CARGO_PKG_VERSION"),
+            include: include.clone(),
+            libs,
+            system_libs,
+            flags,
+            defines,
+            features: components.iter().filter_map(|c| c.feature).collect(),
+        };
+
+        let (pkg_config, cmake) = (out.join("pkgconfig"), out.join("cmake"));
+        package_support::write(&package, &pkg_config, &cmake)?;
+
+        println!("cargo:pkgconfig={}", pkg_config.display());
+        println!("cargo:cmake={}", cmake.display());
     }
 
-    // Collecting files:
-    let cpp_dir_iter = |dir| {
-        in_dir_with_ext(dir, "cpp").unwrap_or_else(|e| {
-            panic!("{}: expected source files in `{}`", e, dir)
-        })
-    };
+    #[cfg(feature = "verify-bindings")]
+    {
+        use std::collections::HashSet;
+        use std::fmt::Write;
 
-    let files = cpp_dir_iter(BACKEND).chain(cpp_dir_iter(EXTRAS));
-    #[cfg(feature = "grader")]
-    let files = files.chain(cpp_dir_iter(GRADER));
-    #[cfg(feature = "frontend")]
-    let files = files.chain(cpp_dir_iter(FRONTEND));
+        let mut archives = vec![out.join("build").join(static_lib(lib))];
+        if let Some(lib_dir) = &lib_dir {
+            archives.push(lib_dir.join(static_lib("lc3core")));
+        }
 
-    for source_file in files {
-        println!("cargo:rerun-if-changed={}", source_file.path().display());
-        build.file(source_file.path());
-    }
+        // Mach-O symbols get a leading underscore.
+        let prefix = match &*env!("CARGO_CFG_TARGET_OS") {
+            "macos" | "ios" => "_",
+            _ => "",
+        };
 
-    // And finally, the build:
-    // `cc` automatically tells cargo to link to this statically.
-    build.out_dir(out.join("build")).compile("lc3core");
-    println!("cargo:root={}", out.display());
+        let mut c_headers = String::new();
+        for dir in &[EXTRAS, EXTRAS_FRONTEND] {
+            for header in in_dir_with_ext(dir, "h")? {
+                c_headers.push_str(&fs::read_to_string(header.path())?);
+            }
+        }
+
+        let bindings = fs::read_to_string(BINDINGS_PATH)?;
+        let functions = symbol_support::functions(
+            &bindings,
+            &c_headers,
+            feature_enabled,
+            prefix,
+        );
+        // (A prebuilt `lc3core` might be a shared library, which we can't
+        // read; asking to verify the bindings against one is an error.)
+        let mut defined = HashSet::new();
+        for archive in &archives {
+            match symbol_support::archive_symbols(archive) {
+                Ok(symbols) => defined.extend(symbols),
+                Err(e) => panic!(
+                    "`verify-bindings` couldn't read the symbols in `{}`: {}",
+                    archive.display(),
+                    e,
+                ),
+            }
+        }
+
+        let missing = functions
+            .iter()
+            .filter(|f| !defined.contains(&f.symbol))
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            let report_path = out.join("missing-symbols.txt");
+            let mut report = String::new();
+            for f in &missing {
+                writeln!(report, "{} ({})", f.path, f.symbol).unwrap();
+            }
+            fs::write(&report_path, &report)?;
+
+            let summary = format!(
+                "{} of the {} functions in the bindings aren't defined by \
+                `lc3core` and won't link if used (full list in `{}`)",
+                missing.len(),
+                functions.len(),
+                report_path.display(),
+            );
+            if env::var_os("LC3TOOLS_DENY_MISSING_SYMBOLS").is_some() {
+                panic!("{}:\n{}", summary, report);
+            }
+
+            println!("cargo:warning={}:", summary);
+            for line in report.lines().take(20) {
+                println!("cargo:warning=  {}", line);
+            }
+            if missing.len() > 20 {
+                println!("cargo:warning=  ... and {} more", missing.len() - 20);
+            }
+        }
+
+        println!("cargo:rerun-if-changed={}", BINDINGS_PATH);
+        println!("cargo:rerun-if-env-changed=LC3TOOLS_DENY_MISSING_SYMBOLS");
+    }
 
     // Since `cc` doesn't: https://github.com/alexcrichton/cc-rs/issues/413
     println!("cargo:rerun-if-env-changed=CC");
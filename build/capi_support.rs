@@ -0,0 +1,321 @@
+use std::fmt::Write;
+
+use super::header_support::strip_comments;
+
+/// Prepended to the names of the functions the shared library exports.
+///
+/// Bump this (and `VERSION_NODE`) when a function changes in a way that
+/// isn't backwards compatible.
+pub const PREFIX: &str = "lc3core_v1_";
+
+/// The ELF symbol version the exported functions get.
+const VERSION_NODE: &str = "LC3CORE_1";
+
+/// The C++ types in the C API and the C types that stand in for them in
+/// `lc3core.h`.
+const TYPES: &[(&str, &str)] = &[
+    ("lc3::sim", "lc3core_sim"),
+    ("lc3::utils::IPrinter", "lc3core_printer"),
+    ("lc3::utils::IInputter", "lc3core_inputter"),
+    ("lc3::utils::PrintType", "lc3core_print_type"),
+    ("ProgramWordCallback", "lc3core_program_word_callback"),
+    ("ProgramSymbolCallback", "lc3core_program_symbol_callback"),
+    (
+        "ProgramStatementCallback",
+        "lc3core_program_statement_callback",
+    ),
+    ("State", "lc3core_state"),
+];
+
+/// A function in the C API.
+pub struct Function {
+    pub name: String,
+    ret: String,
+    params: String,
+    args: Vec<String>,
+}
+
+impl Function {
+    pub fn exported(&self) -> String {
+        format!("{}{}", PREFIX, self.name)
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Replaces the occurrences of `from` in `src` that aren't part of a
+/// larger (possibly qualified) name.
+fn replace_word(src: &str, from: &str, to: &str) -> String {
+    let boundary =
+        |c: Option<char>| c.map_or(true, |c| !is_ident_char(c) && c != ':');
+
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    while let Some(idx) = rest.find(from) {
+        let end = idx + from.len();
+        let whole = boundary(rest[..idx].chars().next_back())
+            && boundary(rest[end..].chars().next());
+
+        out.push_str(&rest[..idx]);
+        out.push_str(if whole { to } else { from });
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// The contents of a header's `extern "C" { ... }` block.
+fn extern_block(header: &str) -> &str {
+    let start = header
+        .find("extern \"C\" {")
+        .map(|i| i + "extern \"C\" {".len())
+        .expect("an `extern \"C\"` block");
+    let end = header.rfind('}').unwrap();
+
+    &header[start..end]
+}
+
+/// The names of the parameters in a parameter list.
+fn arg_names(params: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (idx, c) in params.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(&params[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&params[start..]);
+
+    split
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty() && *p != "void")
+        .map(|p| {
+            // `void (*func)(unsigned char)` or `unsigned char buffer[]`:
+            let p = match p.find("(*") {
+                Some(idx) => p[idx + 2..].split(')').next().unwrap(),
+                None => p.split('[').next().unwrap(),
+            };
+
+            p.trim_end()
+                .rsplit(|c| !is_ident_char(c))
+                .next()
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+/// The functions declared in a C API header.
+pub fn functions(header: &str) -> Vec<Function> {
+    let header = strip_comments(header);
+
+    extern_block(&header)
+        .split(';')
+        .map(str::trim)
+        .filter(|d| !d.starts_with("typedef") && d.contains('('))
+        .map(|decl| {
+            let open = decl.find('(').unwrap();
+            let close = decl.rfind(')').unwrap();
+
+            let before = decl[..open].trim_end();
+            let name_start =
+                before.rfind(|c| !is_ident_char(c)).map_or(0, |i| i + 1);
+            let params = decl[open + 1..close]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            Function {
+                name: before[name_start..].to_string(),
+                ret: before[..name_start].trim().to_string(),
+                args: arg_names(&params),
+                params,
+            }
+        })
+        .collect()
+}
+
+/// `P_SIM_OUTPUT` -> `LC3CORE_PRINT_SIM_OUTPUT`
+fn print_type(enumerator: &str) -> String {
+    let stripped = if enumerator.starts_with("P_") {
+        &enumerator[2..]
+    } else {
+        enumerator
+    };
+
+    format!("LC3CORE_PRINT_{}", stripped)
+}
+
+/// `lc3core.h`: the C API headers with C types and the exported names.
+///
+/// `print_types` are the enumerators of `lc3::utils::PrintType`.
+pub fn header(
+    headers: &[String],
+    functions: &[Function],
+    print_types: &[String],
+) -> String {
+    let mut h = String::new();
+    writeln!(h, "/* Generated by `build.rs` in `lc3tools-sys`. */\n").unwrap();
+    writeln!(h, "#ifndef LC3CORE_H").unwrap();
+    writeln!(h, "#define LC3CORE_H\n").unwrap();
+    writeln!(h, "#include <stdbool.h>").unwrap();
+    writeln!(h, "#include <stddef.h>").unwrap();
+    writeln!(h, "#include <stdint.h>\n").unwrap();
+    writeln!(h, "#ifdef __cplusplus").unwrap();
+    writeln!(h, "extern \"C\" {{").unwrap();
+    writeln!(h, "#endif\n").unwrap();
+
+    writeln!(h, "/// An `lc3::sim`.").unwrap();
+    writeln!(h, "typedef struct lc3core_sim lc3core_sim;").unwrap();
+    writeln!(h, "/// An `lc3::utils::IPrinter`.").unwrap();
+    writeln!(h, "typedef struct lc3core_printer lc3core_printer;").unwrap();
+    writeln!(h, "/// An `lc3::utils::IInputter`.").unwrap();
+    writeln!(h, "typedef struct lc3core_inputter lc3core_inputter;\n").unwrap();
+
+    writeln!(h, "/// An `lc3::utils::PrintType`.").unwrap();
+    writeln!(h, "typedef int32_t lc3core_print_type;").unwrap();
+    writeln!(h, "enum {{").unwrap();
+    for (value, enumerator) in print_types.iter().enumerate() {
+        writeln!(h, "    {} = {},", print_type(enumerator), value).unwrap();
+    }
+    writeln!(h, "}};").unwrap();
+
+    for header in headers {
+        let mut lines: Vec<String> = Vec::new();
+        for line in extern_block(header).lines() {
+            let trimmed = line.trim_start();
+
+            // Links to the Rust docs (and the blank line before them)
+            // don't mean anything here.
+            if trimmed.starts_with("/// [") && trimmed.contains("]: crate::") {
+                if lines.last().map_or(false, |l| l.trim() == "///") {
+                    let _ = lines.pop();
+                }
+                continue;
+            }
+
+            let mut line = if line.starts_with("    ") {
+                line[4..].to_string()
+            } else {
+                line.to_string()
+            };
+            if trimmed.starts_with("//") {
+                line = line.replace("[`", "`").replace("`]", "`");
+            }
+            for (cpp, c) in TYPES {
+                line = replace_word(&line, cpp, c);
+            }
+            for f in functions {
+                line = if trimmed.starts_with("//") {
+                    line.replace(
+                        &format!("`{}`", f.name),
+                        &format!("`{}`", f.exported()),
+                    )
+                } else {
+                    replace_word(&line, &f.name, &f.exported())
+                };
+            }
+
+            lines.push(line);
+        }
+
+        writeln!(h, "\n{}", lines.join("\n").trim()).unwrap();
+    }
+
+    writeln!(h, "\n#ifdef __cplusplus").unwrap();
+    writeln!(h, "}}").unwrap();
+    writeln!(h, "#endif\n").unwrap();
+    writeln!(h, "#endif /* LC3CORE_H */").unwrap();
+    h
+}
+
+/// The C++ source for the exported functions, which forward to the C API.
+///
+/// This also checks that `lc3core.h` got the `PrintType` values right.
+pub fn wrappers(
+    includes: &[&str],
+    functions: &[Function],
+    print_types: &[String],
+) -> String {
+    let mut c = String::new();
+    writeln!(c, "// Generated by `build.rs` in `lc3tools-sys`.\n").unwrap();
+    for include in includes {
+        writeln!(c, "#include \"{}\"", include).unwrap();
+    }
+
+    writeln!(c, "\n#if defined(_WIN32)").unwrap();
+    writeln!(c, "#define LC3CORE_EXPORT __declspec(dllexport)").unwrap();
+    writeln!(c, "#else").unwrap();
+    writeln!(
+        c,
+        "#define LC3CORE_EXPORT __attribute__((visibility(\"default\")))"
+    )
+    .unwrap();
+    writeln!(c, "#endif\n").unwrap();
+
+    for (value, enumerator) in print_types.iter().enumerate() {
+        writeln!(
+            c,
+            "static_assert(static_cast<int32_t>(lc3::utils::PrintType::{}) \
+            == {}, \"`lc3core.h` has the wrong value for `{}`\");",
+            enumerator,
+            value,
+            print_type(enumerator),
+        )
+        .unwrap();
+    }
+
+    writeln!(c, "\nextern \"C\" {{").unwrap();
+    for f in functions {
+        writeln!(
+            c,
+            "    LC3CORE_EXPORT {} {}({}) {{\n        \
+            return ::{}({});\n    }}",
+            f.ret,
+            f.exported(),
+            f.params,
+            f.name,
+            f.args.join(", "),
+        )
+        .unwrap();
+    }
+    writeln!(c, "}}").unwrap();
+    c
+}
+
+/// An ELF version script that exports the functions (and nothing else).
+pub fn version_script(functions: &[Function]) -> String {
+    let mut s = format!("{} {{\n    global:\n", VERSION_NODE);
+    for f in functions {
+        writeln!(s, "        {};", f.exported()).unwrap();
+    }
+    s.push_str("    local:\n        *;\n};\n");
+    s
+}
+
+/// A list of the functions for `ld64`'s `-exported_symbols_list`.
+pub fn exported_symbols_list(functions: &[Function]) -> String {
+    functions
+        .iter()
+        .map(|f| format!("_{}\n", f.exported()))
+        .collect()
+}
+
+/// A module-definition file that exports the functions.
+pub fn def_file(functions: &[Function]) -> String {
+    let mut s = String::from("LIBRARY lc3core\nEXPORTS\n");
+    for f in functions {
+        writeln!(s, "    {}", f.exported()).unwrap();
+    }
+    s
+}
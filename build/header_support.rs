@@ -0,0 +1,31 @@
+/// Strips out `//` and `/* */` comments.
+///
+/// This doesn't know about string literals but that's okay for the
+/// headers we care about.
+pub fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+
+    loop {
+        let line = rest.find("//");
+        let block = rest.find("/*");
+
+        let (start, is_line) = match (line, block) {
+            (Some(l), Some(b)) => (l.min(b), l < b),
+            (Some(l), None) => (l, true),
+            (None, Some(b)) => (b, false),
+            (None, None) => {
+                out.push_str(rest);
+                break out;
+            }
+        };
+
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = if is_line {
+            rest.find('\n').map_or("", |e| &rest[e..])
+        } else {
+            rest.find("*/").map_or("", |e| &rest[e + 2..])
+        };
+    }
+}
@@ -0,0 +1,371 @@
+use std::path::{Path, PathBuf};
+
+/// The changes a patch makes to one file.
+pub struct FilePatch {
+    /// The file's path, relative to the root of the `LC3Tools` sources.
+    pub path: PathBuf,
+    hunks: Vec<Hunk>,
+}
+
+struct Hunk {
+    header: String,
+    /// Where the hunk starts in the original file (from 0).
+    start: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+/// Parses a hunk header range (`start,len` or just `start`).
+fn range(range: &str) -> Option<(usize, usize)> {
+    let mut parts = range.splitn(2, ',');
+    let start = parts.next()?.parse().ok()?;
+    let len = match parts.next() {
+        Some(len) => len.parse().ok()?,
+        None => 1,
+    };
+
+    Some((start, len))
+}
+
+fn count(left: &mut usize, header: &str) -> Result<(), String> {
+    *left = left.checked_sub(1).ok_or_else(|| {
+        format!("hunk `{}` has more lines than it says", header)
+    })?;
+
+    Ok(())
+}
+
+/// Parses a unified diff. Anything outside of the file headers and hunks
+/// (`diff --git` lines, commit messages, etc.) is ignored.
+pub fn parse(diff: &str) -> Result<Vec<FilePatch>, String> {
+    let mut lines = diff.lines();
+    let mut files: Vec<FilePatch> = Vec::new();
+
+    while let Some(line) = lines.next() {
+        if line.starts_with("--- ") {
+            let target = match lines.next() {
+                Some(l) if l.starts_with("+++ ") => l,
+                _ => return Err(format!("no `+++` line after `{}`", line)),
+            };
+
+            // Drop the timestamp (if there is one) and the first path
+            // component (`b/`).
+            let path = target[4..].split('\t').next().unwrap().trim();
+            files.push(FilePatch {
+                path: Path::new(path).components().skip(1).collect(),
+                hunks: Vec::new(),
+            });
+        } else if line.starts_with("@@ ") {
+            let file = files
+                .last_mut()
+                .ok_or_else(|| format!("no file for hunk `{}`", line))?;
+
+            let mut fields = line.split_whitespace().skip(1);
+            let mut field = |sign| {
+                fields
+                    .next()
+                    .filter(|f| f.starts_with(sign))
+                    .and_then(|f| range(&f[1..]))
+            };
+            let ((start, mut old_len), (_, mut new_len)) =
+                match (field('-'), field('+')) {
+                    (Some(old), Some(new)) => (old, new),
+                    _ => return Err(format!("bad hunk header `{}`", line)),
+                };
+
+            let mut hunk = Hunk {
+                header: line.to_string(),
+                // An empty range starts _after_ the line it names.
+                start: if old_len == 0 {
+                    start
+                } else {
+                    start.saturating_sub(1)
+                },
+                old: Vec::new(),
+                new: Vec::new(),
+            };
+
+            while old_len > 0 || new_len > 0 {
+                let line = lines.next().ok_or_else(|| {
+                    format!("hunk `{}` is cut short", hunk.header)
+                })?;
+                let text = line.get(1..).unwrap_or("").to_string();
+
+                match line.chars().next() {
+                    // Some editors strip the space off of empty context
+                    // lines.
+                    Some(' ') | None => {
+                        count(&mut old_len, &hunk.header)?;
+                        count(&mut new_len, &hunk.header)?;
+                        hunk.old.push(text.clone());
+                        hunk.new.push(text);
+                    }
+                    Some('-') => {
+                        count(&mut old_len, &hunk.header)?;
+                        hunk.old.push(text);
+                    }
+                    Some('+') => {
+                        count(&mut new_len, &hunk.header)?;
+                        hunk.new.push(text);
+                    }
+                    // `\ No newline at end of file`
+                    Some('\\') => {}
+                    Some(_) => {
+                        return Err(format!(
+                            "unexpected line in hunk `{}`: `{}`",
+                            hunk.header, line
+                        ))
+                    }
+                }
+            }
+
+            file.hunks.push(hunk);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Finds `needle` in `lines` at or after `from`, as close to `hint` as
+/// possible.
+fn find(
+    lines: &[&str],
+    needle: &[String],
+    from: usize,
+    hint: usize,
+) -> Option<usize> {
+    let last = lines.len().checked_sub(needle.len())?;
+    let distance = |at: usize| if at > hint { at - hint } else { hint - at };
+
+    (from..=last)
+        .filter(|&at| {
+            lines[at..at + needle.len()]
+                .iter()
+                .zip(needle)
+                .all(|(l, n)| l == n)
+        })
+        .min_by_key(|&at| distance(at))
+}
+
+/// Applies (or unapplies) each of the hunks in `patch`; on failure,
+/// returns the header of the hunk that didn't apply.
+fn splice<'p>(
+    lines: &[&str],
+    patch: &'p FilePatch,
+    reverse: bool,
+) -> Result<Vec<String>, &'p str> {
+    let mut out = Vec::with_capacity(lines.len());
+    let (mut pos, mut offset) = (0, 0isize);
+
+    for hunk in &patch.hunks {
+        let (old, new) = if reverse {
+            (&hunk.new, &hunk.old)
+        } else {
+            (&hunk.old, &hunk.new)
+        };
+
+        let hint = (hunk.start as isize + offset).max(0) as usize;
+        let at = find(lines, old, pos, hint).ok_or(&*hunk.header)?;
+
+        out.extend(lines[pos..at].iter().map(|l| l.to_string()));
+        out.extend(new.iter().cloned());
+        pos = at + old.len();
+        offset = at as isize - hunk.start as isize;
+    }
+
+    out.extend(lines[pos..].iter().map(|l| l.to_string()));
+    Ok(out)
+}
+
+/// Applies `patch` to `src`, keeping its line endings.
+///
+/// Returns `None` if the patch has already been applied (i.e. it applies
+/// in reverse) so that a tree that already has a fix (a newer release or
+/// a fork) doesn't break the build.
+pub fn apply(src: &str, patch: &FilePatch) -> Result<Option<String>, String> {
+    let lines = src.lines().collect::<Vec<_>>();
+
+    let patched = match splice(&lines, patch, false) {
+        Ok(patched) => patched,
+        Err(_) if splice(&lines, patch, true).is_ok() => return Ok(None),
+        Err(hunk) => return Err(format!("hunk `{}` doesn't apply", hunk)),
+    };
+
+    let newline = if src.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = patched.join(newline);
+    if src.ends_with('\n') {
+        out.push_str(newline);
+    }
+
+    Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(diff: &str) -> FilePatch {
+        let mut files = parse(diff).unwrap();
+        assert_eq!(files.len(), 1);
+
+        files.pop().unwrap()
+    }
+
+    const FIX: &str = "\
+Fix the thing.
+
+--- a/backend/foo.cpp
++++ b/backend/foo.cpp
+@@ -2,3 +2,3 @@
+ int foo() {
+-    return 1;
++    return 2;
+ }
+";
+
+    #[test]
+    fn parses() {
+        let diff = "\
+diff --git a/backend/a.h b/backend/a.h
+--- a/backend/a.h\t2020-01-01 00:00:00
++++ b/backend/a.h\t2020-01-01 00:00:00
+@@ -1 +1,2 @@
+ a
++b
+@@ -5,0 +7 @@
++c
+--- a/frontend/b.cpp
++++ b/frontend/b.cpp
+@@ -1,2 +1 @@
+-x
+ y
+\\ No newline at end of file
+";
+        let files = parse(diff).unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, Path::new("backend/a.h"));
+        assert_eq!(files[1].path, Path::new("frontend/b.cpp"));
+
+        let hunks = &files[0].hunks;
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            (hunks[0].start, &*hunks[0].old),
+            (0, &["a".to_string()][..])
+        );
+        assert_eq!(hunks[0].new, ["a", "b"]);
+        // Empty ranges start after the line they name.
+        assert_eq!((hunks[1].start, hunks[1].old.len()), (5, 0));
+        assert_eq!(files[1].hunks[0].old, ["x", "y"]);
+        assert_eq!(files[1].hunks[0].new, ["y"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |diff: &str| parse(diff).err().unwrap();
+
+        assert!(error("--- a/x\n@@ -1 +1 @@\n").contains("no `+++`"));
+        assert!(error("@@ -1 +1 @@\n x\n").contains("no file"));
+        assert!(error("--- a/x\n+++ b/x\n@@ 1 1 @@\n").contains("bad hunk"));
+        assert!(error("--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n x\n")
+            .contains("cut short"));
+        assert!(error("--- a/x\n+++ b/x\n@@ -1 +1 @@\n-x\n-y\n+z\n")
+            .contains("more lines"));
+        assert!(error("--- a/x\n+++ b/x\n@@ -1 +1 @@\n*x\n")
+            .contains("unexpected line"));
+    }
+
+    #[test]
+    fn applies() {
+        let src = "// foo\nint foo() {\n    return 1;\n}\n";
+
+        assert_eq!(
+            apply(src, &patch(FIX)).unwrap().as_deref(),
+            Some("// foo\nint foo() {\n    return 2;\n}\n")
+        );
+    }
+
+    #[test]
+    fn applies_at_an_offset() {
+        let src = "#include <a>\n#include <b>\n\n// foo\n\
+                   int foo() {\n    return 1;\n}\n";
+
+        assert_eq!(
+            apply(src, &patch(FIX)).unwrap().as_deref(),
+            Some(
+                "#include <a>\n#include <b>\n\n// foo\n\
+                 int foo() {\n    return 2;\n}\n"
+            )
+        );
+
+        // Later hunks are shifted by the offset earlier ones were found at
+        // and, when their context shows up more than once, go to the closest
+        // match.
+        let diff = "\
+--- a/x
++++ b/x
+@@ -1 +1,2 @@
+ a
++b
+@@ -3 +4,2 @@
+ a
++c
+";
+        let src = "z\nz\na\nz\na\nz\na\n";
+        assert_eq!(
+            apply(src, &patch(diff)).unwrap().as_deref(),
+            Some("z\nz\na\nb\nz\na\nc\nz\na\n")
+        );
+    }
+
+    #[test]
+    fn already_applied() {
+        let src = "// foo\nint foo() {\n    return 2;\n}\n";
+
+        assert_eq!(apply(src, &patch(FIX)).unwrap(), None);
+    }
+
+    #[test]
+    fn does_not_apply() {
+        let src = "int foo() {\n    return 3;\n}\n";
+        let err = apply(src, &patch(FIX)).unwrap_err();
+
+        assert!(err.contains("@@ -2,3 +2,3 @@"), "{}", err);
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let src = "int foo() {\r\n    return 1;\r\n}\r\n";
+        assert_eq!(
+            apply(src, &patch(FIX)).unwrap().as_deref(),
+            Some("int foo() {\r\n    return 2;\r\n}\r\n")
+        );
+
+        // A CRLF patch applies to an LF file (and the other way around).
+        let src = "int foo() {\n    return 1;\n}";
+        assert_eq!(
+            apply(src, &patch(&FIX.replace('\n', "\r\n")))
+                .unwrap()
+                .as_deref(),
+            Some("int foo() {\n    return 2;\n}")
+        );
+    }
+
+    #[test]
+    fn empty_context_lines() {
+        // Some editors strip the trailing space off of empty context lines.
+        let diff = "\
+--- a/x
++++ b/x
+@@ -1,3 +1,4 @@
+ a
+
++b
+ c
+";
+        assert_eq!(
+            apply("a\n\nc\n", &patch(diff)).unwrap().as_deref(),
+            Some("a\n\nb\nc\n")
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::str;
+
+/// A function in the bindings.
+pub struct Function {
+    /// The function's path, i.e. `root::lc3::sim_getMem`.
+    pub path: String,
+    /// The symbol the function links against.
+    pub symbol: String,
+}
+
+/// Whether `name(` appears in `src`.
+fn declares(src: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    src.match_indices(name).any(|(i, _)| {
+        let before = src[..i].chars().next_back();
+        let after = src[i + name.len()..].trim_start();
+
+        !before.map_or(false, is_ident) && after.starts_with('(')
+    })
+}
+
+/// Whether the brackets in `attr` (outside of string literals) balance,
+/// i.e. whether it's a whole attribute and not just the start of one.
+fn complete(attr: &str) -> bool {
+    let (mut depth, mut string, mut escaped) = (0, false, false);
+
+    for c in attr.chars() {
+        if string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => string = false,
+                _ => {}
+            }
+        } else {
+            match c {
+                '"' => string = true,
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    depth == 0
+}
+
+/// The features in a `#[cfg(...)]` attribute: either just one
+/// (`feature = "..."`) or several (`any(feature = "...", ...)`).
+fn cfg_features(attr: &str) -> Vec<String> {
+    attr.split("feature = \"")
+        .skip(1)
+        .filter_map(|f| f.split('"').next())
+        .map(String::from)
+        .collect()
+}
+
+/// The functions in the bindings that `lc3core` should define: everything
+/// under `root::lc3` and the C bindings declared in `c_headers` (the rest
+/// come from libc and the C++ standard library).
+///
+/// Functions gated on features that aren't `enabled` are skipped. This
+/// works on the (`rustfmt`ed) text of the bindings rather than parsing
+/// them properly so we don't need `syn` for it.
+pub fn functions(
+    bindings: &str,
+    c_headers: &str,
+    enabled: impl Fn(&str) -> bool,
+    prefix: &str,
+) -> Vec<Function> {
+    // (indent, name, features)
+    let mut modules: Vec<(usize, String, Option<Vec<String>>)> = Vec::new();
+    let mut feature = None;
+    let mut block: Option<(usize, Option<Vec<String>>)> = None;
+    let mut link_name = None;
+    let mut functions = Vec::new();
+    let mut attr = String::new();
+
+    for line in bindings.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        // Attributes can be spread over several lines.
+        if !attr.is_empty() || trimmed.starts_with("#[") {
+            attr.push_str(trimmed);
+            if !complete(&attr) {
+                continue;
+            }
+
+            if attr.starts_with("#[cfg(") {
+                feature = Some(cfg_features(&attr));
+            } else if attr.starts_with("#[link_name = \"") && block.is_some() {
+                let value = &attr[15..];
+                link_name =
+                    value.rfind('"').map(|end| &value[..end]).map(|n| {
+                        if n.starts_with("\\u{1}") {
+                            n[5..].to_string()
+                        } else {
+                            format!("{}{}", prefix, n)
+                        }
+                    });
+            }
+
+            attr.clear();
+            continue;
+        }
+
+        if trimmed.starts_with("pub mod ") && trimmed.ends_with('{') {
+            let name = trimmed[8..].trim_end_matches('{').trim();
+            modules.push((indent, name.to_string(), feature.take()));
+            continue;
+        }
+
+        if trimmed == "extern \"C\" {" {
+            block = Some((indent, feature.take()));
+            continue;
+        }
+        feature = None;
+
+        if trimmed == "}" {
+            if block.as_ref().map_or(false, |(i, _)| *i == indent) {
+                block = None;
+            } else if modules.last().map_or(false, |(i, ..)| *i == indent) {
+                let _ = modules.pop();
+            }
+
+            continue;
+        }
+
+        let block_feature = match &block {
+            Some((_, f)) => f,
+            None => continue,
+        };
+
+        if !trimmed.starts_with("pub fn ") {
+            continue;
+        }
+
+        let name = trimmed[7..].split('(').next().unwrap_or("").trim();
+        let path = modules
+            .iter()
+            .map(|(_, m, _)| &**m)
+            .chain(Some(name))
+            .collect::<Vec<_>>()
+            .join("::");
+
+        let symbol = link_name.take();
+        let ours = match (&symbol, path.starts_with("root::lc3::")) {
+            (_, true) => true,
+            (None, false) => modules.len() == 1 && declares(c_headers, name),
+            (Some(_), false) => false,
+        };
+
+        let gated = modules
+            .iter()
+            .filter_map(|(.., f)| f.as_ref())
+            .chain(block_feature.as_ref())
+            .all(|features| features.iter().any(|f| enabled(f)));
+
+        if ours && gated {
+            functions.push(Function {
+                path,
+                symbol: symbol.unwrap_or_else(|| format!("{}{}", prefix, name)),
+            });
+        }
+    }
+
+    functions
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn be_u32(b: &[u8]) -> Option<usize> {
+    Some(u32::from_be_bytes(b.get(..4)?.try_into().ok()?) as usize)
+}
+
+fn be_u64(b: &[u8]) -> Option<usize> {
+    Some(u64::from_be_bytes(b.get(..8)?.try_into().ok()?) as usize)
+}
+
+fn le_u32(b: &[u8]) -> Option<usize> {
+    Some(u32::from_le_bytes(b.get(..4)?.try_into().ok()?) as usize)
+}
+
+fn le_u64(b: &[u8]) -> Option<usize> {
+    Some(u64::from_le_bytes(b.get(..8)?.try_into().ok()?) as usize)
+}
+
+fn c_str(b: &[u8]) -> String {
+    let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..end]).into_owned()
+}
+
+/// A System V/GNU (and MSVC first linker member) symbol index: a big
+/// endian count, that many offsets and then that many NUL terminated
+/// names.
+fn gnu_index(body: &[u8], word: usize) -> Option<Vec<String>> {
+    let read = if word == 4 { be_u32 } else { be_u64 };
+    let count = read(body)?;
+    let mut names = body.get(word + count * word..)?;
+
+    let mut symbols = Vec::with_capacity(count);
+    for _ in 0..count {
+        let name = c_str(names);
+        names = names.get(name.len() + 1..).unwrap_or(&[]);
+        symbols.push(name);
+    }
+
+    Some(symbols)
+}
+
+/// A BSD (`__.SYMDEF`) symbol index: the size of a table of
+/// `(name offset, member offset)` pairs, the table, the size of the string
+/// table and the string table.
+fn bsd_index(body: &[u8], word: usize) -> Option<Vec<String>> {
+    let read = if word == 4 { le_u32 } else { le_u64 };
+    let table_len = read(body)?;
+    let table = body.get(word..word + table_len)?;
+    let strings = body.get(word + table_len + word..)?;
+
+    Some(
+        table
+            .chunks(word * 2)
+            .filter_map(|entry| strings.get(read(entry)?..).map(c_str))
+            .collect(),
+    )
+}
+
+/// The symbols in a static library's symbol index.
+pub fn archive_symbols(path: &Path) -> Result<HashSet<String>> {
+    index_symbols(&fs::read(path)?)
+}
+
+/// The symbols in the symbol index of an `ar` archive (the contents of a
+/// static library).
+pub fn index_symbols(data: &[u8]) -> Result<HashSet<String>> {
+    if !data.starts_with(b"!<arch>\n") {
+        return Err(invalid("not an `ar` archive"));
+    }
+
+    // The index, if there is one, is the first member.
+    let header = data
+        .get(8..68)
+        .ok_or_else(|| invalid("the archive is empty"))?;
+    let name = str::from_utf8(&header[..16]).unwrap_or("").trim_end();
+    let size = str::from_utf8(&header[48..58])
+        .ok()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .ok_or_else(|| invalid("malformed archive member header"))?;
+    let mut body = data
+        .get(68..68 + size)
+        .ok_or_else(|| invalid("truncated archive"))?;
+
+    // BSD style long names (`#1/<len>`) precede the member's data.
+    let name = if name.starts_with("#1/") {
+        let len = name[3..].parse::<usize>().unwrap_or(0).min(size);
+        let long = c_str(&body[..len]);
+        body = &body[len..];
+        long
+    } else {
+        name.to_string()
+    };
+
+    let symbols = match &*name {
+        "/" => gnu_index(body, 4),
+        "/SYM64/" => gnu_index(body, 8),
+        "__.SYMDEF" | "__.SYMDEF SORTED" => bsd_index(body, 4),
+        "__.SYMDEF_64" | "__.SYMDEF_64 SORTED" => bsd_index(body, 8),
+        _ => return Err(invalid("the archive has no symbol index")),
+    };
+
+    symbols
+        .map(|s| s.into_iter().collect())
+        .ok_or_else(|| invalid("malformed symbol index"))
+}
@@ -2,11 +2,13 @@ use std::error::Error;
 use std::ops::Deref;
 use std::time::{Duration, Instant};
 
+use lc3tools_sys::asm::assemble;
+use lc3tools_sys::enums::PrintType;
 use lc3tools_sys::root::lc3::sim as Sim;
-use lc3tools_sys::root::lc3::utils::PrintType_P_NONE as PrintTypeNone;
 use lc3tools_sys::root::{
     free_sim, get_mem, load_program, new_sim_with_no_op_io, run_program, State,
 };
+use lc3tools_sys::sim::Simulator;
 
 use lc3_isa::{
     program,
@@ -86,6 +88,16 @@ fn inner_main() {
 
     println!();
 
+    rust_interface();
+
+    println!();
+
+    #[cfg(feature = "cxx-bridge")]
+    {
+        cxx_interface(&prog_gen);
+        println!();
+    }
+
     #[cfg(feature = "cpp-interface-example")]
     cpp_interface(&prog_gen);
 }
@@ -111,7 +123,7 @@ fn c_interface(prog_gen: &impl Fn(u16, u16) -> AssembledProgram) {
         let words_ptr = words.as_ptr();
         let len = addrs.len();
 
-        let sim = unsafe { new_sim_with_no_op_io(PrintTypeNone) };
+        let sim = unsafe { new_sim_with_no_op_io(PrintType::None.into()) };
         unsafe { load_program(sim, len as u16, addrs_ptr, words_ptr) };
 
         drop((addrs, words));
@@ -138,6 +150,101 @@ fn c_interface(prog_gen: &impl Fn(u16, u16) -> AssembledProgram) {
     test(1, 65535); // This one has the worst runtime.
 }
 
+fn rust_interface() {
+    let source = |a: u16, b: u16| {
+        format!(
+            "
+            .ORIG x3000
+                    BRnzp START
+
+            ; Calculates a * b
+            A       .FILL #{}
+            B       .FILL #{}
+
+            START   AND R0, R0, #0  ; R0 as acc
+                    LD R1, A        ; R1 as inc
+                    LD R2, B        ; R2 as count
+
+            LOOP    BRz END
+                    ADD R0, R0, R1
+                    ADD R2, R2, #-1
+                    BRnzp LOOP
+
+            END     ST R0, RES
+                    HALT
+
+            RES     .FILL #0
+            .END
+            ",
+            a as i16, b as i16,
+        )
+    };
+
+    let test = |a: u16, b: u16| {
+        print!("{:5} x {:5}: ", a, b);
+        let prog = assemble(&source(a, b)).unwrap();
+        let symbol = |name: &str| prog.symbols[name];
+        let expected =
+            a.checked_mul(b).expect("multiplication does not overflow");
+
+        let mut sim = Simulator::new(PrintType::None);
+        sim.load_program(prog.iter());
+
+        let (state, elapsed) = time(|| sim.run(symbol("START")));
+        println!("[in {:?}]", elapsed);
+
+        let got = sim.mem(symbol("RES"));
+        assert!(state.is_ok());
+        eq!(expected, got, "Expected `{}`, got `{:?}`.", expected, state);
+    };
+
+    test(0, 0);
+    test(0, 8);
+    test(9, 0);
+    test(1, 1);
+    test(1, 50);
+    test(30, 50);
+    test(6, 7); // → 42
+    test(1, 65535); // This one has the worst runtime.
+}
+
+#[cfg(feature = "cxx-bridge")]
+fn cxx_interface(prog_gen: &impl Fn(u16, u16) -> AssembledProgram) {
+    use lc3tools_sys::bridge::Sim;
+
+    let mut sim = Sim::new(|_: &[u8]| {}, || None, PrintType::None);
+
+    let mut test = |a: u16, b: u16| {
+        print!("{:5} x {:5}: ", a, b);
+        let prog: AssembledProgram = prog_gen(a, b);
+        let expected =
+            a.checked_mul(b).expect("multiplication does not overflow");
+
+        sim.pin_mut().reinitialize();
+        for (addr, word) in &prog {
+            sim.pin_mut().set_mem(addr, word);
+        }
+
+        sim.pin_mut().set_pc(0x3000);
+        let (success, elapsed) = time(|| sim.pin_mut().run_until_halt());
+
+        assert!(success);
+        println!("[in {:?}]", elapsed);
+
+        let got = sim.get_mem(0x3020);
+        eq!(expected, got, "Expected `{}`, got `{}`.", expected, got);
+    };
+
+    test(0, 0);
+    test(0, 8);
+    test(9, 0);
+    test(1, 1);
+    test(1, 50);
+    test(30, 50);
+    test(6, 7); // → 42
+    test(1, 65535); // This one has the worst runtime.
+}
+
 #[cfg(feature = "cpp-interface-example")]
 fn cpp_interface(prog_gen: &impl Fn(u16, u16) -> AssembledProgram) {
     use lc3tools_sys::root::lc3::shims::{noOpInputShim, noOpPrintShim};
@@ -1,7 +1,21 @@
+#include <algorithm>
+#include <cstring>
+#include <deque>
+#include <fstream>
+#include <map>
+#include <memory>
+#include <random>
+#include <sstream>
+#include <string>
+#include <vector>
+
+#include "asm.h"
 #include "bindings.h"
 #include "interface.h"
+#include "mem.h"
 #include "simulator.h"
 #include "shims.h"
+#include "utils.h"
 
 extern "C" lc3::sim *new_sim(
     lc3::utils::IPrinter *printer,
@@ -61,6 +75,211 @@ extern "C" lc3::utils::IInputter *callback_inputter(
     return (lc3::utils::IInputter*)(inputter);
 }
 
+namespace {
+    /// Calls a function (with some context) for every `char` emitted.
+    class ContextPrinter: public lc3::utils::IPrinter
+    {
+    public:
+        ContextPrinter(void *ctx, void (*func)(void *, unsigned char)):
+            ctx(ctx), func(func) {}
+
+        virtual void setColor(lc3::utils::PrintColor) override {}
+        virtual void print(std::string const & string) override {
+            for (auto& c: string) { this->func(this->ctx, c); }
+        }
+        virtual void newline(void) override { this->func(this->ctx, '\n'); }
+
+    private:
+        void *ctx;
+        void (*func)(void *, unsigned char);
+    };
+
+    /// Releases input according to a schedule of `(instructions, bytes)`
+    /// events; time is the instruction count of the attached `sim`.
+    class ScheduledInputter: public lc3::utils::IInputter
+    {
+    public:
+        virtual void beginInput(void) override {}
+        virtual bool getChar(char & c) override {
+            uint64_t const now =
+                this->sim == nullptr ? 0 : this->sim->getInstExecCount();
+
+            // Release everything that's due:
+            auto due = this->events.upper_bound(now);
+            for (auto it = this->events.begin(); it != due; ++it) {
+                this->pending.insert(
+                    this->pending.end(), it->second.begin(), it->second.end()
+                );
+            }
+            this->events.erase(this->events.begin(), due);
+
+            if (this->pending.empty()) { return false; }
+
+            c = this->pending.front();
+            this->pending.pop_front();
+            return true;
+        }
+        virtual void endInput(void) override {}
+
+        void attach(lc3::sim *sim) { this->sim = sim; }
+
+        void add(uint64_t after, std::string bytes) {
+            this->events.emplace(after, std::move(bytes));
+        }
+
+        void clear(void) {
+            this->events.clear();
+            this->pending.clear();
+        }
+
+    private:
+        lc3::sim *sim = nullptr;
+        std::multimap<uint64_t, std::string> events;
+        std::deque<char> pending;
+    };
+}
+
+extern "C" lc3::utils::IPrinter *context_printer(
+    void *ctx,
+    void (*func)(void *ctx, unsigned char c)
+) {
+    auto printer = new ContextPrinter(ctx, func);
+    return (lc3::utils::IPrinter*)(printer);
+}
+
+extern "C" lc3::utils::IInputter *scheduled_inputter(void) {
+    auto inputter = new ScheduledInputter();
+    return (lc3::utils::IInputter*)(inputter);
+}
+
+extern "C" void scheduled_inputter_attach(
+    lc3::utils::IInputter *inputter,
+    lc3::sim *sim
+) {
+    auto scheduled = dynamic_cast<ScheduledInputter*>(inputter);
+    if (scheduled == nullptr) { return; }
+
+    scheduled->attach(sim);
+}
+
+extern "C" void scheduled_inputter_add(
+    lc3::utils::IInputter *inputter,
+    uint64_t after,
+    size_t const len,
+    unsigned char const bytes[/*len*/]
+) {
+    auto scheduled = dynamic_cast<ScheduledInputter*>(inputter);
+    if (scheduled == nullptr) { return; }
+
+    scheduled->add(after, std::string(bytes, bytes + len));
+}
+
+extern "C" void scheduled_inputter_clear(lc3::utils::IInputter *inputter) {
+    auto scheduled = dynamic_cast<ScheduledInputter*>(inputter);
+    if (scheduled == nullptr) { return; }
+
+    scheduled->clear();
+}
+
+extern "C" void free_printer(lc3::utils::IPrinter *printer) {
+    delete printer;
+}
+
+extern "C" void free_inputter(lc3::utils::IInputter *inputter) {
+    delete inputter;
+}
+
+// Mirrors `lc3::core::Simulator::loadObj` except that it hands the words to a
+// callback instead of writing them into memory.
+static bool read_obj(std::istream &buffer, void *ctx, ProgramWordCallback func) {
+    std::string const expected =
+        lc3::utils::getMagicHeader() + lc3::utils::getVersionString();
+    std::string header(expected.size(), '\0');
+
+    buffer.read(&header[0], expected.size());
+    if (!buffer || header != expected) { return false; }
+
+    uint16_t addr = 0;
+    while (true) {
+        lc3::core::MemEntry entry;
+        buffer >> entry;
+        if (!buffer) { break; }
+
+        if (entry.isOrig()) {
+            addr = entry.getValue();
+        } else {
+            func(ctx, addr, entry.getValue(), entry.getLine().c_str());
+            addr++;
+        }
+    }
+
+    return true;
+}
+
+extern "C" bool assemble(
+    lc3::utils::IPrinter *printer,
+    lc3::utils::PrintType print_level,
+    char const *asm_path,
+    bool liberal,
+    void *ctx,
+    ProgramWordCallback func
+) {
+    lc3::as assembler(*printer, static_cast<uint32_t>(print_level), false);
+    assembler.setEnableLiberalAsm(liberal);
+
+    auto obj_path = assembler.assemble(std::string(asm_path));
+    if (!obj_path) { return false; }
+
+    std::string const path = *obj_path;
+    return read_obj_file(path.c_str(), ctx, func);
+}
+
+extern "C" bool read_obj_file(
+    char const *obj_path,
+    void *ctx,
+    ProgramWordCallback func
+) {
+    std::ifstream file(obj_path, std::ios::in | std::ios::binary);
+    if (!file) { return false; }
+
+    return read_obj(file, ctx, func);
+}
+
+extern "C" bool analyze_asm(
+    size_t const len,
+    char const source[/*len*/],
+    bool liberal,
+    void *ctx,
+    ProgramSymbolCallback symbol_func,
+    ProgramStatementCallback statement_func
+) {
+    std::unique_ptr<lc3::utils::IPrinter> printer(no_op_printer());
+    lc3::core::Assembler assembler(*printer, 0, liberal);
+    std::istringstream buffer(std::string(source, len));
+
+    std::vector<lc3::core::asmbl::Statement> statements;
+    lc3::core::SymbolTable symbols;
+    try {
+        symbols = assembler.analyze(buffer, statements);
+    } catch (lc3::utils::exception const &) {
+        return false;
+    }
+
+    for (auto const &symbol : symbols) {
+        symbol_func(
+            ctx, symbol.first.c_str(), static_cast<uint16_t>(symbol.second)
+        );
+    }
+
+    for (auto const &statement : statements) {
+        statement_func(
+            ctx, statement.row, static_cast<uint16_t>(statement.pc)
+        );
+    }
+
+    return true;
+}
+
 extern "C" void load_program(
     lc3::sim* sim,
     uint16_t const len,
@@ -68,24 +287,85 @@ extern "C" void load_program(
     uint16_t const words[/*len*/]
 ) {
     sim->reinitialize();
+    load_program_overlay(sim, len, addresses, words);
+}
 
+extern "C" void load_program_overlay(
+    lc3::sim* sim,
+    uint16_t const len,
+    uint16_t const addresses[/*len*/],
+    uint16_t const words[/*len*/]
+) {
     for (auto i = 0; i < len; i++) {
         sim->setMem(addresses[i], words[i]);
     }
 }
 
+extern "C" void reinitialize_sim(lc3::sim* sim) {
+    sim->reinitialize();
+}
+
+extern "C" void restart_sim(lc3::sim* sim) {
+    sim->restart();
+}
+
 extern "C" uint16_t get_mem(lc3::sim* sim, uint16_t addr) {
     return sim->getMem(addr);
 }
 
-extern "C" State run_program(
+extern "C" size_t get_mem_line(
     lc3::sim* sim,
-    uint16_t const pc
+    uint16_t addr,
+    size_t const len,
+    char buffer[/*len*/]
 ) {
-    sim->setPC(pc);
+    std::string const line = sim->getMemLine(addr);
 
-    auto success = sim->runUntilHalt();
+    if (len > 0) {
+        size_t const copied = std::min(line.size(), len - 1);
+        std::memcpy(buffer, line.data(), copied);
+        buffer[copied] = '\0';
+    }
+
+    return line.size();
+}
+
+extern "C" void set_mem(lc3::sim* sim, uint16_t addr, uint16_t value) {
+    sim->setMem(addr, value);
+}
+
+extern "C" void set_reg(lc3::sim *sim, uint16_t reg, uint16_t value) {
+    sim->setReg(reg, value);
+}
+
+extern "C" void randomize_sim(lc3::sim* sim, uint64_t seed) {
+    // The output of `mt19937_64` is fully specified by the standard (unlike
+    // the distributions in `<random>`) so we use it directly to keep things
+    // reproducible across standard libraries.
+    std::mt19937_64 gen(seed);
+
+    for (uint32_t addr = 0x3000; addr < 0xFE00; addr++) {
+        sim->setMem(static_cast<uint16_t>(addr), static_cast<uint16_t>(gen()));
+    }
 
+    for (uint16_t reg = 0; reg < 8; reg++) {
+        sim->setReg(reg, static_cast<uint16_t>(gen()));
+    }
+}
+
+extern "C" uint64_t get_inst_exec_count(lc3::sim* sim) {
+    return sim->getInstExecCount();
+}
+
+extern "C" void set_run_inst_limit(lc3::sim *sim, uint64_t limit) {
+    sim->setRunInstLimit(limit);
+}
+
+extern "C" bool did_exceed_inst_limit(lc3::sim *sim) {
+    return sim->didExceedInstLimit();
+}
+
+static State state(lc3::sim *sim, bool success) {
     return State {
         .regs = {
             sim->getReg(0),
@@ -105,6 +385,52 @@ extern "C" State run_program(
     };
 }
 
+extern "C" State run_program(
+    lc3::sim* sim,
+    uint16_t const pc
+) {
+    sim->setPC(pc);
+
+    auto success = sim->runUntilHalt();
+    return state(sim, success);
+}
+
+extern "C" State resume_program(lc3::sim *sim) {
+    auto success = sim->runUntilHalt();
+    return state(sim, success);
+}
+
+extern "C" State step_in(lc3::sim *sim) {
+    auto success = sim->stepIn();
+    return state(sim, success);
+}
+
+extern "C" State step_over(lc3::sim *sim) {
+    auto success = sim->stepOver();
+    return state(sim, success);
+}
+
+extern "C" State step_out(lc3::sim *sim) {
+    auto success = sim->stepOut();
+    return state(sim, success);
+}
+
+extern "C" State get_state(lc3::sim *sim) {
+    return state(sim, true);
+}
+
+extern "C" void set_pc(lc3::sim *sim, uint16_t pc) {
+    sim->setPC(pc);
+}
+
+extern "C" void set_breakpoint(lc3::sim *sim, uint16_t addr) {
+    sim->setBreakpoint(addr);
+}
+
+extern "C" bool remove_breakpoint(lc3::sim *sim, uint16_t addr) {
+    return sim->removeBreakpointByAddr(addr);
+}
+
 extern "C" void free_sim(lc3::sim *sim) {
     // delete sim->inputter; // TODO: inputter!
     // delete sim->inputter; // TODO: printer!
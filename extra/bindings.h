@@ -12,6 +12,30 @@ extern "C" {
         bool success;
     } State;
 
+    /// Called for each word in an assembled program or object file with the
+    /// word's address, its value and the source line it came from.
+    typedef void (*ProgramWordCallback)(
+        void *ctx,
+        uint16_t addr,
+        uint16_t word,
+        char const *line
+    );
+
+    /// Called for each label in a program with its name and address.
+    typedef void (*ProgramSymbolCallback)(
+        void *ctx,
+        char const *name,
+        uint16_t addr
+    );
+
+    /// Called for each statement in a program (in source order) with the
+    /// line it's on (from 0) and the address it starts at.
+    typedef void (*ProgramStatementCallback)(
+        void *ctx,
+        uint32_t row,
+        uint16_t addr
+    );
+
     // Sim constructors:
     /// Creates a new [`sim`] with the given `Printer` and `Inputter`.
     ///
@@ -54,21 +78,186 @@ extern "C" {
     /// to block.
     lc3::utils::IInputter *callback_inputter(unsigned char (*func)(void));
 
+    // Context I/O constructors:
+    /// Creates a `Printer` that calls a function with `ctx` for every `char`
+    /// that's emitted.
+    ///
+    /// Unlike `callback_printer` this lets the function tell `Printer`s apart.
+    lc3::utils::IPrinter *context_printer(
+        void *ctx,
+        void (*func)(void *ctx, unsigned char c)
+    );
+
+    // Scheduled input:
+    /// Creates an `Inputter` that releases input according to a schedule
+    /// (see `scheduled_inputter_add`); like the grader's
+    /// `StringInputter::setStringAfter` this is useful for testing
+    /// interrupt-driven programs.
+    ///
+    /// Time is measured in instructions executed by the [`sim`] the
+    /// `Inputter` is attached to (see `scheduled_inputter_attach`).
+    ///
+    /// [`sim`]: crate::root::lc3::sim
+    lc3::utils::IInputter *scheduled_inputter(void);
+    /// Has the `Inputter` measure time with `sim`'s instruction count
+    /// (`get_inst_exec_count`); this should be the [`sim`] that's using it.
+    /// Until it's attached, only input scheduled after 0 instructions is
+    /// released.
+    ///
+    /// `inputter` must have been made by `scheduled_inputter`; if it wasn't
+    /// this does nothing.
+    ///
+    /// [`sim`]: crate::root::lc3::sim
+    void scheduled_inputter_attach(
+        lc3::utils::IInputter *inputter,
+        lc3::sim *sim
+    );
+    /// Schedules `bytes` to become available once the attached [`sim`] has
+    /// executed `after` instructions.
+    ///
+    /// `inputter` must have been made by `scheduled_inputter`; if it wasn't
+    /// this does nothing.
+    void scheduled_inputter_add(
+        lc3::utils::IInputter *inputter,
+        uint64_t after,
+        size_t const len,
+        unsigned char const bytes[/*len*/]
+    );
+    /// Drops all scheduled and pending input.
+    ///
+    /// `inputter` must have been made by `scheduled_inputter`; if it wasn't
+    /// this does nothing.
+    void scheduled_inputter_clear(lc3::utils::IInputter *inputter);
+
+    // I/O destructors:
+    /// Frees a `Printer` made by one of the functions above.
+    ///
+    /// Only do this once nothing (i.e. a [`sim`]) is using the `Printer`.
+    ///
+    /// [`sim`]: crate::root::lc3::sim
+    void free_printer(lc3::utils::IPrinter *printer);
+    /// Frees an `Inputter` made by one of the functions above.
+    ///
+    /// Only do this once nothing (i.e. a [`sim`]) is using the `Inputter`.
+    ///
+    /// [`sim`]: crate::root::lc3::sim
+    void free_inputter(lc3::utils::IInputter *inputter);
+
+    // Assembler functions:
+    /// Assembles the file at `asm_path`, writing an object file next to it.
+    ///
+    /// Calls `func` with `ctx` for every word in the assembled program and
+    /// returns whether assembly succeeded. Diagnostics are sent to `printer`.
+    bool assemble(
+        lc3::utils::IPrinter *printer,
+        lc3::utils::PrintType print_level,
+        char const *asm_path,
+        bool liberal,
+        void *ctx,
+        ProgramWordCallback func
+    );
+    /// Reads the object file at `obj_path`.
+    ///
+    /// Calls `func` with `ctx` for every word in the object file and returns
+    /// whether the file could be read.
+    bool read_obj_file(
+        char const *obj_path,
+        void *ctx,
+        ProgramWordCallback func
+    );
+    /// Runs the assembler's first passes over `source` without printing
+    /// anything and reports what they found.
+    ///
+    /// Calls `symbol_func` with `ctx` for every entry in the assembler's
+    /// symbol table and `statement_func` for every statement (including
+    /// pseudo-ops and lines that only have a label). Returns `false` if the
+    /// source couldn't be tokenized.
+    bool analyze_asm(
+        size_t const len,
+        char const source[/*len*/],
+        bool liberal,
+        void *ctx,
+        ProgramSymbolCallback symbol_func,
+        ProgramStatementCallback statement_func
+    );
+
     // Sim functions:
-    /// Loads a program into memory (and resets memory, probably).
+    /// Reinitializes the machine (clearing memory and reloading the OS) and
+    /// then loads a program into memory.
     void load_program(
         lc3::sim *sim,
         uint16_t const len,
         uint16_t const addresses[/*len*/],
         uint16_t const words[/*len*/]
     );
+    /// Loads a program into memory *without* reinitializing the machine
+    /// first; anything already in memory is left alone unless the program
+    /// overwrites it.
+    void load_program_overlay(
+        lc3::sim *sim,
+        uint16_t const len,
+        uint16_t const addresses[/*len*/],
+        uint16_t const words[/*len*/]
+    );
+    /// Reinitializes the machine: clears memory and the registers and reloads
+    /// the OS.
+    void reinitialize_sim(lc3::sim *sim);
+    /// Restarts the machine without clearing memory.
+    void restart_sim(lc3::sim *sim);
     /// Gets the value of a memory address.
     uint16_t get_mem(lc3::sim *sim, uint16_t addr);
+    /// Copies the source line the word at `addr` came from (`sim::getMemLine`)
+    /// into `buffer`, truncating it to fit and NUL terminating it.
+    ///
+    /// Returns the length of the whole line (without the NUL); lines are only
+    /// known for words the simulator loaded from an object file.
+    size_t get_mem_line(
+        lc3::sim *sim,
+        uint16_t addr,
+        size_t const len,
+        char buffer[/*len*/]
+    );
+    /// Sets the value of a memory address.
+    void set_mem(lc3::sim *sim, uint16_t addr, uint16_t value);
+    /// Sets the value of a general purpose register (`R0` to `R7`).
+    void set_reg(lc3::sim *sim, uint16_t reg, uint16_t value);
+    /// Fills user memory (`x3000` to `xFDFF`) and the general purpose
+    /// registers with random values derived from the given seed.
+    ///
+    /// Unlike `sim::randomize`, calling this twice with the same seed produces
+    /// the same machine state (on every platform).
+    void randomize_sim(lc3::sim *sim, uint64_t seed);
+    /// Gets the number of instructions the machine has executed.
+    uint64_t get_inst_exec_count(lc3::sim *sim);
+    /// Sets the maximum number of instructions a run may execute before it's
+    /// stopped (0 for no limit).
+    void set_run_inst_limit(lc3::sim *sim, uint64_t limit);
+    /// Whether the last run was stopped because it hit the instruction limit.
+    bool did_exceed_inst_limit(lc3::sim *sim);
     /// Runs the program starting at the given PC.
     ///
     /// Returns the machine state when the program halts (or raises an
     /// exception).
     State run_program(lc3::sim *sim, uint16_t const pc);
+    /// Runs the program from the current PC until it halts, raises an
+    /// exception or hits a breakpoint.
+    State resume_program(lc3::sim *sim);
+    /// Executes a single instruction (`sim::stepIn`).
+    State step_in(lc3::sim *sim);
+    /// Executes a single instruction, running subroutine calls to completion
+    /// (`sim::stepOver`).
+    State step_over(lc3::sim *sim);
+    /// Runs until the current subroutine returns (`sim::stepOut`).
+    State step_out(lc3::sim *sim);
+    /// Gets the state of the machine (`success` is always set).
+    State get_state(lc3::sim *sim);
+    /// Sets the PC.
+    void set_pc(lc3::sim *sim, uint16_t pc);
+    /// Sets a breakpoint at the given address.
+    void set_breakpoint(lc3::sim *sim, uint16_t addr);
+    /// Removes the breakpoint at the given address; returns whether there was
+    /// one.
+    bool remove_breakpoint(lc3::sim *sim, uint16_t addr);
     /// Frees the memory allocated to the given [`sim`] instance.
     ///
     /// [`sim`]: crate::root::lc3::sim
@@ -0,0 +1,141 @@
+#include "bridge.h"
+
+// Generated by `cxx_build` from `src/bridge.rs`; has the definitions of the
+// Rust types.
+#include "lc3tools-sys/src/bridge.rs.h"
+
+namespace lc3tools_sys { namespace bridge
+{
+    RustPrinter::RustPrinter(rust::Box<PrinterBox> printer):
+        printer(std::move(printer)) {}
+
+    RustPrinter::~RustPrinter(void) = default;
+
+    void RustPrinter::setColor(lc3::utils::PrintColor color) {
+        this->printer->set_color(static_cast<int32_t>(color));
+    }
+
+    void RustPrinter::print(std::string const & string) {
+        this->printer->print(rust::Slice<uint8_t const>(
+            reinterpret_cast<uint8_t const *>(string.data()),
+            string.size()
+        ));
+    }
+
+    void RustPrinter::newline(void) { this->printer->newline(); }
+
+    RustInputter::RustInputter(rust::Box<InputterBox> inputter):
+        inputter(std::move(inputter)) {}
+
+    RustInputter::~RustInputter(void) = default;
+
+    void RustInputter::beginInput(void) { this->inputter->begin_input(); }
+
+    bool RustInputter::getChar(char & c) {
+        uint8_t byte = 0;
+        if (!this->inputter->get_char(byte)) { return false; }
+
+        c = static_cast<char>(byte);
+        return true;
+    }
+
+    void RustInputter::endInput(void) { this->inputter->end_input(); }
+
+    Sim::Sim(
+        rust::Box<PrinterBox> printer,
+        rust::Box<InputterBox> inputter,
+        uint32_t print_level
+    ):
+        printer(std::move(printer)),
+        inputter(std::move(inputter)),
+        sim(this->printer, this->inputter, false, print_level, false) {}
+
+    bool Sim::load_obj_file(rust::Str path) {
+        return this->sim.loadObjFile(std::string(path));
+    }
+
+    void Sim::reinitialize(void) { this->sim.reinitialize(); }
+    void Sim::restart(void) { this->sim.restart(); }
+
+    bool Sim::run_until_halt(void) { return this->sim.runUntilHalt(); }
+    bool Sim::step_in(void) { return this->sim.stepIn(); }
+    bool Sim::step_over(void) { return this->sim.stepOver(); }
+    bool Sim::step_out(void) { return this->sim.stepOut(); }
+
+    uint16_t Sim::get_reg(uint16_t id) const { return this->sim.getReg(id); }
+    uint16_t Sim::get_mem(uint16_t addr) const {
+        return this->sim.getMem(addr);
+    }
+    std::unique_ptr<std::string> Sim::get_mem_line(uint16_t addr) const {
+        return std::unique_ptr<std::string>(
+            new std::string(this->sim.getMemLine(addr))
+        );
+    }
+    uint16_t Sim::get_pc(void) const { return this->sim.getPC(); }
+    uint16_t Sim::get_psr(void) const { return this->sim.getPSR(); }
+    uint16_t Sim::get_mcr(void) const { return this->sim.getMCR(); }
+    uint8_t Sim::get_cc(void) const {
+        return static_cast<uint8_t>(this->sim.getCC());
+    }
+
+    void Sim::set_reg(uint16_t id, uint16_t value) {
+        this->sim.setReg(id, value);
+    }
+    void Sim::set_mem(uint16_t addr, uint16_t value) {
+        this->sim.setMem(addr, value);
+    }
+    void Sim::set_pc(uint16_t value) { this->sim.setPC(value); }
+
+    void Sim::set_breakpoint(uint16_t addr) { this->sim.setBreakpoint(addr); }
+    bool Sim::remove_breakpoint(uint16_t addr) {
+        return this->sim.removeBreakpointByAddr(addr);
+    }
+
+    void Sim::set_run_inst_limit(uint64_t limit) {
+        this->sim.setRunInstLimit(limit);
+    }
+    uint64_t Sim::get_inst_exec_count(void) const {
+        return this->sim.getInstExecCount();
+    }
+    bool Sim::did_exceed_inst_limit(void) const {
+        return this->sim.didExceedInstLimit();
+    }
+
+    Assembler::Assembler(
+        rust::Box<PrinterBox> printer,
+        uint32_t print_level,
+        bool liberal
+    ):
+        printer(std::move(printer)),
+        assembler(this->printer, print_level, false)
+    {
+        this->assembler.setEnableLiberalAsm(liberal);
+    }
+
+    std::unique_ptr<std::string> Assembler::assemble(rust::Str asm_path) {
+        auto obj_path = this->assembler.assemble(std::string(asm_path));
+        if (!obj_path) { return nullptr; }
+
+        return std::unique_ptr<std::string>(new std::string(*obj_path));
+    }
+
+    std::unique_ptr<Sim> new_sim(
+        rust::Box<PrinterBox> printer,
+        rust::Box<InputterBox> inputter,
+        uint32_t print_level
+    ) {
+        return std::unique_ptr<Sim>(
+            new Sim(std::move(printer), std::move(inputter), print_level)
+        );
+    }
+
+    std::unique_ptr<Assembler> new_assembler(
+        rust::Box<PrinterBox> printer,
+        uint32_t print_level,
+        bool liberal
+    ) {
+        return std::unique_ptr<Assembler>(
+            new Assembler(std::move(printer), print_level, liberal)
+        );
+    }
+}}
@@ -0,0 +1,128 @@
+#pragma once
+
+#include <cstdint>
+#include <memory>
+#include <string>
+
+#include "rust/cxx.h"
+
+#include "inputter.h"
+#include "interface.h"
+#include "printer.h"
+
+// The C++ half of the `cxx` bridge in `src/bridge.rs`.
+//
+// `cxx` can only bind functions whose signatures it understands so rather than
+// binding `lc3::sim` and `lc3::as` directly we wrap them in classes that own
+// their I/O and speak in `cxx` types.
+
+namespace lc3tools_sys { namespace bridge
+{
+    // Defined in Rust (`src/bridge.rs`):
+    struct PrinterBox;
+    struct InputterBox;
+
+    /// An `IPrinter` that forwards to a Rust `Printer`.
+    class RustPrinter: public lc3::utils::IPrinter
+    {
+    public:
+        explicit RustPrinter(rust::Box<PrinterBox> printer);
+        ~RustPrinter(void);
+
+        virtual void setColor(lc3::utils::PrintColor color) override;
+        virtual void print(std::string const & string) override;
+        virtual void newline(void) override;
+
+    private:
+        rust::Box<PrinterBox> printer;
+    };
+
+    /// An `IInputter` that forwards to a Rust `Inputter`.
+    class RustInputter: public lc3::utils::IInputter
+    {
+    public:
+        explicit RustInputter(rust::Box<InputterBox> inputter);
+        ~RustInputter(void);
+
+        virtual void beginInput(void) override;
+        virtual bool getChar(char & c) override;
+        virtual void endInput(void) override;
+
+    private:
+        rust::Box<InputterBox> inputter;
+    };
+
+    /// An `lc3::sim` along with the `Printer` and `Inputter` it uses.
+    class Sim
+    {
+    public:
+        Sim(
+            rust::Box<PrinterBox> printer,
+            rust::Box<InputterBox> inputter,
+            uint32_t print_level
+        );
+
+        bool load_obj_file(rust::Str path);
+        void reinitialize(void);
+        void restart(void);
+
+        bool run_until_halt(void);
+        bool step_in(void);
+        bool step_over(void);
+        bool step_out(void);
+
+        uint16_t get_reg(uint16_t id) const;
+        uint16_t get_mem(uint16_t addr) const;
+        std::unique_ptr<std::string> get_mem_line(uint16_t addr) const;
+        uint16_t get_pc(void) const;
+        uint16_t get_psr(void) const;
+        uint16_t get_mcr(void) const;
+        uint8_t get_cc(void) const;
+
+        void set_reg(uint16_t id, uint16_t value);
+        void set_mem(uint16_t addr, uint16_t value);
+        void set_pc(uint16_t value);
+
+        void set_breakpoint(uint16_t addr);
+        bool remove_breakpoint(uint16_t addr);
+
+        void set_run_inst_limit(uint64_t limit);
+        uint64_t get_inst_exec_count(void) const;
+        bool did_exceed_inst_limit(void) const;
+
+    private:
+        // These have to come first: `sim` holds references to them.
+        RustPrinter printer;
+        RustInputter inputter;
+        lc3::sim sim;
+    };
+
+    /// An `lc3::as` along with the `Printer` it uses.
+    class Assembler
+    {
+    public:
+        Assembler(
+            rust::Box<PrinterBox> printer,
+            uint32_t print_level,
+            bool liberal
+        );
+
+        std::unique_ptr<std::string> assemble(rust::Str asm_path);
+
+    private:
+        RustPrinter printer;
+        lc3::as assembler;
+    };
+
+    std::unique_ptr<Sim> new_sim(
+        rust::Box<PrinterBox> printer,
+        rust::Box<InputterBox> inputter,
+        uint32_t print_level
+    );
+
+    std::unique_ptr<Assembler> new_assembler(
+        rust::Box<PrinterBox> printer,
+        uint32_t print_level,
+        bool liberal
+    );
+}}
@@ -0,0 +1,13 @@
+#include "frontend_bindings.h"
+#include "console_inputter.h"
+#include "console_printer.h"
+
+extern "C" lc3::utils::IPrinter *console_printer(void) {
+    auto printer = new lc3::ConsolePrinter();
+    return (lc3::utils::IPrinter*)(printer);
+}
+
+extern "C" lc3::utils::IInputter *console_inputter(void) {
+    auto inputter = new lc3::ConsoleInputter();
+    return (lc3::utils::IInputter*)(inputter);
+}
@@ -0,0 +1,16 @@
+#include "inputter.h"
+#include "printer.h"
+
+extern "C" {
+    // Console I/O constructors:
+    /// Creates a `Printer` that prints to the console (a
+    /// [`ConsolePrinter`]).
+    ///
+    /// [`ConsolePrinter`]: crate::root::lc3::ConsolePrinter
+    lc3::utils::IPrinter *console_printer(void);
+    /// Creates an `Inputter` that reads from the console (a
+    /// [`ConsoleInputter`]).
+    ///
+    /// [`ConsoleInputter`]: crate::root::lc3::ConsoleInputter
+    lc3::utils::IInputter *console_inputter(void);
+}
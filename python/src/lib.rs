@@ -0,0 +1,423 @@
+//! Python bindings for the [safe simulator API] in `lc3tools-sys`.
+//!
+//! This is a separate crate (built with [`maturin`]) so that `lc3tools-sys`
+//! doesn't have to be a `cdylib`. The Python module mirrors
+//! [`Simulator`](lc3tools_sys::sim::Simulator) and the [assembler]; see
+//! `tests/` for what using it looks like.
+//!
+//! [safe simulator API]: lc3tools_sys::sim
+//! [`maturin`]: https://github.com/PyO3/maturin
+//! [assembler]: lc3tools_sys::asm
+
+#![deny(rust_2018_idioms, unsafe_code)]
+// PyO3's macros trip this for every function that returns a `PyResult`.
+#![allow(clippy::useless_conversion)]
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use lc3tools_sys::asm::{self, AsmError, Assembler};
+use lc3tools_sys::enums::PrintType;
+use lc3tools_sys::root;
+use lc3tools_sys::sim;
+
+use exceptions::{AssemblyError, RunError};
+
+// (`create_exception!` checks for PyO3's `gil-refs` feature in this crate.)
+#[allow(unexpected_cfgs)]
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(
+        lc3tools,
+        AssemblyError,
+        PyException,
+        "Raised when a program doesn't assemble or an object file can't be \
+        read."
+    );
+    create_exception!(
+        lc3tools,
+        RunError,
+        PyException,
+        "Raised when a program doesn't halt cleanly; `state`, \
+        `exceeded_instruction_limit` and `seed` say what happened."
+    );
+}
+
+fn asm_error(err: AsmError) -> PyErr {
+    match err {
+        AsmError::Io(err) => err.into(),
+        err => AssemblyError::new_err(err.to_string()),
+    }
+}
+
+fn run_error(py: Python<'_>, err: sim::RunError) -> PyErr {
+    let exception = RunError::new_err(err.to_string());
+
+    let value = exception.value_bound(py);
+    let attrs = Py::new(py, State::from(err.state))
+        .and_then(|state| value.setattr("state", state))
+        .and_then(|()| {
+            value.setattr(
+                "exceeded_instruction_limit",
+                err.exceeded_instruction_limit,
+            )
+        })
+        .and_then(|()| value.setattr("seed", err.seed));
+
+    match attrs {
+        Ok(()) => exception,
+        Err(other) => other,
+    }
+}
+
+/// An assembled program (or a program read from an object file).
+#[pyclass(module = "lc3tools")]
+#[derive(Clone)]
+struct Program(asm::Program);
+
+#[pymethods]
+impl Program {
+    /// Reads an `LC3Tools` object file.
+    #[staticmethod]
+    fn from_obj_file(path: PathBuf) -> PyResult<Self> {
+        asm::Program::from_obj_file(path)
+            .map(Program)
+            .map_err(asm_error)
+    }
+
+    /// The `(address, word)` pairs in the program.
+    #[getter]
+    fn words(&self) -> Vec<(u16, u16)> {
+        self.0.iter().collect()
+    }
+
+    /// The program's labels and their addresses.
+    #[getter]
+    fn symbols(&self) -> HashMap<String, u16> {
+        self.0
+            .symbols
+            .iter()
+            .map(|(name, addr)| (name.to_string(), addr))
+            .collect()
+    }
+
+    /// The address of a label; like the assembler, this is case insensitive.
+    fn symbol(&self, name: &str) -> PyResult<u16> {
+        self.0
+            .symbols
+            .get(name)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.words.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Program: {} words, {} symbols>",
+            self.0.words.len(),
+            self.0.symbols.len()
+        )
+    }
+}
+
+/// Assembles some LC-3 assembly.
+#[pyfunction]
+#[pyo3(signature = (source, *, liberal = false))]
+fn assemble(source: &str, liberal: bool) -> PyResult<Program> {
+    Assembler::new()
+        .liberal(liberal)
+        .assemble(source)
+        .map(Program)
+        .map_err(asm_error)
+}
+
+/// Assembles the file at the given path; with `write_obj`, an object file is
+/// written next to it.
+#[pyfunction]
+#[pyo3(signature = (path, *, liberal = false, write_obj = false))]
+fn assemble_file(
+    path: PathBuf,
+    liberal: bool,
+    write_obj: bool,
+) -> PyResult<Program> {
+    let assembler = Assembler::new().liberal(liberal);
+    let program = if write_obj {
+        assembler.assemble_file_to_obj(path)
+    } else {
+        assembler.assemble_file(path)
+    };
+
+    program.map(Program).map_err(asm_error)
+}
+
+/// The state of the machine.
+#[pyclass(module = "lc3tools", get_all)]
+#[derive(Clone)]
+struct State {
+    regs: Vec<u16>,
+    pc: u16,
+    /// The condition codes: `'N'`, `'Z'` or `'P'`.
+    cc: char,
+    psr: u16,
+    mcr: u16,
+}
+
+impl From<root::State> for State {
+    fn from(state: root::State) -> Self {
+        State {
+            regs: state.regs.iter().map(|r| *r as u16).collect(),
+            pc: state.pc as u16,
+            cc: state.cc as u8 as char,
+            psr: state.psr as u16,
+            mcr: state.mcr as u16,
+        }
+    }
+}
+
+#[pymethods]
+impl State {
+    fn __repr__(&self) -> String {
+        format!(
+            "<State: PC = {:#06x}, CC = {}, regs = {:04x?}>",
+            self.pc, self.cc, self.regs
+        )
+    }
+}
+
+/// An LC-3 simulator running the stock OS.
+///
+/// Output is captured (see `output`) and input comes from `input` and
+/// `schedule_input`. Runs that don't halt cleanly raise `RunError`.
+#[pyclass(module = "lc3tools")]
+struct Simulator(sim::Simulator);
+
+impl Simulator {
+    fn check(
+        py: Python<'_>,
+        res: Result<root::State, sim::RunError>,
+    ) -> PyResult<State> {
+        res.map(State::from).map_err(|e| run_error(py, e))
+    }
+}
+
+#[pymethods]
+impl Simulator {
+    /// `print_level` is one of the `PRINT_*` constants; `seed` randomizes
+    /// the machine (and is reported in `RunError`s).
+    #[new]
+    #[pyo3(signature = (*, instruction_limit = 0, seed = None, print_level = None))]
+    fn new(
+        instruction_limit: u64,
+        seed: Option<u64>,
+        print_level: Option<i32>,
+    ) -> PyResult<Self> {
+        let print_level = match print_level {
+            Some(level) => PrintType::try_from(level)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            None => PrintType::FatalError,
+        };
+
+        let mut builder = sim::Simulator::builder()
+            .print_level(print_level)
+            .instruction_limit(instruction_limit);
+        if let Some(seed) = seed {
+            builder = builder.randomize(seed);
+        }
+
+        Ok(Simulator(builder.build()))
+    }
+
+    /// Resets the machine and loads a program.
+    fn load(&mut self, program: &Program) {
+        self.0.load_program(program.0.iter())
+    }
+
+    /// Loads a program without resetting the machine first; returns the
+    /// addresses of previously loaded words the program overwrote.
+    fn load_overlay(&mut self, program: &Program) -> Vec<u16> {
+        match self.0.load_overlay(program.0.iter()) {
+            Ok(()) => Vec::new(),
+            Err(err) => err.addrs,
+        }
+    }
+
+    /// Clears memory and the registers, reloads the OS and re-randomizes the
+    /// machine (if it has a seed).
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+
+    /// Restarts the machine without clearing memory.
+    fn restart(&mut self) {
+        self.0.restart()
+    }
+
+    /// Randomizes user memory and the registers with a seed; loaded programs
+    /// are kept.
+    fn randomize(&mut self, seed: u64) {
+        self.0.randomize(seed)
+    }
+
+    /// The seed the machine was randomized with, if any.
+    #[getter]
+    fn seed(&self) -> Option<u64> {
+        self.0.seed()
+    }
+
+    /// Runs the program starting at `pc` until it halts.
+    fn run(&mut self, py: Python<'_>, pc: u16) -> PyResult<State> {
+        let sim = &mut self.0;
+        Self::check(py, py.allow_threads(move || sim.run(pc)))
+    }
+
+    /// Continues running from the current PC.
+    fn resume(&mut self, py: Python<'_>) -> PyResult<State> {
+        let sim = &mut self.0;
+        Self::check(py, py.allow_threads(move || sim.resume()))
+    }
+
+    /// Executes a single instruction.
+    fn step_in(&mut self, py: Python<'_>) -> PyResult<State> {
+        Self::check(py, self.0.step_in())
+    }
+
+    /// Executes a single instruction, running subroutine calls to
+    /// completion.
+    fn step_over(&mut self, py: Python<'_>) -> PyResult<State> {
+        Self::check(py, self.0.step_over())
+    }
+
+    /// Runs until the current subroutine returns.
+    fn step_out(&mut self, py: Python<'_>) -> PyResult<State> {
+        Self::check(py, self.0.step_out())
+    }
+
+    /// The current state of the machine.
+    #[getter]
+    fn state(&self) -> State {
+        self.0.state().into()
+    }
+
+    /// The general purpose registers (`R0` to `R7`).
+    #[getter]
+    fn regs(&self) -> Vec<u16> {
+        State::from(self.0.state()).regs
+    }
+
+    fn set_reg(&mut self, reg: u8, value: u16) -> PyResult<()> {
+        if reg >= 8 {
+            return Err(PyValueError::new_err(format!("there is no R{}", reg)));
+        }
+
+        self.0.set_reg(reg, value);
+        Ok(())
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.0.state().pc as u16
+    }
+
+    #[setter]
+    fn set_pc(&mut self, pc: u16) {
+        self.0.set_pc(pc)
+    }
+
+    /// Whether the machine has halted.
+    #[getter]
+    fn halted(&self) -> bool {
+        self.0.halted()
+    }
+
+    fn mem(&self, addr: u16) -> u16 {
+        self.0.mem(addr)
+    }
+
+    fn set_mem(&mut self, addr: u16, value: u16) {
+        self.0.set_mem(addr, value)
+    }
+
+    /// Everything the machine has printed since the last reset or
+    /// `clear_output`.
+    #[getter]
+    fn output(&self) -> String {
+        self.0.output().into_owned()
+    }
+
+    /// `output`, as `bytes`.
+    #[getter]
+    fn output_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.0.output_bytes())
+    }
+
+    fn clear_output(&mut self) {
+        self.0.clear_output()
+    }
+
+    /// Makes input available right away.
+    fn input(&mut self, bytes: &[u8]) {
+        self.0.input(bytes)
+    }
+
+    /// Makes input available once the machine has executed `after`
+    /// instructions.
+    fn schedule_input(&mut self, after: u64, bytes: &[u8]) {
+        self.0.schedule_input(after, bytes)
+    }
+
+    #[getter]
+    fn instructions_executed(&self) -> u64 {
+        self.0.instructions_executed()
+    }
+
+    /// Limits how many instructions a run may execute (0 for no limit).
+    fn set_instruction_limit(&mut self, limit: u64) {
+        self.0.set_instruction_limit(limit)
+    }
+
+    /// Returns `False` if there already was a breakpoint at `addr`.
+    fn set_breakpoint(&mut self, addr: u16) -> bool {
+        self.0.set_breakpoint(addr)
+    }
+
+    /// Returns `False` if there wasn't a breakpoint at `addr`.
+    fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.0.remove_breakpoint(addr)
+    }
+
+    #[getter]
+    fn breakpoints(&self) -> Vec<u16> {
+        self.0.breakpoints().collect()
+    }
+}
+
+#[pymodule]
+fn lc3tools(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Program>()?;
+    m.add_class::<State>()?;
+    m.add_class::<Simulator>()?;
+
+    m.add_function(wrap_pyfunction!(assemble, m)?)?;
+    m.add_function(wrap_pyfunction!(assemble_file, m)?)?;
+
+    m.add("AssemblyError", m.py().get_type_bound::<AssemblyError>())?;
+    m.add("RunError", m.py().get_type_bound::<RunError>())?;
+
+    // `PRINT_NONE`, `PRINT_FATAL_ERROR`, ...; like `LC3CORE_PRINT_*` in the
+    // shared library's header.
+    for level in PrintType::VARIANTS {
+        let name = level.cpp_name().trim_start_matches("P_");
+        m.add(format!("PRINT_{}", name).as_str(), i32::from(*level))?;
+    }
+
+    Ok(())
+}
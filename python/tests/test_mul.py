@@ -0,0 +1,64 @@
+"""The multiplication program from `examples/mul.rs`, from Python."""
+
+import pytest
+
+import lc3tools
+
+MUL = """
+.ORIG x3000
+        BRnzp START
+
+; Calculates a * b
+A       .FILL #{a}
+B       .FILL #{b}
+
+START   AND R0, R0, #0  ; R0 as acc
+        LD R1, A        ; R1 as inc
+        LD R2, B        ; R2 as count
+
+LOOP    BRz END
+        ADD R0, R0, R1
+        ADD R2, R2, #-1
+        BRnzp LOOP
+
+END     ST R0, RES
+        HALT
+
+RES     .FILL #0
+.END
+"""
+
+
+def to_signed(n):
+    """The assembler wants `.FILL` values that fit in an `i16`."""
+    return n - 0x10000 if n >= 0x8000 else n
+
+
+@pytest.mark.parametrize(
+    "a, b",
+    [(0, 0), (0, 8), (9, 0), (1, 1), (1, 50), (30, 50), (6, 7), (1, 65535)],
+)
+def test_mul(a, b):
+    prog = lc3tools.assemble(MUL.format(a=to_signed(a), b=to_signed(b)))
+
+    sim = lc3tools.Simulator()
+    sim.load(prog)
+    sim.run(prog.symbol("START"))
+
+    assert sim.mem(prog.symbol("RES")) == a * b
+    assert sim.halted
+
+
+def test_mul_instruction_limit():
+    prog = lc3tools.assemble(MUL.format(a=1, b=to_signed(65535)))
+
+    sim = lc3tools.Simulator(instruction_limit=1000)
+    sim.load(prog)
+
+    with pytest.raises(lc3tools.RunError) as err:
+        sim.run(prog.symbol("START"))
+
+    assert err.value.exceeded_instruction_limit
+    assert err.value.seed is None
+    assert sim.instructions_executed >= 1000
+    assert sim.mem(prog.symbol("RES")) == 0
@@ -0,0 +1,172 @@
+import pytest
+
+import lc3tools
+
+HELLO = """
+.ORIG x3000
+        LEA R0, MSG
+        PUTS
+        HALT
+MSG     .STRINGZ "Hello, world!"
+.END
+"""
+
+ECHO = """
+.ORIG x3000
+        GETC
+        OUT
+        GETC
+        OUT
+        HALT
+.END
+"""
+
+ADD = """
+.ORIG x3000
+        ADD R1, R0, #5
+        ADD R2, R1, R1
+        HALT
+.END
+"""
+
+
+def test_assemble():
+    prog = lc3tools.assemble(HELLO)
+
+    assert prog.symbols == {"MSG": 0x3003}
+    assert prog.symbol("msg") == 0x3003
+    assert prog.words[0][0] == 0x3000
+    assert len(prog) == 3 + len("Hello, world!") + 1
+
+    with pytest.raises(KeyError):
+        prog.symbol("NOPE")
+
+
+def test_assembly_error():
+    with pytest.raises(lc3tools.AssemblyError):
+        lc3tools.assemble(".ORIG x3000\nADD R9, R0, #1\n.END\n")
+
+
+def test_assemble_file(tmp_path):
+    path = tmp_path / "hello.asm"
+    path.write_text(HELLO)
+
+    prog = lc3tools.assemble_file(path)
+    assert prog.words == lc3tools.assemble(HELLO).words
+    assert not (tmp_path / "hello.obj").exists()
+
+    prog = lc3tools.assemble_file(path, write_obj=True)
+    obj = lc3tools.Program.from_obj_file(tmp_path / "hello.obj")
+
+    assert prog.words == obj.words
+
+
+def test_output():
+    sim = lc3tools.Simulator()
+    sim.load(lc3tools.assemble(HELLO))
+    sim.run(0x3000)
+
+    assert sim.output.startswith("Hello, world!")
+    assert sim.output_bytes.startswith(b"Hello, world!")
+
+    sim.clear_output()
+    assert sim.output == ""
+
+
+def test_input():
+    sim = lc3tools.Simulator(instruction_limit=100000)
+    sim.load(lc3tools.assemble(ECHO))
+    sim.input(b"hi")
+    sim.run(0x3000)
+
+    assert sim.output.startswith("hi")
+
+
+def test_scheduled_input():
+    sim = lc3tools.Simulator(instruction_limit=100000)
+    sim.load(lc3tools.assemble(ECHO))
+    sim.schedule_input(0, b"a")
+    sim.schedule_input(500, b"b")
+    sim.run(0x3000)
+
+    assert sim.output.startswith("ab")
+    assert sim.instructions_executed > 500
+
+
+def test_registers_and_memory():
+    sim = lc3tools.Simulator()
+    sim.load(lc3tools.assemble(ADD))
+    sim.set_reg(0, 10)
+    sim.set_mem(0x4000, 0xBEEF)
+
+    state = sim.run(0x3000)
+
+    assert state.regs[1] == 15
+    assert sim.regs[2] == 30
+    assert sim.mem(0x4000) == 0xBEEF
+
+    with pytest.raises(ValueError):
+        sim.set_reg(8, 0)
+
+
+def test_stepping():
+    sim = lc3tools.Simulator()
+    sim.load(lc3tools.assemble(ADD))
+    sim.set_reg(0, 1)
+    sim.pc = 0x3000
+
+    state = sim.step_in()
+    assert state.pc == 0x3001
+    assert state.regs[1] == 6
+    assert state.cc == "P"
+
+
+def test_breakpoints():
+    sim = lc3tools.Simulator()
+    sim.load(lc3tools.assemble(ADD))
+
+    assert sim.set_breakpoint(0x3001)
+    assert not sim.set_breakpoint(0x3001)
+    assert sim.breakpoints == [0x3001]
+
+    state = sim.run(0x3000)
+    assert state.pc == 0x3001
+    assert not sim.halted
+
+    assert sim.remove_breakpoint(0x3001)
+    sim.resume()
+    assert sim.halted
+
+
+def test_randomize():
+    prog = lc3tools.assemble(ADD)
+
+    a = lc3tools.Simulator(seed=42)
+    b = lc3tools.Simulator(seed=42)
+    a.load(prog)
+    b.load(prog)
+
+    assert a.seed == 42
+    assert [a.mem(addr) for addr in range(0x4000, 0x4100)] == [
+        b.mem(addr) for addr in range(0x4000, 0x4100)
+    ]
+
+
+def test_run_error_reports_seed():
+    sim = lc3tools.Simulator(seed=7, instruction_limit=100)
+    sim.load(lc3tools.assemble(".ORIG x3000\nLOOP BRnzp LOOP\n.END\n"))
+
+    with pytest.raises(lc3tools.RunError) as err:
+        sim.run(0x3000)
+
+    assert err.value.seed == 7
+    assert err.value.exceeded_instruction_limit
+    assert err.value.state.pc == 0x3000
+    assert "0x7" in str(err.value)
+
+
+def test_print_level():
+    assert lc3tools.PRINT_NONE < lc3tools.PRINT_FATAL_ERROR
+
+    with pytest.raises(ValueError):
+        lc3tools.Simulator(print_level=-1)
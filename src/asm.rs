@@ -0,0 +1,713 @@
+//! A safe wrapper around the [C bindings](crate::root::assemble) for the
+//! `LC3Tools` assembler.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::enums::PrintType;
+use crate::root;
+
+/// A word in a [`Program`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Word {
+    /// Where the word goes in memory.
+    pub addr: u16,
+    /// The word itself.
+    pub value: u16,
+    /// The line of source the word came from (empty if unknown).
+    pub line: String,
+}
+
+/// An assembled program (or OS image).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Program {
+    /// The words in the program, in the order they were emitted.
+    pub words: Vec<Word>,
+    /// The program's labels.
+    pub symbols: SymbolTable,
+    /// Where in the source each word came from.
+    pub source_map: SourceMap,
+}
+
+impl Program {
+    /// Reads an `LC3Tools` object file.
+    pub fn from_obj_file(path: impl AsRef<Path>) -> Result<Self, AsmError> {
+        let path = c_path(path.as_ref())?;
+        let mut program = Program::default();
+
+        let read = unsafe {
+            root::read_obj_file(
+                path.as_ptr(),
+                &mut program as *mut Program as *mut c_void,
+                Some(push_word),
+            )
+        };
+
+        if read {
+            program.symbols = SymbolTable::from_words(&program.words);
+            Ok(program)
+        } else {
+            Err(AsmError::InvalidObjectFile)
+        }
+    }
+
+    /// The `(address, word)` pairs in this program.
+    ///
+    /// This is what [`Simulator::load_program`] takes.
+    ///
+    /// [`Simulator::load_program`]: crate::sim::Simulator::load_program
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.words.iter().map(|w| (w.addr, w.value))
+    }
+}
+
+impl FromIterator<(u16, u16)> for Program {
+    fn from_iter<I: IntoIterator<Item = (u16, u16)>>(iter: I) -> Self {
+        Program {
+            words: iter
+                .into_iter()
+                .map(|(addr, value)| Word {
+                    addr,
+                    value,
+                    line: String::new(),
+                })
+                .collect(),
+            ..Program::default()
+        }
+    }
+}
+
+unsafe extern "C" fn push_word(
+    ctx: *mut c_void,
+    addr: u16,
+    word: u16,
+    line: *const c_char,
+) {
+    let program = &mut *(ctx as *mut Program);
+    let line = if line.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(line).to_string_lossy().into_owned()
+    };
+
+    program.words.push(Word {
+        addr,
+        value: word,
+        line,
+    });
+}
+
+/// What the assembler's first passes found in some source (see
+/// [`analyze_asm`](crate::root::analyze_asm)).
+#[derive(Debug, Default)]
+struct Analysis {
+    symbols: SymbolTable,
+    /// The line each statement is on (from 0) and the address it starts at,
+    /// in source order.
+    statements: Vec<(u32, u16)>,
+}
+
+impl Analysis {
+    fn run(source: &str, liberal: bool) -> Option<Self> {
+        let mut analysis = Analysis::default();
+
+        let ok = unsafe {
+            root::analyze_asm(
+                source.len() as root::size_t,
+                source.as_ptr() as *const c_char,
+                liberal,
+                &mut analysis as *mut Analysis as *mut c_void,
+                Some(push_symbol),
+                Some(push_statement),
+            )
+        };
+
+        if ok {
+            Some(analysis)
+        } else {
+            None
+        }
+    }
+}
+
+unsafe extern "C" fn push_symbol(
+    ctx: *mut c_void,
+    name: *const c_char,
+    addr: u16,
+) {
+    let analysis = &mut *(ctx as *mut Analysis);
+    let name = CStr::from_ptr(name).to_string_lossy();
+
+    analysis.symbols.insert(&name, addr);
+}
+
+unsafe extern "C" fn push_statement(ctx: *mut c_void, row: u32, addr: u16) {
+    let analysis = &mut *(ctx as *mut Analysis);
+    analysis.statements.push((row, addr));
+}
+
+/// A program's labels and the addresses they refer to.
+///
+/// Like the assembler, lookups by name are case insensitive.
+///
+/// For assembled programs this is the assembler's own symbol table. Object
+/// files don't have one so it's rebuilt by running the assembler over the
+/// source lines the words carry; labels that sit on a line of their own are
+/// lost there.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SymbolTable {
+    /// Keyed by the upper case name.
+    by_name: BTreeMap<String, (String, u16)>,
+    by_addr: BTreeMap<u16, String>,
+}
+
+impl SymbolTable {
+    fn from_words(words: &[Word]) -> Self {
+        let mut symbols = SymbolTable::default();
+        let mut prev = None;
+
+        for word in words {
+            // Only the first word of a multi-word line (`.STRINGZ`, `.BLKW`)
+            // gets the label.
+            if prev != Some(&word.line) && !word.line.trim().is_empty() {
+                let line =
+                    format!(".ORIG x{:04X}\n{}\n.END\n", word.addr, word.line);
+                if let Some(analysis) = Analysis::run(&line, true) {
+                    for (name, addr) in analysis.symbols.iter() {
+                        symbols.insert(name, addr);
+                    }
+                }
+            }
+
+            prev = Some(&word.line);
+        }
+
+        symbols
+    }
+
+    fn insert(&mut self, name: &str, addr: u16) {
+        let _ = self.by_addr.entry(addr).or_insert_with(|| name.to_string());
+        let _ = self
+            .by_name
+            .insert(name.to_ascii_uppercase(), (name.to_string(), addr));
+    }
+
+    /// The address of a label.
+    pub fn get(&self, name: &str) -> Option<u16> {
+        self.by_name
+            .get(&name.to_ascii_uppercase())
+            .map(|&(_, a)| a)
+    }
+
+    /// The label at an address (the first one, if there are several).
+    pub fn name(&self, addr: u16) -> Option<&str> {
+        self.by_addr.get(&addr).map(|n| &**n)
+    }
+
+    /// Describes an address relative to the closest label at or before it
+    /// (i.e. `LOOP+2`); addresses more than `x100` past a label aren't
+    /// described.
+    pub fn describe(&self, addr: u16) -> Option<String> {
+        let (&base, name) = self.by_addr.range(..=addr).next_back()?;
+        match addr - base {
+            0 => Some(name.clone()),
+            off if off < 0x100 => Some(format!("{}+{}", name, off)),
+            _ => None,
+        }
+    }
+
+    /// The number of labels.
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    /// Whether there are no labels.
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// All the labels (as written in the source) with their addresses,
+    /// sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        let mut all = self
+            .by_name
+            .values()
+            .map(|(n, a)| (&**n, *a))
+            .collect::<Vec<_>>();
+        all.sort_by_key(|&(_, a)| a);
+
+        all.into_iter()
+    }
+}
+
+impl Index<&str> for SymbolTable {
+    type Output = u16;
+
+    /// The address of a label.
+    ///
+    /// # Panics
+    /// If there's no such label.
+    fn index(&self, name: &str) -> &u16 {
+        match self.by_name.get(&name.to_ascii_uppercase()) {
+            Some((_, addr)) => addr,
+            None => panic!("no such label: `{}`", name),
+        }
+    }
+}
+
+/// Maps the words in a program to the lines of source they came from and
+/// back.
+///
+/// This is only filled in for programs assembled by an [`Assembler`]; object
+/// files don't record line numbers.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SourceMap {
+    file: Option<PathBuf>,
+    /// Address to line number (1-based).
+    lines: BTreeMap<u16, usize>,
+    /// Line number to the first address the line produced.
+    addrs: BTreeMap<usize, u16>,
+}
+
+impl SourceMap {
+    /// The file the program was assembled from (`None` for programs assembled
+    /// from a string).
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// The line (1-based) the word at an address came from.
+    pub fn line(&self, addr: u16) -> Option<usize> {
+        self.lines.get(&addr).copied()
+    }
+
+    /// The address of the first word a line produced.
+    pub fn addr(&self, line: usize) -> Option<u16> {
+        self.addrs.get(&line).copied()
+    }
+
+    /// The first line at or after `line` that produced any words, with the
+    /// address of its first word.
+    ///
+    /// This is handy for breakpoints, which people like to put on comments
+    /// and labels.
+    pub fn next_addr(&self, line: usize) -> Option<(usize, u16)> {
+        self.addrs.range(line..).next().map(|(&l, &a)| (l, a))
+    }
+
+    /// Every address with the line it came from, sorted by address.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, usize)> + '_ {
+        self.lines.iter().map(|(&a, &l)| (a, l))
+    }
+}
+
+impl SourceMap {
+    /// Works out which line each word came from using the statements the
+    /// assembler found: a word belongs to the statement that starts at or
+    /// most closely before it. Statements that start at the same address
+    /// (i.e. a label on a line of its own and the instruction after it) go
+    /// to the last of them.
+    fn new(
+        words: &[Word],
+        statements: &[(u32, u16)],
+        file: Option<&Path>,
+    ) -> Self {
+        let mut starts = BTreeMap::new();
+        for &(row, addr) in statements {
+            let _ = starts.insert(addr, row as usize + 1);
+        }
+
+        let mut map = SourceMap {
+            file: file.map(Path::to_path_buf),
+            ..SourceMap::default()
+        };
+
+        for word in words {
+            let start = starts.range(..=word.addr).next_back();
+            if let Some((&start, &line)) = start {
+                let _ = map.lines.insert(word.addr, line);
+                if start == word.addr {
+                    let _ = map.addrs.insert(line, start);
+                }
+            }
+        }
+
+        map
+    }
+}
+
+/// Things that can go wrong when assembling a program or reading an object
+/// file.
+#[derive(Debug)]
+pub enum AsmError {
+    /// The assembler rejected the program; this has the diagnostics it
+    /// printed.
+    Failed(String),
+    /// The object file was missing or malformed.
+    InvalidObjectFile,
+    /// A path that wasn't valid UTF-8 or had a NUL byte in it.
+    InvalidPath(PathBuf),
+    /// Something went wrong while shuffling files around.
+    Io(io::Error),
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AsmError::*;
+        match self {
+            Failed(diag) => write!(f, "assembly failed:\n{}", diag),
+            InvalidObjectFile => write!(f, "invalid object file"),
+            InvalidPath(p) => write!(f, "unsupported path: `{}`", p.display()),
+            Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl Error for AsmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AsmError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AsmError {
+    fn from(err: io::Error) -> Self {
+        AsmError::Io(err)
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString, AsmError> {
+    path.to_str()
+        .and_then(|p| CString::new(p).ok())
+        .ok_or_else(|| AsmError::InvalidPath(path.to_path_buf()))
+}
+
+/// A directory under the system's temp directory that's removed on drop.
+#[derive(Debug)]
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "lc3tools-sys-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+
+        fs::create_dir_all(&dir)?;
+        Ok(TempDir(dir))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// How large a buffer we give the assembler for its diagnostics.
+const DIAGNOSTICS_BUFFER_LEN: usize = 64 * 1024;
+
+/// The `LC3Tools` assembler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assembler {
+    print_level: PrintType,
+    liberal: bool,
+    #[cfg(feature = "frontend")]
+    console: bool,
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Assembler {
+    /// An assembler that reports errors and warnings and doesn't use liberal
+    /// mode.
+    pub fn new() -> Self {
+        Self {
+            print_level: PrintType::Warning,
+            liberal: false,
+            #[cfg(feature = "frontend")]
+            console: false,
+        }
+    }
+
+    /// Sets what the assembler reports.
+    pub fn print_level(mut self, print_level: PrintType) -> Self {
+        self.print_level = print_level;
+        self
+    }
+
+    /// Enables or disables liberal mode, which relaxes some of the
+    /// assembler's checks.
+    pub fn liberal(mut self, liberal: bool) -> Self {
+        self.liberal = liberal;
+        self
+    }
+
+    /// Prints diagnostics to the console (`lc3::ConsolePrinter`) as they're
+    /// emitted instead of collecting them; [`AsmError::Failed`] will be empty.
+    #[cfg(feature = "frontend")]
+    #[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "frontend")))]
+    pub fn console(mut self, console: bool) -> Self {
+        self.console = console;
+        self
+    }
+
+    fn printer(&self, buffer: &mut [u8]) -> *mut root::lc3::utils::IPrinter {
+        #[cfg(feature = "frontend")]
+        {
+            if self.console {
+                return unsafe { root::console_printer() };
+            }
+        }
+
+        unsafe {
+            root::buffer_printer(
+                buffer.len() as root::size_t,
+                buffer.as_mut_ptr(),
+            )
+        }
+    }
+
+    /// Assembles some LC-3 assembly.
+    pub fn assemble(&self, source: &str) -> Result<Program, AsmError> {
+        self.assemble_in_temp_dir(Path::new("program.asm"), source, None)
+    }
+
+    /// Assembles the file at the given path.
+    ///
+    /// Unlike the `LC3Tools` assembler this doesn't write an object file;
+    /// see [`assemble_file_to_obj`](Assembler::assemble_file_to_obj) for
+    /// that.
+    pub fn assemble_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Program, AsmError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| AsmError::InvalidPath(path.to_path_buf()))?;
+
+        self.assemble_in_temp_dir(Path::new(name), &source, Some(path))
+    }
+
+    /// Assembles the file at the given path and, like the `LC3Tools`
+    /// assembler, writes an object file next to it (i.e. `foo.asm` →
+    /// `foo.obj`).
+    pub fn assemble_file_to_obj(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Program, AsmError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+
+        self.assemble_source(path, &source, Some(path))
+    }
+
+    /// The assembler only works on files (and writes an object file next to
+    /// them) so everything but [`assemble_file_to_obj`] goes through a
+    /// temporary directory; `name` is what the file is called there.
+    ///
+    /// [`assemble_file_to_obj`]: Assembler::assemble_file_to_obj
+    fn assemble_in_temp_dir(
+        &self,
+        name: &Path,
+        source: &str,
+        file: Option<&Path>,
+    ) -> Result<Program, AsmError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join(name);
+        fs::write(&path, source)?;
+
+        self.assemble_source(&path, source, file)
+    }
+
+    /// Assembles `path` (which holds `source`); `file` is what goes in the
+    /// [`SourceMap`].
+    fn assemble_source(
+        &self,
+        path: &Path,
+        source: &str,
+        file: Option<&Path>,
+    ) -> Result<Program, AsmError> {
+        let path = c_path(path)?;
+        let mut program = Program::default();
+        let mut diagnostics = vec![0u8; DIAGNOSTICS_BUFFER_LEN];
+
+        let printer = self.printer(&mut diagnostics);
+        let succeeded = unsafe {
+            let res = root::assemble(
+                printer,
+                self.print_level.into(),
+                path.as_ptr(),
+                self.liberal,
+                &mut program as *mut Program as *mut c_void,
+                Some(push_word),
+            );
+
+            root::free_printer(printer);
+            res
+        };
+
+        if succeeded {
+            // The source just assembled so this shouldn't fail.
+            let analysis =
+                Analysis::run(source, self.liberal).unwrap_or_default();
+
+            program.source_map =
+                SourceMap::new(&program.words, &analysis.statements, file);
+            program.symbols = analysis.symbols;
+
+            Ok(program)
+        } else {
+            let len = diagnostics
+                .iter()
+                .position(|b| *b == 0)
+                .unwrap_or(diagnostics.len());
+            diagnostics.truncate(len);
+
+            Err(AsmError::Failed(
+                String::from_utf8_lossy(&diagnostics).into_owned(),
+            ))
+        }
+    }
+}
+
+/// Assembles some LC-3 assembly with the default [`Assembler`] settings.
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    Assembler::new().assemble(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(addrs: &[u16]) -> Vec<Word> {
+        addrs
+            .iter()
+            .map(|&addr| Word {
+                addr,
+                value: 0,
+                line: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn source_map() {
+        // .ORIG x3000        ; 0
+        // LOOP               ; 1
+        //     ADD R1, R1, #1 ; 2
+        //     BRnzp LOOP     ; 3
+        // MSG .STRINGZ "hi"  ; 4
+        // .END               ; 5
+        let statements = [
+            (0, 0x3000),
+            (1, 0x3000),
+            (2, 0x3000),
+            (3, 0x3001),
+            (4, 0x3002),
+            (5, 0x3005),
+        ];
+        let map = SourceMap::new(
+            &words(&[0x3000, 0x3001, 0x3002, 0x3003, 0x3004]),
+            &statements,
+            Some(Path::new("loop.asm")),
+        );
+
+        assert_eq!(map.file(), Some(Path::new("loop.asm")));
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [
+                (0x3000, 3),
+                (0x3001, 4),
+                (0x3002, 5),
+                (0x3003, 5),
+                (0x3004, 5)
+            ]
+        );
+
+        assert_eq!(map.addr(1), None);
+        assert_eq!(map.addr(2), None);
+        assert_eq!(map.addr(3), Some(0x3000));
+        assert_eq!(map.addr(5), Some(0x3002));
+        assert_eq!(map.addr(6), None);
+        assert_eq!(map.next_addr(1), Some((3, 0x3000)));
+        assert_eq!(map.next_addr(6), None);
+    }
+
+    #[test]
+    fn source_map_sections() {
+        // .ORIG x4000 ; 0
+        //     HALT    ; 1
+        // .END        ; 2
+        // .ORIG x3000 ; 3
+        //     ADD ... ; 4
+        //     HALT    ; 5
+        // .END        ; 6
+        let statements = [
+            (0, 0x4000),
+            (1, 0x4000),
+            (2, 0x4001),
+            (3, 0x3000),
+            (4, 0x3000),
+            (5, 0x3001),
+            (6, 0x3002),
+        ];
+        let map = SourceMap::new(
+            &words(&[0x4000, 0x3000, 0x3001]),
+            &statements,
+            None,
+        );
+
+        assert_eq!(map.file(), None);
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [(0x3000, 5), (0x3001, 6), (0x4000, 2)]
+        );
+        assert_eq!(map.next_addr(3), Some((5, 0x3000)));
+    }
+
+    #[test]
+    fn symbol_table() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert("Loop", 0x3000);
+        symbols.insert("Start", 0x3000);
+        symbols.insert("MSG", 0x3002);
+
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols.get("LOOP"), Some(0x3000));
+        assert_eq!(symbols["msg"], 0x3002);
+        assert_eq!(symbols.get("nope"), None);
+        assert_eq!(symbols.name(0x3000), Some("Loop"));
+        assert_eq!(symbols.describe(0x3001).as_deref(), Some("Loop+1"));
+        assert_eq!(symbols.describe(0x2FFF), None);
+        assert_eq!(
+            symbols.iter().map(|(_, a)| a).collect::<Vec<_>>(),
+            [0x3000, 0x3000, 0x3002]
+        );
+    }
+}
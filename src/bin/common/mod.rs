@@ -0,0 +1,55 @@
+//! Bits shared by the binaries in this crate.
+
+#![allow(dead_code)] // Not every binary uses everything.
+
+use lc3tools_sys::root::State;
+
+/// Parses a number in LC-3 notation (`x3000`, `#12`), with a `0x` prefix or
+/// in decimal; negative numbers are allowed.
+pub fn parse_num(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (neg, s) = if s.starts_with('-') {
+        (true, &s[1..])
+    } else {
+        (false, s)
+    };
+
+    let n = if s.starts_with("0x") || s.starts_with("0X") {
+        i64::from_str_radix(&s[2..], 16).ok()
+    } else if s.starts_with('x') || s.starts_with('X') {
+        i64::from_str_radix(&s[1..], 16).ok()
+    } else if s.starts_with('#') {
+        s[1..].parse().ok()
+    } else {
+        s.parse().ok()
+    }?;
+
+    Some(if neg { -n } else { n })
+}
+
+/// Parses a word (see [`parse_num`]); negative numbers are stored in two's
+/// complement.
+pub fn parse_word(s: &str) -> Option<u16> {
+    match parse_num(s)? {
+        n if n >= i64::from(i16::min_value())
+            && n <= i64::from(u16::max_value()) =>
+        {
+            Some(n as u16)
+        }
+        _ => None,
+    }
+}
+
+/// Prints the registers, four to a line, followed by the PC, PSR, condition
+/// codes and MCR.
+pub fn dump_state(state: &State) {
+    for (i, reg) in state.regs.iter().enumerate() {
+        let sep = if i == 3 || i == 7 { "\n" } else { "  " };
+        print!("R{} x{:04X}{}", i, reg, sep);
+    }
+
+    println!(
+        "PC x{:04X}  PSR x{:04X}  CC {}  MCR x{:04X}",
+        state.pc, state.psr, state.cc as u8 as char, state.mcr
+    );
+}
@@ -0,0 +1,698 @@
+//! A [Debug Adapter Protocol][dap] server for LC-3 programs, for editors like
+//! VS Code.
+//!
+//! ```text
+//! lc3-dap
+//! ```
+//!
+//! Speaks DAP over stdin and stdout. The `launch` request takes:
+//!   - `program`: the assembly file to debug (required)
+//!   - `stopOnEntry`: whether to stop before the first instruction
+//!     (default: `false`)
+//!   - `input`: input for the program (default: none)
+//!   - `instructionLimit`: stop runs after this many instructions (default:
+//!     [`DEFAULT_INSTRUCTION_LIMIT`]; 0 for no limit); runs can't be paused so
+//!     this is the only way to stop a runaway program
+//!
+//! There's a single thread with a single stack frame (at the PC) that has one
+//! scope: the registers. Breakpoints on lines that don't produce any words
+//! move to the next line that does.
+//!
+//! Memory references are word addresses (`x3000`); `readMemory` offsets and
+//! counts are in bytes, with each word sent little endian.
+//!
+//! [dap]: https://microsoft.github.io/debug-adapter-protocol/
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use lc3tools_sys::asm::{Assembler, Program};
+use lc3tools_sys::grader::DEFAULT_INSTRUCTION_LIMIT;
+use lc3tools_sys::root::State;
+use lc3tools_sys::sim::{RunError, Simulator};
+use serde_json::{json, Value};
+
+mod common;
+use common::parse_word;
+
+/// We only have one thread.
+const THREAD_ID: u64 = 1;
+const REGISTERS_REF: u64 = 1;
+
+/// Reads and writes DAP messages (`Content-Length` framed JSON).
+struct Transport<R, W> {
+    input: R,
+    output: W,
+    seq: u64,
+}
+
+impl<R: BufRead, W: Write> Transport<R, W> {
+    /// Reads the next message; `None` at the end of the input.
+    fn read(&mut self) -> Result<Option<Value>, Box<dyn Error>> {
+        let mut len = None;
+        loop {
+            let mut header = String::new();
+            if self.input.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            let mut parts = header.splitn(2, ':');
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                if name.trim().eq_ignore_ascii_case("Content-Length") {
+                    len = Some(value.trim().parse::<usize>()?);
+                }
+            }
+        }
+
+        let len = len.ok_or("message without a `Content-Length` header")?;
+        let mut body = vec![0; len];
+        self.input.read_exact(&mut body)?;
+
+        Ok(Some(serde_json::from_slice(&body)?))
+    }
+
+    fn send(&mut self, mut msg: Value) -> io::Result<()> {
+        self.seq += 1;
+        if let Value::Object(map) = &mut msg {
+            let _ = map.insert("seq".to_string(), self.seq.into());
+        }
+
+        let body = serde_json::to_vec(&msg)?;
+        write!(self.output, "Content-Length: {}\r\n\r\n", body.len())?;
+        self.output.write_all(&body)?;
+        self.output.flush()
+    }
+
+    fn respond(
+        &mut self,
+        req: &Value,
+        res: Result<Value, String>,
+    ) -> io::Result<()> {
+        let (success, body, message) = match res {
+            Ok(body) => (true, body, Value::Null),
+            Err(msg) => (false, Value::Null, msg.into()),
+        };
+
+        self.send(json!({
+            "type": "response",
+            "request_seq": req["seq"],
+            "command": req["command"],
+            "success": success,
+            "message": message,
+            "body": body,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+}
+
+/// A launched program.
+struct Session {
+    sim: Simulator,
+    program: Program,
+    /// The program's source file (canonicalized, if possible).
+    source: PathBuf,
+}
+
+/// How the machine was last told to run; decides what we say when it stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Run {
+    Entry,
+    Continue,
+    StepOver,
+    StepIn,
+    StepOut,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n =
+            (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn word(value: u16) -> String {
+    format!("x{:04X} (#{})", value, value as i16)
+}
+
+impl Session {
+    fn launch(args: &Value) -> Result<Self, String> {
+        let path = args["program"]
+            .as_str()
+            .ok_or("`launch` needs a `program` (an assembly file)")?;
+        let program: Program = Assembler::new()
+            .assemble_file(path)
+            .map_err(|e| format!("couldn't assemble `{}`: {}", path, e))?;
+
+        let input = args["input"].as_str().unwrap_or("");
+        let limit = args["instructionLimit"]
+            .as_u64()
+            .unwrap_or(DEFAULT_INSTRUCTION_LIMIT);
+        let mut sim = Simulator::builder()
+            .input_after(0, input)
+            .instruction_limit(limit)
+            .build();
+
+        sim.load_program(program.iter());
+        sim.set_pc(program.words.first().map(|w| w.addr).unwrap_or(0x3000));
+
+        Ok(Self {
+            sim,
+            program,
+            source: canonical(Path::new(path)),
+        })
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let path = args["source"]["path"].as_str().map(Path::new);
+        let ours = path.map_or(false, |p| canonical(p) == self.source);
+
+        if ours {
+            let all: Vec<u16> = self.sim.breakpoints().collect();
+            for addr in all {
+                let _ = self.sim.remove_breakpoint(addr);
+            }
+        }
+
+        let requested = args["breakpoints"].as_array().cloned();
+        let breakpoints: Vec<Value> = requested
+            .unwrap_or_default()
+            .iter()
+            .map(|bp| {
+                let line = bp["line"].as_u64().unwrap_or(0) as usize;
+                match self.program.source_map.next_addr(line).filter(|_| ours) {
+                    Some((line, addr)) => {
+                        let _ = self.sim.set_breakpoint(addr);
+                        json!({ "verified": true, "line": line })
+                    }
+                    None => json!({
+                        "verified": false,
+                        "line": line,
+                        "message": "no code on or after this line",
+                    }),
+                }
+            })
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn stack_trace(&self) -> Value {
+        let pc = self.sim.state().pc as u16;
+        let name = self
+            .program
+            .symbols
+            .describe(pc)
+            .unwrap_or_else(|| format!("x{:04X}", pc));
+
+        let mut frame = json!({
+            "id": 1,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("x{:04X}", pc),
+        });
+        if let (Some(line), Value::Object(frame)) =
+            (self.program.source_map.line(pc), &mut frame)
+        {
+            let source = json!({
+                "name": self.source.file_name().map(|n| n.to_string_lossy()),
+                "path": self.source.to_string_lossy(),
+            });
+
+            let _ = frame.insert("source".to_string(), source);
+            let _ = frame.insert("line".to_string(), (line as u64).into());
+            let _ = frame.insert("column".to_string(), 1u64.into());
+        }
+
+        json!({ "stackFrames": [frame], "totalFrames": 1 })
+    }
+
+    fn variables(&self) -> Value {
+        let state = self.sim.state();
+        let reg = |name: String, value: u16| {
+            json!({
+                "name": name,
+                "value": word(value),
+                "variablesReference": 0,
+                "memoryReference": format!("x{:04X}", value),
+            })
+        };
+
+        let mut vars: Vec<Value> = state
+            .regs
+            .iter()
+            .enumerate()
+            .map(|(i, &r)| reg(format!("R{}", i), r as u16))
+            .collect();
+
+        let pc = state.pc as u16;
+        let pc_label = match self.program.symbols.describe(pc) {
+            Some(label) => format!("x{:04X} <{}>", pc, label),
+            None => format!("x{:04X}", pc),
+        };
+        vars.push(json!({
+            "name": "PC",
+            "value": pc_label,
+            "variablesReference": 0,
+            "memoryReference": format!("x{:04X}", pc),
+        }));
+        vars.push(json!({
+            "name": "PSR",
+            "value": format!("x{:04X}", state.psr),
+            "variablesReference": 0,
+        }));
+        vars.push(json!({
+            "name": "CC",
+            "value": (state.cc as u8 as char).to_string(),
+            "variablesReference": 0,
+        }));
+
+        json!({ "variables": vars })
+    }
+
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let base = args["memoryReference"]
+            .as_str()
+            .and_then(parse_word)
+            .ok_or("invalid memory reference")?;
+        let offset = args["offset"].as_i64().unwrap_or(0);
+        let count = args["count"].as_u64().unwrap_or(0).min(0x2_0000);
+
+        // Byte addresses, two to a word.
+        let start = i64::from(base) * 2 + offset;
+        let bytes: Vec<u8> = (start..start + count as i64)
+            .map(|b| {
+                let word = self.sim.mem((b.rem_euclid(0x2_0000) / 2) as u16);
+                word.to_le_bytes()[b.rem_euclid(2) as usize]
+            })
+            .collect();
+
+        let address = (start.rem_euclid(0x2_0000) / 2) as u16;
+        Ok(json!({
+            "address": format!("x{:04X}", address),
+            "data": base64(&bytes),
+        }))
+    }
+
+    fn evaluate(&self, args: &Value) -> Result<Value, String> {
+        let expr = args["expression"].as_str().unwrap_or("").trim();
+        let state = self.sim.state();
+        let upper = expr.to_ascii_uppercase();
+
+        let (result, memory) = match &*upper {
+            "PC" => (word(state.pc as u16), Some(state.pc as u16)),
+            "PSR" => (format!("x{:04X}", state.psr), None),
+            r if r.len() == 2 && r.starts_with('R') => {
+                match r[1..].parse::<usize>().ok().filter(|&r| r < 8) {
+                    Some(r) => {
+                        let value = state.regs[r] as u16;
+                        (word(value), Some(value))
+                    }
+                    None => {
+                        return Err(format!("unknown register: `{}`", expr))
+                    }
+                }
+            }
+            _ => {
+                let addr = self
+                    .program
+                    .symbols
+                    .get(expr)
+                    .or_else(|| parse_word(expr))
+                    .ok_or_else(|| {
+                        format!("`{}` isn't a register, label or address", expr)
+                    })?;
+
+                let value =
+                    format!("[x{:04X}] = {}", addr, word(self.sim.mem(addr)));
+                (value, Some(addr))
+            }
+        };
+
+        Ok(json!({
+            "result": result,
+            "variablesReference": 0,
+            "memoryReference": memory.map(|m| format!("x{:04X}", m)),
+        }))
+    }
+}
+
+struct Server<R, W> {
+    transport: Transport<R, W>,
+    session: Option<Session>,
+    stop_on_entry: bool,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    fn session(&mut self) -> Result<&mut Session, String> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| "nothing's running".to_string())
+    }
+
+    /// Runs the machine (after the request has been answered) and reports
+    /// where it stopped.
+    fn run(&mut self, how: Run) -> io::Result<()> {
+        let session = match self.session.as_mut() {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        let res: Result<State, RunError> = if session.sim.halted() {
+            Ok(session.sim.state())
+        } else {
+            match how {
+                Run::Entry => Ok(session.sim.state()),
+                Run::Continue => session.sim.resume(),
+                Run::StepOver => session.sim.step_over(),
+                Run::StepIn => session.sim.step_in(),
+                Run::StepOut => session.sim.step_out(),
+            }
+        };
+
+        let output = session.sim.output().into_owned();
+        session.sim.clear_output();
+        let halted = session.sim.halted();
+        let pc = session.sim.state().pc as u16;
+        let at_breakpoint = session.sim.breakpoints().any(|b| b == pc);
+
+        if !output.is_empty() {
+            self.transport.event(
+                "output",
+                json!({ "category": "stdout", "output": output }),
+            )?;
+        }
+
+        let (reason, text) = match res {
+            Err(err) => ("exception", Some(err.to_string())),
+            Ok(_) if halted => {
+                self.transport.event("exited", json!({ "exitCode": 0 }))?;
+                return self.transport.event("terminated", json!({}));
+            }
+            Ok(_) if how == Run::Entry => ("entry", None),
+            Ok(_) if at_breakpoint && how != Run::StepIn => {
+                ("breakpoint", None)
+            }
+            Ok(_) => ("step", None),
+        };
+
+        self.transport.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": text,
+                "text": text,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )
+    }
+
+    /// Handles a request; returns `false` once the client disconnects.
+    fn handle(&mut self, req: &Value) -> io::Result<bool> {
+        let args = &req["arguments"];
+        let command = req["command"].as_str().unwrap_or("");
+
+        let mut then = None;
+        let res = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsReadMemoryRequest": true,
+                "supportsEvaluateForHovers": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => Session::launch(args).map(|session| {
+                self.session = Some(session);
+                self.stop_on_entry =
+                    args["stopOnEntry"].as_bool().unwrap_or(false);
+                json!({})
+            }),
+            "setBreakpoints" => self.session().map(|s| s.set_breakpoints(args)),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => {
+                then = Some(if self.stop_on_entry {
+                    Run::Entry
+                } else {
+                    Run::Continue
+                });
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({
+                "threads": [{ "id": THREAD_ID, "name": "LC-3" }],
+            })),
+            "stackTrace" => self.session().map(|s| s.stack_trace()),
+            "scopes" => Ok(json!({
+                "scopes": [{
+                    "name": "Registers",
+                    "variablesReference": REGISTERS_REF,
+                    "expensive": false,
+                }],
+            })),
+            "variables" => match args["variablesReference"].as_u64() {
+                Some(REGISTERS_REF) => self.session().map(|s| s.variables()),
+                _ => Ok(json!({ "variables": [] })),
+            },
+            "readMemory" => self.session().and_then(|s| s.read_memory(args)),
+            "evaluate" => self.session().and_then(|s| s.evaluate(args)),
+            "continue" | "next" | "stepIn" | "stepOut"
+                if self.session.is_none() =>
+            {
+                Err("nothing's running".to_string())
+            }
+            "continue" => {
+                then = Some(Run::Continue);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                then = Some(match command {
+                    "next" => Run::StepOver,
+                    "stepIn" => Run::StepIn,
+                    _ => Run::StepOut,
+                });
+                Ok(json!({}))
+            }
+            "pause" => Err("the simulator can't be paused".to_string()),
+            "terminate" => {
+                self.transport.respond(req, Ok(json!({})))?;
+                self.transport.event("terminated", json!({}))?;
+                return Ok(true);
+            }
+            "disconnect" => {
+                self.transport.respond(req, Ok(json!({})))?;
+                return Ok(false);
+            }
+            other => Err(format!("unsupported request: `{}`", other)),
+        };
+
+        let ok = res.is_ok();
+        self.transport.respond(req, res)?;
+
+        if command == "launch" && ok {
+            self.transport.event("initialized", json!({}))?;
+        }
+        if let Some(how) = then.filter(|_| ok) {
+            self.run(how)?;
+        }
+
+        Ok(true)
+    }
+}
+
+fn main() {
+    if std::env::args_os().nth(1).is_some() {
+        eprintln!(
+            "usage: lc3-dap\n\n\
+            Speaks the Debug Adapter Protocol over stdin and stdout."
+        );
+        process::exit(2);
+    }
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut server = Server {
+        transport: Transport {
+            input: stdin.lock(),
+            output: stdout.lock(),
+            seq: 0,
+        },
+        session: None,
+        stop_on_entry: false,
+    };
+
+    loop {
+        let req = match server.transport.read() {
+            Ok(Some(req)) => req,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        };
+
+        match server.handle(&req) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn transport(input: &[u8]) -> Transport<Cursor<Vec<u8>>, Vec<u8>> {
+        Transport {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+            seq: 0,
+        }
+    }
+
+    #[test]
+    fn base64_padding() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+            (&[0x00], "AA=="),
+            (&[0xFF, 0xFF], "//8="),
+            (&[0xFB, 0xEF, 0xBE], "++++"),
+        ];
+
+        for &(bytes, expected) in cases {
+            assert_eq!(base64(bytes), expected, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn reading_messages() {
+        let mut t = transport(
+            b"Content-Length: 9\r\n\r\n{\"seq\":1}\
+            content-length:10\r\nContent-Type: json\r\n\r\n{\"seq\": 2}",
+        );
+
+        assert_eq!(t.read().unwrap(), Some(json!({ "seq": 1 })));
+        assert_eq!(t.read().unwrap(), Some(json!({ "seq": 2 })));
+        assert_eq!(t.read().unwrap(), None);
+
+        assert!(transport(b"Content-Type: json\r\n\r\n{}").read().is_err());
+        assert!(transport(b"Content-Length: 10\r\n\r\n{}").read().is_err());
+    }
+
+    #[test]
+    fn sending_messages() {
+        let mut t = transport(b"");
+        t.event("output", json!({ "output": "é" })).unwrap();
+        t.event("terminated", json!({})).unwrap();
+
+        let mut sent = transport(&t.output);
+        let first = sent.read().unwrap().unwrap();
+        assert_eq!(first["seq"], 1);
+        assert_eq!(first["body"]["output"], "é");
+        assert_eq!(sent.read().unwrap().unwrap()["seq"], 2);
+        assert_eq!(sent.read().unwrap(), None);
+    }
+
+    #[test]
+    fn breakpoint_lines() {
+        let path = std::env::temp_dir()
+            .join(format!("lc3-dap-breakpoints-{}.asm", process::id()));
+        fs::write(
+            &path,
+            "; a comment\n\
+            .ORIG x3000\n\
+            AND R0, R0, #0\n\
+            \n\
+            LOOP\n\
+            ADD R0, R0, #1\n\
+            HALT\n\
+            .END\n",
+        )
+        .unwrap();
+
+        let mut session = Session::launch(&json!({
+            "program": path.to_str().unwrap(),
+        }))
+        .unwrap();
+        let set = |session: &mut Session, path: &Path, lines: &[u64]| {
+            let lines: Vec<Value> =
+                lines.iter().map(|l| json!({ "line": l })).collect();
+            session.set_breakpoints(&json!({
+                "source": { "path": path.to_str().unwrap() },
+                "breakpoints": lines,
+            }))["breakpoints"]
+                .clone()
+        };
+
+        let bps = set(&mut session, &path, &[1, 3, 4, 6, 9]);
+        let lines: Vec<_> = bps.as_array().unwrap().iter().collect();
+        assert_eq!(lines[0], &json!({ "verified": true, "line": 3 }));
+        assert_eq!(lines[1], &json!({ "verified": true, "line": 3 }));
+        assert_eq!(lines[2], &json!({ "verified": true, "line": 6 }));
+        assert_eq!(lines[3], &json!({ "verified": true, "line": 6 }));
+        assert_eq!(lines[4]["verified"], false);
+        assert_eq!(
+            session.sim.breakpoints().collect::<Vec<_>>(),
+            [0x3000, 0x3001]
+        );
+
+        // Setting breakpoints replaces the old ones...
+        let _ = set(&mut session, &path, &[7]);
+        assert_eq!(session.sim.breakpoints().collect::<Vec<_>>(), [0x3002]);
+
+        // ...but not when they're for some other file.
+        let other = set(&mut session, Path::new("other.asm"), &[3]);
+        assert_eq!(other[0]["verified"], false);
+        assert_eq!(session.sim.breakpoints().collect::<Vec<_>>(), [0x3002]);
+
+        let _ = fs::remove_file(&path);
+    }
+}
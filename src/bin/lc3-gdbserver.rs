@@ -0,0 +1,167 @@
+//! Serves an LC-3 program to a debugger over the GDB remote serial protocol.
+//!
+//! ```text
+//! lc3-gdbserver [--listen <host:port> | --unix <path>] [options] <program>
+//! ```
+//!
+//! Serves a single connection and then exits. See the `gdb` module for what
+//! the stub supports.
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use lc3tools_sys::asm::{Assembler, Program};
+use lc3tools_sys::gdb::GdbStub;
+use lc3tools_sys::sim::SimulatorBuilder;
+
+const USAGE: &str = "\
+usage: lc3-gdbserver [--listen <host:port> | --unix <path>] [options] <program>
+
+  --listen <host:port>  listen on a TCP port (default: 127.0.0.1:1234)
+  --unix <path>         listen on a Unix socket instead
+  --input <file>        read the program's input from a file instead of the
+                        console
+  --limit <n>           stop runs after `n` instructions
+";
+
+#[derive(Debug)]
+enum Listen {
+    Tcp(String),
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Unix(PathBuf),
+}
+
+#[derive(Debug)]
+struct Args {
+    listen: Listen,
+    input: Option<PathBuf>,
+    limit: u64,
+    program: PathBuf,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args_os().skip(1);
+    let mut listen = Listen::Tcp("127.0.0.1:1234".to_string());
+    let (mut input, mut limit, mut program) = (None, 0, None);
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .ok_or_else(|| format!("`{}` needs a value", flag))
+        };
+
+        match arg.to_str() {
+            Some("-h") | Some("--help") => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            Some("--listen") => {
+                listen = Listen::Tcp(
+                    value("--listen")?
+                        .into_string()
+                        .map_err(|a| format!("invalid address: {:?}", a))?,
+                )
+            }
+            Some("--unix") => listen = Listen::Unix(value("--unix")?.into()),
+            Some("--input") => input = Some(value("--input")?.into()),
+            Some("--limit") => {
+                let l = value("--limit")?;
+                limit = l
+                    .to_str()
+                    .and_then(|l| l.parse().ok())
+                    .ok_or_else(|| format!("invalid limit: {:?}", l))?;
+            }
+            Some(flag) if flag.starts_with('-') => {
+                return Err(format!("unknown flag: `{}`", flag))
+            }
+            _ => {
+                if program.replace(PathBuf::from(arg)).is_some() {
+                    return Err("expected one program".to_string());
+                }
+            }
+        }
+    }
+
+    match program {
+        Some(program) => Ok(Args {
+            listen,
+            input,
+            limit,
+            program,
+        }),
+        None => Err("expected a program".to_string()),
+    }
+}
+
+fn load_program(path: &Path) -> Result<Program, Box<dyn Error>> {
+    if path.extension() == Some(OsStr::new("obj")) {
+        Ok(Program::from_obj_file(path)?)
+    } else {
+        Ok(Assembler::new().assemble_file(path)?)
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let program = load_program(&args.program)?;
+    let input = args.input.as_ref().map(fs::read).transpose()?;
+
+    let mut builder = SimulatorBuilder::new()
+        .instruction_limit(args.limit)
+        .console_output(true)
+        .console_input(input.is_none());
+    if let Some(input) = input {
+        builder = builder.input_after(0, input);
+    }
+
+    let mut sim = builder.build();
+    sim.load_program(program.iter());
+    sim.set_pc(program.words.first().map(|w| w.addr).unwrap_or(0x3000));
+
+    let mut stub = GdbStub::new(&mut sim);
+    match args.listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(&addr)?;
+            eprintln!("listening on {}", listener.local_addr()?);
+
+            let (stream, peer) = listener.accept()?;
+            eprintln!("debugger connected from {}", peer);
+            stub.serve(stream)?;
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            eprintln!("listening on {}", path.display());
+
+            let (stream, _) = listener.accept()?;
+            eprintln!("debugger connected");
+            let res = stub.serve(stream);
+            let _ = fs::remove_file(&path);
+            res?;
+        }
+        #[cfg(not(unix))]
+        Listen::Unix(_) => {
+            return Err("Unix sockets aren't supported on this platform".into())
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprint!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,223 @@
+//! Grades an LC-3 program against a declarative test spec.
+//!
+//! ```text
+//! lc3-grade [--format text|json|junit] <program.asm|program.obj> <spec.toml|spec.yaml>
+//! ```
+//!
+//! See `spec.rs` for the spec format. Exits with 0 if every test case passed,
+//! 1 if any failed and 2 if something went wrong (i.e. the program didn't
+//! assemble or the spec is invalid).
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt::{self, Debug};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use lc3tools_sys::asm::{Assembler, Program};
+use lc3tools_sys::grader::{Grader, TestSuite};
+use lc3tools_sys::sim::Simulator;
+
+#[path = "../common/mod.rs"]
+mod common;
+mod spec;
+use spec::{Spec, Test};
+
+const USAGE: &str = "\
+usage: lc3-grade [--format text|json|junit] <program> <spec>
+
+  <program>  an assembly file or an LC3Tools object file (.obj)
+  <spec>     a test spec (.toml, .yaml or .yml)
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+    JUnit,
+}
+
+#[derive(Debug)]
+struct Args {
+    format: Format,
+    program: PathBuf,
+    spec: PathBuf,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut format = Format::Text;
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-h") | Some("--help") => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            Some("--format") | Some("-f") => {
+                format = match args.next().as_ref().and_then(|f| f.to_str()) {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    Some("junit") => Format::JUnit,
+                    other => {
+                        return Err(format!("unknown format: {:?}", other))
+                    }
+                }
+            }
+            Some(flag) if flag.starts_with('-') => {
+                return Err(format!("unknown flag: `{}`", flag))
+            }
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    match (positional.next(), positional.next(), positional.next()) {
+        (Some(program), Some(spec), None) => Ok(Args {
+            format,
+            program,
+            spec,
+        }),
+        _ => Err("expected a program and a spec".to_string()),
+    }
+}
+
+fn load_spec(path: &Path) -> Result<Spec, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+
+    match path.extension().and_then(OsStr::to_str) {
+        Some("toml") => Ok(toml::from_str(&contents)?),
+        Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(&contents)?),
+        _ => Err(format!(
+            "can't tell what format `{}` is in (use .toml, .yaml or .yml)",
+            path.display()
+        )
+        .into()),
+    }
+}
+
+fn load_program(path: &Path, liberal: bool) -> Result<Program, Box<dyn Error>> {
+    if path.extension() == Some(OsStr::new("obj")) {
+        Ok(Program::from_obj_file(path)?)
+    } else {
+        Ok(Assembler::new().liberal(liberal).assemble_file(path)?)
+    }
+}
+
+/// Shows words in LC-3 hex notation in reports.
+#[derive(PartialEq)]
+struct Hex(u16);
+
+impl Debug for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x{:04X}", self.0)
+    }
+}
+
+fn add_test<'t>(suite: TestSuite<'t>, test: Test) -> TestSuite<'t> {
+    let (name, points, randomize) =
+        (test.name.clone(), test.points, test.randomize);
+    let func = move |sim: &mut Simulator, grader: &mut Grader| {
+        run_test(&test, sim, grader)
+    };
+
+    if randomize {
+        suite.randomized_test(name, points, func)
+    } else {
+        suite.test(name, points, func)
+    }
+}
+
+fn run_test(test: &Test, sim: &mut Simulator, grader: &mut Grader) {
+    for &(reg, value) in &test.registers {
+        sim.set_reg(reg, value);
+    }
+    for &(addr, value) in &test.memory {
+        sim.set_mem(addr, value);
+    }
+    if let Some(input) = &test.input {
+        sim.input(input);
+    }
+    sim.set_instruction_limit(test.instruction_limit);
+
+    let res = sim.run(test.pc);
+    let state = match &res {
+        Ok(state) => *state,
+        Err(err) => err.state,
+    };
+
+    if test.halts {
+        let _ = match &res {
+            Ok(_) => grader.verify("program halts", true),
+            Err(err) => {
+                grader.verify(format!("program halts ({})", err), false)
+            }
+        };
+    }
+
+    for &(reg, value) in &test.expected_registers {
+        let actual = state.regs[reg as usize] as u16;
+        let _ = grader.verify_eq(format!("R{}", reg), Hex(value), Hex(actual));
+    }
+
+    for &(addr, value) in &test.expected_memory {
+        let label = format!("mem[x{:04X}]", addr);
+        let _ = grader.verify_eq(label, Hex(value), Hex(sim.mem(addr)));
+    }
+
+    if let Some(matcher) = &test.output {
+        let _ = grader.verify_output("output", matcher, &sim.output());
+    }
+}
+
+fn run(args: Args) -> Result<i32, Box<dyn Error>> {
+    let spec = load_spec(&args.spec)?;
+    let program = load_program(&args.program, spec.liberal)?;
+
+    let origin = program.words.first().map(|w| w.addr).unwrap_or(0x3000);
+    let tests = spec.tests(origin)?;
+
+    let mut suite = tests.into_iter().fold(TestSuite::new(program), add_test);
+    let report = suite.run();
+
+    match args.format {
+        Format::Text => print!("{}", report),
+        Format::Json => print!("{}", report.to_json()),
+        Format::JUnit => {
+            let name = spec.name.as_deref().unwrap_or_else(|| {
+                args.program
+                    .file_stem()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or("lc3-grade")
+            });
+
+            print!("{}", report.to_junit_xml(name))
+        }
+    }
+
+    Ok(if report.tests.iter().all(|t| t.passed()) {
+        0
+    } else {
+        1
+    })
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprint!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    process::exit(match run(args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            2
+        }
+    });
+}
@@ -0,0 +1,480 @@
+//! The test spec format.
+//!
+//! Specs can be written in TOML or YAML; in TOML they look like this:
+//! ```toml
+//! name = "mul"                  # optional; used in JUnit reports
+//! instruction_limit = 100000    # optional; per test case (0 is no limit)
+//!                               # defaults to 5000000
+//! pc = "x3000"                  # optional; defaults to the program's origin
+//! liberal = false               # optional; assemble in liberal mode
+//!
+//! [[test]]
+//! name = "6 * 7"
+//! points = 10
+//! randomize = true              # optional
+//! input = "abc"                 # optional
+//! registers = { R0 = 6, R1 = 7 }
+//! memory = { x4000 = "xFFFF" }
+//!
+//! [test.expect]
+//! halts = true                  # the default
+//! registers = { R2 = 42 }
+//! memory = { x4001 = 42 }
+//! output = "42\n"               # exact match; or, for more options:
+//! # output = { expected = "42", match = "lines", whitespace = "trailing", ignore_case = false }
+//! ```
+//!
+//! Words can be integers or strings in LC-3 notation (`x3000`, `#12`) or with
+//! a `0x` prefix.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use lc3tools_sys::grader::{
+    OutputMatcher, Whitespace, DEFAULT_INSTRUCTION_LIMIT,
+};
+use serde::Deserialize;
+
+use crate::common::parse_num;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub instruction_limit: Option<u64>,
+    #[serde(default)]
+    pub pc: Option<Word>,
+    #[serde(default)]
+    pub liberal: bool,
+    #[serde(rename = "test", alias = "tests")]
+    pub tests: Vec<TestSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestSpec {
+    pub name: String,
+    pub points: u32,
+    #[serde(default)]
+    pub randomize: bool,
+    #[serde(default)]
+    pub instruction_limit: Option<u64>,
+    #[serde(default)]
+    pub pc: Option<Word>,
+    #[serde(default)]
+    pub input: Option<String>,
+    #[serde(default)]
+    pub registers: BTreeMap<String, Word>,
+    #[serde(default)]
+    pub memory: BTreeMap<String, Word>,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    #[serde(default = "yes")]
+    pub halts: bool,
+    #[serde(default)]
+    pub registers: BTreeMap<String, Word>,
+    #[serde(default)]
+    pub memory: BTreeMap<String, Word>,
+    #[serde(default)]
+    pub output: Option<OutputSpec>,
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl Default for Expect {
+    fn default() -> Self {
+        Self {
+            halts: true,
+            registers: BTreeMap::new(),
+            memory: BTreeMap::new(),
+            output: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum OutputSpec {
+    Exact(String),
+    Detailed(DetailedOutputSpec),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetailedOutputSpec {
+    pub expected: String,
+    #[serde(default, rename = "match")]
+    pub kind: MatchKind,
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub whitespace: WhitespaceSpec,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    Exact,
+    Substring,
+    Regex,
+    Lines,
+}
+
+impl Default for MatchKind {
+    fn default() -> Self {
+        MatchKind::Exact
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhitespaceSpec {
+    Exact,
+    Trailing,
+    Collapse,
+    Ignore,
+}
+
+impl Default for WhitespaceSpec {
+    fn default() -> Self {
+        WhitespaceSpec::Exact
+    }
+}
+
+impl From<WhitespaceSpec> for Whitespace {
+    fn from(w: WhitespaceSpec) -> Self {
+        use WhitespaceSpec::*;
+        match w {
+            Exact => Whitespace::Exact,
+            Trailing => Whitespace::Trailing,
+            Collapse => Whitespace::Collapse,
+            Ignore => Whitespace::Ignore,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Word {
+    Int(i64),
+    Str(String),
+}
+
+impl Word {
+    fn value(&self) -> Result<u16, String> {
+        let (num, raw) = match self {
+            Word::Int(i) => (Some(*i), i.to_string()),
+            Word::Str(s) => (parse_num(s), s.clone()),
+        };
+
+        let range = i64::from(i16::min_value())..=i64::from(u16::max_value());
+        match num {
+            Some(n) if range.contains(&n) => Ok(n as u16),
+            Some(_) => Err(format!("`{}` doesn't fit in a word", raw)),
+            None => Err(format!("`{}` isn't a valid word", raw)),
+        }
+    }
+}
+
+/// A spec error, with the test case it's in.
+#[derive(Debug)]
+pub struct SpecError {
+    test: String,
+    msg: String,
+}
+
+impl Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in test `{}`: {}", self.test, self.msg)
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+/// A test case with all its values checked and parsed.
+#[derive(Debug)]
+pub struct Test {
+    pub name: String,
+    pub points: u32,
+    pub randomize: bool,
+    pub instruction_limit: u64,
+    pub pc: u16,
+    pub input: Option<String>,
+    pub registers: Vec<(u8, u16)>,
+    pub memory: Vec<(u16, u16)>,
+    pub halts: bool,
+    pub expected_registers: Vec<(u8, u16)>,
+    pub expected_memory: Vec<(u16, u16)>,
+    pub output: Option<OutputMatcher>,
+}
+
+fn register(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('R'), Some(d), None) | (Some('r'), Some(d), None) => {
+            d.to_digit(8).map(|d| d as u8)
+        }
+        _ => None,
+    }
+}
+
+fn registers(regs: &BTreeMap<String, Word>) -> Result<Vec<(u8, u16)>, String> {
+    regs.iter()
+        .map(|(reg, val)| match register(reg) {
+            Some(r) => Ok((r, val.value()?)),
+            None => Err(format!("`{}` isn't a register (R0 to R7)", reg)),
+        })
+        .collect()
+}
+
+fn memory(mem: &BTreeMap<String, Word>) -> Result<Vec<(u16, u16)>, String> {
+    mem.iter()
+        .map(|(addr, val)| {
+            let addr = Word::Str(addr.clone()).value()?;
+            Ok((addr, val.value()?))
+        })
+        .collect()
+}
+
+impl Spec {
+    /// Checks and parses the test cases; `origin` is the PC to use when the
+    /// spec doesn't give one.
+    ///
+    /// Test cases run with at most [`DEFAULT_INSTRUCTION_LIMIT`] instructions
+    /// unless the spec says otherwise so that programs that never halt can't
+    /// hang the grader.
+    pub fn tests(&self, origin: u16) -> Result<Vec<Test>, SpecError> {
+        let pc = match &self.pc {
+            Some(pc) => pc.value().map_err(|msg| SpecError {
+                test: "<top level>".to_string(),
+                msg,
+            })?,
+            None => origin,
+        };
+
+        self.tests
+            .iter()
+            .map(|t| {
+                let limit =
+                    self.instruction_limit.unwrap_or(DEFAULT_INSTRUCTION_LIMIT);
+                t.check(pc, limit).map_err(|msg| SpecError {
+                    test: t.name.clone(),
+                    msg,
+                })
+            })
+            .collect()
+    }
+}
+
+impl TestSpec {
+    fn check(&self, pc: u16, instruction_limit: u64) -> Result<Test, String> {
+        let output = match &self.expect.output {
+            None => None,
+            Some(OutputSpec::Exact(s)) => Some(OutputMatcher::exact(s.clone())),
+            Some(OutputSpec::Detailed(d)) => {
+                let matcher = match d.kind {
+                    MatchKind::Exact => {
+                        OutputMatcher::exact(d.expected.clone())
+                    }
+                    MatchKind::Substring => {
+                        OutputMatcher::substring(d.expected.clone())
+                    }
+                    MatchKind::Regex => OutputMatcher::regex(&d.expected)
+                        .map_err(|e| format!("invalid regex: {}", e))?,
+                    MatchKind::Lines => {
+                        OutputMatcher::lines(d.expected.clone())
+                    }
+                };
+
+                Some(
+                    matcher
+                        .ignore_case(d.ignore_case)
+                        .whitespace(d.whitespace.into()),
+                )
+            }
+        };
+
+        Ok(Test {
+            name: self.name.clone(),
+            points: self.points,
+            randomize: self.randomize,
+            instruction_limit: self
+                .instruction_limit
+                .unwrap_or(instruction_limit),
+            pc: match &self.pc {
+                Some(pc) => pc.value()?,
+                None => pc,
+            },
+            input: self.input.clone(),
+            registers: registers(&self.registers)?,
+            memory: memory(&self.memory)?,
+            halts: self.expect.halts,
+            expected_registers: registers(&self.expect.registers)?,
+            expected_memory: memory(&self.expect.memory)?,
+            output,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const TOML: &str = r##"
+        name = "mul"
+        pc = "x3000"
+
+        [[test]]
+        name = "6 * 7"
+        points = 10
+        randomize = true
+        input = "abc"
+        registers = { R0 = 6, r1 = "#7" }
+        memory = { x4000 = "xFFFF", "0x4001" = -1 }
+
+        [test.expect]
+        registers = { R2 = 42 }
+        memory = { x4001 = "0x2a" }
+        output = "42\n"
+
+        [[test]]
+        name = "0 * 0"
+        points = 5
+        instruction_limit = 100
+        pc = "x3005"
+
+        [test.expect]
+        halts = false
+        output = { expected = "0", match = "lines", whitespace = "trailing", ignore_case = true }
+    "##;
+
+    fn tests(spec: &str) -> Result<Vec<Test>, String> {
+        let spec: Spec = toml::from_str(spec).map_err(|e| e.to_string())?;
+        spec.tests(0x3000).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn toml() {
+        let tests = tests(TOML).unwrap();
+        let (first, second) = (&tests[0], &tests[1]);
+
+        assert_eq!(first.name, "6 * 7");
+        assert_eq!(first.points, 10);
+        assert!(first.randomize);
+        assert_eq!(first.instruction_limit, DEFAULT_INSTRUCTION_LIMIT);
+        assert_eq!(first.pc, 0x3000);
+        assert_eq!(first.input.as_deref(), Some("abc"));
+        assert_eq!(first.registers, vec![(0, 6), (1, 7)]);
+        // (In the order of the keys, as strings.)
+        assert_eq!(first.memory, vec![(0x4001, 0xFFFF), (0x4000, 0xFFFF)]);
+        assert!(first.halts);
+        assert_eq!(first.expected_registers, vec![(2, 42)]);
+        assert_eq!(first.expected_memory, vec![(0x4001, 42)]);
+        assert_eq!(first.output, Some(OutputMatcher::exact("42\n")));
+
+        assert_eq!(second.instruction_limit, 100);
+        assert_eq!(second.pc, 0x3005);
+        assert!(!second.halts);
+        assert_eq!(
+            second.output,
+            Some(
+                OutputMatcher::lines("0")
+                    .whitespace(Whitespace::Trailing)
+                    .ignore_case(true)
+            )
+        );
+    }
+
+    #[test]
+    fn yaml() {
+        let spec: Spec = serde_yaml::from_str(
+            "
+            instruction_limit: 0
+            tests:
+              - name: halts
+                points: 1
+                expect:
+                  output: { expected: '^4[0-9]$', match: regex }
+            ",
+        )
+        .unwrap();
+        let tests = spec.tests(0x3100).unwrap();
+
+        assert_eq!(tests[0].instruction_limit, 0);
+        assert_eq!(tests[0].pc, 0x3100);
+        assert!(tests[0].output.as_ref().unwrap().matches("42"));
+    }
+
+    #[test]
+    fn spec_limit_applies_to_every_test() {
+        let tests = tests(
+            "instruction_limit = 7\n\
+            [[test]]\nname = \"a\"\npoints = 1\n\
+            [[test]]\nname = \"b\"\npoints = 1\ninstruction_limit = 8\n",
+        )
+        .unwrap();
+
+        assert_eq!(tests[0].instruction_limit, 7);
+        assert_eq!(tests[1].instruction_limit, 8);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let err = tests("[[test]]\nname = \"a\"\npoints = 1\nlimit = 3\n")
+            .unwrap_err();
+        assert!(err.contains("unknown field `limit`"), "{}", err);
+    }
+
+    #[test]
+    fn bad_values() {
+        let test = |body: &str| {
+            tests(&format!("[[test]]\nname = \"t\"\npoints = 1\n{}", body))
+                .unwrap_err()
+        };
+
+        assert_eq!(
+            test("registers = { R8 = 1 }"),
+            "in test `t`: `R8` isn't a register (R0 to R7)"
+        );
+        assert_eq!(
+            test("memory = { x4000 = 65536 }"),
+            "in test `t`: `65536` doesn't fit in a word"
+        );
+        assert_eq!(
+            test("memory = { x4000 = -32769 }"),
+            "in test `t`: `-32769` doesn't fit in a word"
+        );
+        assert_eq!(
+            test("memory = { y4000 = 1 }"),
+            "in test `t`: `y4000` isn't a valid word"
+        );
+        assert_eq!(
+            test(
+                "expect = { output = { expected = \"(\", match = \"regex\" } }"
+            )
+            .lines()
+            .next(),
+            Some("in test `t`: invalid regex: regex parse error:")
+        );
+    }
+
+    #[test]
+    fn bad_top_level_pc() {
+        assert_eq!(
+            tests("pc = \"xG\"\n[[test]]\nname = \"t\"\npoints = 1\n")
+                .unwrap_err(),
+            "in test `<top level>`: `xG` isn't a valid word"
+        );
+    }
+}
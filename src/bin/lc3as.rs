@@ -0,0 +1,65 @@
+//! Assembles LC-3 programs into `LC3Tools` object files.
+//!
+//! ```text
+//! lc3as [--liberal] [--quiet] <file.asm>...
+//! ```
+//!
+//! Like the `LC3Tools` assembler, object files are written next to the source
+//! files (`foo.asm` → `foo.obj`).
+
+use std::path::PathBuf;
+use std::process;
+
+use lc3tools_sys::asm::{AsmError, Assembler};
+use lc3tools_sys::enums::PrintType;
+
+const USAGE: &str = "\
+usage: lc3as [--liberal] [--quiet] <file.asm>...
+
+  --liberal  relax some of the assembler's checks
+  --quiet    only report errors
+";
+
+fn main() {
+    let mut assembler = Assembler::new().console(true);
+    let mut files = Vec::new();
+
+    for arg in std::env::args_os().skip(1) {
+        match arg.to_str() {
+            Some("-h") | Some("--help") => {
+                print!("{}", USAGE);
+                return;
+            }
+            Some("--liberal") => assembler = assembler.liberal(true),
+            Some("--quiet") | Some("-q") => {
+                assembler = assembler.print_level(PrintType::Error)
+            }
+            Some(flag) if flag.starts_with('-') => {
+                eprint!("error: unknown flag: `{}`\n\n{}", flag, USAGE);
+                process::exit(2);
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    if files.is_empty() {
+        eprint!("error: expected at least one file\n\n{}", USAGE);
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for file in files {
+        // Diagnostics go straight to the console.
+        if let Err(err) = assembler.assemble_file_to_obj(&file) {
+            failed = true;
+            match err {
+                AsmError::Failed(_) => {}
+                err => eprintln!("error: {}: {}", file.display(), err),
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,178 @@
+//! A disassembler for LC-3 instructions.
+
+use lc3tools_sys::asm::SymbolTable;
+
+fn sext(value: u16, bits: u32) -> i16 {
+    let shift = 16 - bits;
+    ((value << shift) as i16) >> shift
+}
+
+fn reg(word: u16, lsb: u32) -> String {
+    format!("R{}", (word >> lsb) & 0b111)
+}
+
+/// Formats a PC-relative target as a label if there is one there.
+fn target(addr: u16, offset: i16, symbols: &SymbolTable) -> String {
+    let target = addr.wrapping_add(1).wrapping_add(offset as u16);
+    match symbols.name(target) {
+        Some(name) => name.to_string(),
+        None => format!("x{:04X}", target),
+    }
+}
+
+fn trap_name(vector: u16) -> Option<&'static str> {
+    Some(match vector {
+        0x20 => "GETC",
+        0x21 => "OUT",
+        0x22 => "PUTS",
+        0x23 => "IN",
+        0x24 => "PUTSP",
+        0x25 => "HALT",
+        _ => return None,
+    })
+}
+
+/// Disassembles the word at `addr`; words that aren't valid instructions come
+/// out as `.FILL`s.
+pub fn disassemble(addr: u16, word: u16, symbols: &SymbolTable) -> String {
+    let fill = || format!(".FILL x{:04X}", word);
+    let (dr, sr1, base) = (reg(word, 9), reg(word, 6), reg(word, 6));
+    let pc_offset9 = || target(addr, sext(word, 9), symbols);
+
+    match word >> 12 {
+        0b0001 | 0b0101 => {
+            let op = if word >> 12 == 0b0001 { "ADD" } else { "AND" };
+            if word & 0x20 != 0 {
+                format!("{} {}, {}, #{}", op, dr, sr1, sext(word, 5))
+            } else {
+                format!("{} {}, {}, {}", op, dr, sr1, reg(word, 0))
+            }
+        }
+        0b0000 => {
+            let nzp = (word >> 9) & 0b111;
+            if nzp == 0 {
+                return fill();
+            }
+
+            let cc: String = ["n", "z", "p"]
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| nzp & (0b100 >> i) != 0)
+                .map(|(_, c)| *c)
+                .collect();
+            format!("BR{} {}", cc, pc_offset9())
+        }
+        0b1100 if (word >> 6) & 0b111 == 7 => "RET".to_string(),
+        0b1100 => format!("JMP {}", base),
+        0b0100 if word & 0x800 != 0 => {
+            format!("JSR {}", target(addr, sext(word, 11), symbols))
+        }
+        0b0100 => format!("JSRR {}", base),
+        0b0010 => format!("LD {}, {}", dr, pc_offset9()),
+        0b1010 => format!("LDI {}, {}", dr, pc_offset9()),
+        0b1110 => format!("LEA {}, {}", dr, pc_offset9()),
+        0b0011 => format!("ST {}, {}", dr, pc_offset9()),
+        0b1011 => format!("STI {}, {}", dr, pc_offset9()),
+        0b0110 => format!("LDR {}, {}, #{}", dr, base, sext(word, 6)),
+        0b0111 => format!("STR {}, {}, #{}", dr, base, sext(word, 6)),
+        0b1001 => format!("NOT {}, {}", dr, sr1),
+        0b1000 => "RTI".to_string(),
+        0b1111 => {
+            let vector = word & 0xFF;
+            match trap_name(vector) {
+                Some(name) => name.to_string(),
+                None => format!("TRAP x{:02X}", vector),
+            }
+        }
+        _ => fill(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lc3tools_sys::asm::assemble;
+
+    #[test]
+    fn every_opcode() {
+        let symbols = SymbolTable::default();
+        let table: &[(u16, u16, &str)] = &[
+            (0x3000, 0x1283, "ADD R1, R2, R3"),
+            (0x3000, 0x103F, "ADD R0, R0, #-1"),
+            (0x3000, 0x5FAF, "AND R7, R6, #15"),
+            (0x3000, 0x5042, "AND R0, R1, R2"),
+            (0x3000, 0x997F, "NOT R4, R5"),
+            (0x3000, 0x0E00, "BRnzp x3001"),
+            (0x3000, 0x05FF, "BRz x3000"),
+            (0x3000, 0x0A02, "BRnp x3003"),
+            (0xFFFF, 0x0E00, "BRnzp x0000"),
+            (0x3000, 0x0000, ".FILL x0000"),
+            (0x3000, 0x01FF, ".FILL x01FF"),
+            (0x3000, 0xC080, "JMP R2"),
+            (0x3000, 0xC1C0, "RET"),
+            (0x3000, 0x4FFE, "JSR x2FFF"),
+            (0x3000, 0x40C0, "JSRR R3"),
+            (0x3000, 0x2005, "LD R0, x3006"),
+            (0x3000, 0xA300, "LDI R1, x2F01"),
+            (0x3000, 0xE400, "LEA R2, x3001"),
+            (0x3000, 0x36FF, "ST R3, x3100"),
+            (0x3000, 0xB9FF, "STI R4, x3000"),
+            (0x3000, 0x6BA0, "LDR R5, R6, #-32"),
+            (0x3000, 0x7E1F, "STR R7, R0, #31"),
+            (0x3000, 0x8000, "RTI"),
+            (0x3000, 0xF020, "GETC"),
+            (0x3000, 0xF021, "OUT"),
+            (0x3000, 0xF022, "PUTS"),
+            (0x3000, 0xF023, "IN"),
+            (0x3000, 0xF024, "PUTSP"),
+            (0x3000, 0xF025, "HALT"),
+            (0x3000, 0xF026, "TRAP x26"),
+            (0x3000, 0xF0FF, "TRAP xFF"),
+            (0x3000, 0xD000, ".FILL xD000"),
+            (0x3000, 0xDEAD, ".FILL xDEAD"),
+        ];
+
+        for &(addr, word, expected) in table {
+            assert_eq!(
+                disassemble(addr, word, &symbols),
+                expected,
+                "x{:04X} at x{:04X}",
+                word,
+                addr
+            );
+        }
+    }
+
+    #[test]
+    fn labels() {
+        let program = assemble(
+            ".ORIG x3000
+    LOOP    BRnzp LOOP
+            JSR SUB
+            LD R0, DATA
+            HALT
+    SUB     RET
+    DATA    .FILL xD123
+            .END",
+        )
+        .unwrap();
+
+        let disassembled = program
+            .words
+            .iter()
+            .map(|w| disassemble(w.addr, w.value, &program.symbols))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            disassembled,
+            [
+                "BRnzp LOOP",
+                "JSR SUB",
+                "LD R0, DATA",
+                "HALT",
+                "RET",
+                ".FILL xD123"
+            ]
+        );
+    }
+}
@@ -0,0 +1,447 @@
+//! An interactive debugger for LC-3 programs.
+//!
+//! ```text
+//! lc3db [--input <file>] [--limit <n>] <program.asm|program.obj>
+//! ```
+//!
+//! Type `help` at the prompt for the commands; an empty line repeats the last
+//! one. Locations can be labels or addresses (`LOOP`, `x3004`).
+//!
+//! Runs can't be interrupted, so they stop after
+//! [`DEFAULT_INSTRUCTION_LIMIT`] instructions unless `--limit` says otherwise.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use lc3tools_sys::asm::{AsmError, Assembler, Program};
+use lc3tools_sys::grader::DEFAULT_INSTRUCTION_LIMIT;
+use lc3tools_sys::root::State;
+use lc3tools_sys::sim::{RunError, Simulator};
+
+#[path = "../common/mod.rs"]
+mod common;
+mod disas;
+
+use common::{dump_state, parse_word};
+use disas::disassemble;
+
+const USAGE: &str = "\
+usage: lc3db [--input <file>] [--limit <n>] <program.asm|program.obj>
+
+  --input <file>  input for the program (more can be added with `input`)
+  --limit <n>     stop runs after `n` instructions (default: 5000000; 0 for
+                  no limit)
+";
+
+const HELP: &str = "\
+  run                  start the program over from its origin (with the
+                       `--input` input)
+  step [n]             execute `n` instructions (default: 1)
+  next [n]             like `step` but runs subroutine calls to completion
+  finish               run until the current subroutine returns
+  continue             run until a breakpoint or the end of the program
+  break [loc]          set a breakpoint (lists them without `loc`)
+  delete [loc]         remove a breakpoint (all of them without `loc`)
+  regs                 print the registers
+  x/<n> <loc>          print `n` words of memory starting at `loc`
+  set <reg> <value>    set R0-R7 or the PC
+  set <loc> <value>    set a word of memory
+  disas [loc] [n]      disassemble `n` instructions (default: 10) from `loc`
+                       (default: the PC)
+  symbols              list the labels in the program
+  input <text>         queue input for the program (`\\n` is a newline)
+  quit                 exit
+";
+
+struct Debugger {
+    sim: Simulator,
+    program: Program,
+    origin: u16,
+}
+
+/// What a command did to the machine.
+enum Stop {
+    Ran(Result<State, RunError>),
+    Nothing,
+    Quit,
+}
+
+fn load(path: &Path) -> Result<Program, AsmError> {
+    if path.extension() == Some(OsStr::new("obj")) {
+        Program::from_obj_file(path)
+    } else {
+        Assembler::new().assemble_file(path)
+    }
+}
+
+/// Turns `\n`, `\t`, `\\` and `\xNN` into the bytes they stand for.
+fn unescape(text: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                out.push(u8::from_str_radix(&hex, 16).unwrap_or(b'?'));
+            }
+            Some(c) => out.extend_from_slice(c.to_string().as_bytes()),
+            None => out.push(b'\\'),
+        }
+    }
+
+    out
+}
+
+impl Debugger {
+    fn new(program: Program, input: &[u8]) -> Self {
+        let origin = program.words.first().map(|w| w.addr).unwrap_or(0x3000);
+        let mut sim = Simulator::builder().input_after(0, input).build();
+
+        sim.load_program(program.iter());
+        sim.set_pc(origin);
+
+        Self {
+            sim,
+            program,
+            origin,
+        }
+    }
+
+    fn loc(&self, s: &str) -> Result<u16, String> {
+        self.program
+            .symbols
+            .get(s)
+            .or_else(|| parse_word(s))
+            .ok_or_else(|| format!("`{}` isn't a label or an address", s))
+    }
+
+    fn addr_label(&self, addr: u16) -> String {
+        match self.program.symbols.describe(addr) {
+            Some(d) => format!("x{:04X} <{}>", addr, d),
+            None => format!("x{:04X}", addr),
+        }
+    }
+
+    fn print_insn(&self, addr: u16) {
+        let pc = self.sim.state().pc as u16;
+        let marker =
+            match (addr == pc, self.sim.breakpoints().any(|b| b == addr)) {
+                (true, _) => "=>",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+
+        let word = self.sim.mem(addr);
+        println!(
+            "{} {}: {}",
+            marker,
+            self.addr_label(addr),
+            disassemble(addr, word, &self.program.symbols)
+        );
+    }
+
+    fn running(&self) -> Result<(), String> {
+        if self.sim.halted() {
+            Err("the program has halted; use `run` to start it over".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn count(arg: Option<&str>) -> Result<usize, String> {
+        match arg {
+            None => Ok(1),
+            Some(n) => n
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("`{}` isn't a count", n)),
+        }
+    }
+
+    fn repeat(
+        &mut self,
+        n: usize,
+        step: fn(&mut Simulator) -> Result<State, RunError>,
+    ) -> Result<Stop, String> {
+        self.running()?;
+
+        let mut res = step(&mut self.sim);
+        for _ in 1..n {
+            let pc = self.sim.state().pc as u16;
+            if res.is_err()
+                || self.sim.halted()
+                || self.sim.breakpoints().any(|b| b == pc)
+            {
+                break;
+            }
+
+            res = step(&mut self.sim);
+        }
+
+        Ok(Stop::Ran(res))
+    }
+
+    fn exec(&mut self, line: &str) -> Result<Stop, String> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(c) => c,
+            None => return Ok(Stop::Nothing),
+        };
+        let args: Vec<&str> = words.collect();
+
+        if cmd.starts_with("x/") || cmd == "x" {
+            let n = Self::count(cmd.get(2..).filter(|n| !n.is_empty()))?;
+            let start = match args.first() {
+                Some(loc) => self.loc(loc)?,
+                None => self.sim.state().pc as u16,
+            };
+
+            for addr in (0..n).map(|i| start.wrapping_add(i as u16)) {
+                let word = self.sim.mem(addr);
+                println!(
+                    "{}: x{:04X}  #{}",
+                    self.addr_label(addr),
+                    word,
+                    word as i16
+                );
+            }
+
+            return Ok(Stop::Nothing);
+        }
+
+        match (cmd, &*args) {
+            ("help", _) | ("h", _) | ("?", _) => print!("{}", HELP),
+            ("quit", _) | ("q", _) => return Ok(Stop::Quit),
+            ("run", []) | ("r", []) => {
+                // This also schedules the `--input` input again.
+                self.sim.load_program(self.program.iter());
+                return Ok(Stop::Ran(self.sim.run(self.origin)));
+            }
+            ("step", n) | ("s", n) if n.len() <= 1 => {
+                let n = Self::count(n.first().copied())?;
+                return self.repeat(n, Simulator::step_in);
+            }
+            ("next", n) | ("n", n) if n.len() <= 1 => {
+                let n = Self::count(n.first().copied())?;
+                return self.repeat(n, Simulator::step_over);
+            }
+            ("finish", []) | ("fin", []) => {
+                return self.repeat(1, Simulator::step_out)
+            }
+            ("continue", []) | ("c", []) => {
+                return self.repeat(1, Simulator::resume)
+            }
+            ("break", []) | ("b", []) => {
+                if self.sim.breakpoints().next().is_none() {
+                    println!("No breakpoints.");
+                }
+                for (i, addr) in self.sim.breakpoints().enumerate() {
+                    println!("{}: {}", i + 1, self.addr_label(addr));
+                }
+            }
+            ("break", [loc]) | ("b", [loc]) => {
+                let addr = self.loc(loc)?;
+                if self.sim.set_breakpoint(addr) {
+                    println!("Breakpoint at {}.", self.addr_label(addr));
+                } else {
+                    println!("There's already a breakpoint there.");
+                }
+            }
+            ("delete", []) | ("d", []) => {
+                let all: Vec<u16> = self.sim.breakpoints().collect();
+                for addr in all {
+                    let _ = self.sim.remove_breakpoint(addr);
+                }
+            }
+            ("delete", [loc]) | ("d", [loc]) => {
+                let addr = self.loc(loc)?;
+                if !self.sim.remove_breakpoint(addr) {
+                    return Err(format!(
+                        "there's no breakpoint at {}",
+                        self.addr_label(addr)
+                    ));
+                }
+            }
+            ("regs", []) => dump_state(&self.sim.state()),
+            ("set", [target, value]) => {
+                let value = parse_word(value)
+                    .ok_or_else(|| format!("`{}` isn't a word", value))?;
+                let upper = target.to_ascii_uppercase();
+
+                if upper == "PC" {
+                    self.sim.set_pc(value);
+                } else if let Some(reg) = register(&upper) {
+                    self.sim.set_reg(reg, value);
+                } else {
+                    let addr = self.loc(target)?;
+                    self.sim.set_mem(addr, value);
+                }
+            }
+            ("disas", args) if args.len() <= 2 => {
+                let start = match args.first() {
+                    Some(loc) => self.loc(loc)?,
+                    None => self.sim.state().pc as u16,
+                };
+                let n = match args.get(1) {
+                    Some(n) => Self::count(Some(n))?,
+                    None => 10,
+                };
+
+                for i in 0..n {
+                    self.print_insn(start.wrapping_add(i as u16));
+                }
+            }
+            ("symbols", []) => {
+                for (name, addr) in self.program.symbols.iter() {
+                    println!("x{:04X}  {}", addr, name);
+                }
+            }
+            ("input", _) => {
+                // Keep the text as typed, spaces and all.
+                let text = line.trim_start()[cmd.len()..].trim_start();
+                self.sim.input(unescape(text));
+            }
+            _ => {
+                return Err(format!(
+                    "unknown command (or wrong arguments): `{}`; try `help`",
+                    line.trim()
+                ))
+            }
+        }
+
+        Ok(Stop::Nothing)
+    }
+
+    /// Prints what the program printed and where it stopped.
+    fn report(&mut self, res: Result<State, RunError>) {
+        let output = self.sim.output().into_owned();
+        if !output.is_empty() {
+            print!("{}", output);
+            if !output.ends_with('\n') {
+                println!();
+            }
+            self.sim.clear_output();
+        }
+
+        match res {
+            Err(err) => println!("Stopped: {}.", err),
+            Ok(_) if self.sim.halted() => println!("The program halted."),
+            Ok(state) => {
+                let pc = state.pc as u16;
+                if self.sim.breakpoints().any(|b| b == pc) {
+                    println!("Breakpoint at {}.", self.addr_label(pc));
+                }
+
+                self.print_insn(pc);
+            }
+        }
+    }
+}
+
+fn register(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('R'), Some(d), None) => d.to_digit(8).map(|d| d as u8),
+        _ => None,
+    }
+}
+
+fn parse_args() -> Result<(PathBuf, Vec<u8>, u64), String> {
+    let mut args = std::env::args_os().skip(1);
+    let (mut program, mut input, mut limit) =
+        (None, Vec::new(), DEFAULT_INSTRUCTION_LIMIT);
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-h") | Some("--help") => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            Some("--input") | Some("-i") => {
+                let path = args.next().ok_or("`--input` needs a file")?;
+                input = fs::read(&path)
+                    .map_err(|e| format!("couldn't read {:?}: {}", path, e))?;
+            }
+            Some("--limit") | Some("-l") => {
+                limit = args
+                    .next()
+                    .as_ref()
+                    .and_then(|l| l.to_str())
+                    .and_then(|l| l.parse().ok())
+                    .ok_or("`--limit` needs a number")?;
+            }
+            Some(flag) if flag.starts_with('-') => {
+                return Err(format!("unknown flag: `{}`", flag))
+            }
+            _ => {
+                if program.replace(PathBuf::from(arg)).is_some() {
+                    return Err("expected one program".to_string());
+                }
+            }
+        }
+    }
+
+    match program {
+        Some(program) => Ok((program, input, limit)),
+        None => Err("expected a program".to_string()),
+    }
+}
+
+fn main() {
+    let (path, input, limit) = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprint!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let program = match load(&path) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(2);
+        }
+    };
+
+    let mut db = Debugger::new(program, &input);
+    db.sim.set_instruction_limit(limit);
+    db.print_insn(db.origin);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut last = String::new();
+
+    loop {
+        print!("(lc3db) ");
+        let _ = io::stdout().flush();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        if !line.trim().is_empty() {
+            last = line;
+        }
+
+        match db.exec(&last) {
+            Ok(Stop::Ran(res)) => db.report(res),
+            Ok(Stop::Nothing) => {}
+            Ok(Stop::Quit) => break,
+            Err(err) => println!("error: {}", err),
+        }
+    }
+}
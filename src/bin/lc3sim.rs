@@ -0,0 +1,212 @@
+//! Runs an LC-3 program.
+//!
+//! ```text
+//! lc3sim [options] <program.obj|program.asm>
+//! ```
+//!
+//! Output goes to the console; input comes from the console unless a file
+//! (or `-` for stdin) is given with `--input`.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use lc3tools_sys::asm::{Assembler, Program};
+use lc3tools_sys::enums::PrintType;
+use lc3tools_sys::sim::{Simulator, SimulatorBuilder};
+
+mod common;
+use common::{dump_state, parse_word};
+
+const USAGE: &str = "\
+usage: lc3sim [options] <program.obj|program.asm>
+
+  -i, --input <file>         read input from a file (`-` for stdin) instead of
+                             the console
+  -l, --limit <n>            stop after `n` instructions
+      --pc <addr>            start at `addr` (default: the program's origin)
+      --dump                 print the registers after the run
+      --mem <start>:<end>    print memory from `start` to `end` (inclusive)
+                             after the run
+      --print-level <0-9>    how much the simulator reports (default: 2)
+";
+
+#[derive(Debug, Default)]
+struct Args {
+    input: Option<PathBuf>,
+    limit: u64,
+    pc: Option<u16>,
+    dump: bool,
+    mem: Vec<(u16, u16)>,
+    print_level: Option<PrintType>,
+    program: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args_os().skip(1);
+    let mut parsed = Args::default();
+
+    macro_rules! value {
+        ($flag:expr) => {
+            args.next()
+                .ok_or_else(|| format!("`{}` needs a value", $flag))?
+        };
+    }
+
+    while let Some(arg) = args.next() {
+        let flag = match arg.to_str() {
+            Some(f) if f.starts_with('-') && f != "-" => f.to_string(),
+            _ => {
+                if parsed.program.replace(PathBuf::from(arg)).is_some() {
+                    return Err("expected one program".to_string());
+                }
+                continue;
+            }
+        };
+
+        match &*flag {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            "-i" | "--input" => parsed.input = Some(value!(flag).into()),
+            "-l" | "--limit" => {
+                let limit = value!(flag);
+                parsed.limit = limit
+                    .to_str()
+                    .and_then(|l| l.parse().ok())
+                    .ok_or_else(|| format!("invalid limit: {:?}", limit))?;
+            }
+            "--pc" => {
+                let pc = value!(flag);
+                parsed.pc = Some(
+                    pc.to_str()
+                        .and_then(parse_word)
+                        .ok_or_else(|| format!("invalid address: {:?}", pc))?,
+                );
+            }
+            "--dump" => parsed.dump = true,
+            "--mem" => {
+                let range = value!(flag);
+                let parsed_range = range.to_str().and_then(|r| {
+                    let mut it = r.splitn(2, ':');
+                    Some((parse_word(it.next()?)?, parse_word(it.next()?)?))
+                });
+
+                match parsed_range {
+                    Some((start, end)) if start <= end => {
+                        parsed.mem.push((start, end))
+                    }
+                    _ => return Err(format!("invalid range: {:?}", range)),
+                }
+            }
+            "--print-level" => {
+                let level = value!(flag);
+                parsed.print_level = Some(
+                    level
+                        .to_str()
+                        .and_then(|l| l.parse::<i32>().ok())
+                        .and_then(|l| PrintType::try_from(l).ok())
+                        .ok_or_else(|| {
+                            format!("invalid print level: {:?}", level)
+                        })?,
+                );
+            }
+            other => return Err(format!("unknown flag: `{}`", other)),
+        }
+    }
+
+    if parsed.program.is_none() {
+        return Err("expected a program".to_string());
+    }
+
+    Ok(parsed)
+}
+
+fn load_program(path: &Path) -> Result<Program, Box<dyn Error>> {
+    if path.extension() == Some(OsStr::new("obj")) {
+        Ok(Program::from_obj_file(path)?)
+    } else {
+        Ok(Assembler::new().assemble_file(path)?)
+    }
+}
+
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut input = Vec::new();
+        let _ = io::stdin().read_to_end(&mut input)?;
+        Ok(input)
+    } else {
+        fs::read(path)
+    }
+}
+
+fn dump_mem(sim: &Simulator, start: u16, end: u16) {
+    for addr in start..=end {
+        let word = sim.mem(addr);
+        println!("x{:04X}: x{:04X} ({})", addr, word, word as i16);
+    }
+}
+
+fn run(args: Args) -> Result<bool, Box<dyn Error>> {
+    let program = load_program(args.program.as_ref().unwrap())?;
+    let input = args.input.as_ref().map(|p| read_input(p)).transpose()?;
+
+    let mut builder = SimulatorBuilder::new()
+        .print_level(args.print_level.unwrap_or(PrintType::FatalError))
+        .instruction_limit(args.limit)
+        .console_output(true)
+        .console_input(input.is_none());
+    if let Some(input) = input {
+        builder = builder.input_after(0, input);
+    }
+
+    let mut sim = builder.build();
+    sim.load_program(program.iter());
+
+    let pc = args
+        .pc
+        .or_else(|| program.words.first().map(|w| w.addr))
+        .unwrap_or(0x3000);
+
+    let res = sim.run(pc);
+    let state = match &res {
+        Ok(state) => *state,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            err.state
+        }
+    };
+
+    if args.dump {
+        dump_state(&state);
+    }
+    for &(start, end) in &args.mem {
+        dump_mem(&sim, start, end);
+    }
+
+    Ok(res.is_ok())
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprint!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    process::exit(match run(args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            2
+        }
+    });
+}
@@ -0,0 +1,390 @@
+//! A [`cxx`](https://cxx.rs) bridge to the `LC3Tools` C++ API.
+//!
+//! Unlike the C++ items in the [bindgen output](crate::root), nothing here
+//! depends on name mangling or on class layouts: `cxx` generates C++ glue that
+//! `build.rs` compiles alongside `LC3Tools` so it works with whatever compiler
+//! and flags `cc` ends up using.
+//!
+//! `cxx` can't bind `lc3::sim` and `lc3::as` directly (their constructors take
+//! references to I/O objects that have to outlive them) so [`Sim`] and
+//! [`Assembler`] are thin C++ wrappers (in `extra/bridge`) that own their I/O.
+//! Output and input go through the [`Printer`] and [`Inputter`] traits, which
+//! are implemented for closures:
+//!
+//! ```rust,ignore
+//! use lc3tools_sys::bridge::Sim;
+//! use lc3tools_sys::enums::PrintType;
+//!
+//! let mut sim = Sim::new(
+//!     |bytes: &[u8]| print!("{}", String::from_utf8_lossy(bytes)),
+//!     || None,
+//!     PrintType::FatalError,
+//! );
+//!
+//! sim.pin_mut().set_mem(0x3000, 0xF025); // HALT
+//! sim.pin_mut().set_pc(0x3000);
+//! assert!(sim.pin_mut().run_until_halt());
+//! ```
+
+use std::convert::TryFrom;
+
+use cxx::UniquePtr;
+
+use crate::enums::{PrintColor, PrintType};
+
+pub use ffi::{Assembler, Sim};
+
+#[cxx::bridge(namespace = "lc3tools_sys::bridge")]
+mod ffi {
+    extern "Rust" {
+        type PrinterBox;
+        fn set_color(self: &mut PrinterBox, color: i32);
+        fn print(self: &mut PrinterBox, text: &[u8]);
+        fn newline(self: &mut PrinterBox);
+
+        type InputterBox;
+        fn begin_input(self: &mut InputterBox);
+        fn get_char(self: &mut InputterBox, c: &mut u8) -> bool;
+        fn end_input(self: &mut InputterBox);
+    }
+
+    unsafe extern "C++" {
+        include!("bridge.h");
+
+        /// An `lc3::sim` along with its I/O.
+        type Sim;
+
+        fn new_sim(
+            printer: Box<PrinterBox>,
+            inputter: Box<InputterBox>,
+            print_level: u32,
+        ) -> UniquePtr<Sim>;
+
+        /// Loads an object file (`sim::loadObjFile`); returns whether the
+        /// file could be read.
+        fn load_obj_file(self: Pin<&mut Sim>, path: &str) -> bool;
+        /// Clears memory and the registers and reloads the OS
+        /// (`sim::reinitialize`).
+        fn reinitialize(self: Pin<&mut Sim>);
+        /// Restarts the machine without clearing memory (`sim::restart`).
+        fn restart(self: Pin<&mut Sim>);
+
+        /// Runs from the current PC until the program halts
+        /// (`sim::runUntilHalt`).
+        fn run_until_halt(self: Pin<&mut Sim>) -> bool;
+        /// Executes a single instruction (`sim::stepIn`).
+        fn step_in(self: Pin<&mut Sim>) -> bool;
+        /// Executes a single instruction, running subroutine calls to
+        /// completion (`sim::stepOver`).
+        fn step_over(self: Pin<&mut Sim>) -> bool;
+        /// Runs until the current subroutine returns (`sim::stepOut`).
+        fn step_out(self: Pin<&mut Sim>) -> bool;
+
+        /// Gets a general purpose register (`R0` to `R7`).
+        fn get_reg(self: &Sim, id: u16) -> u16;
+        /// Gets the value of a memory address.
+        fn get_mem(self: &Sim, addr: u16) -> u16;
+        /// Gets the source line the word at an address came from
+        /// (`sim::getMemLine`).
+        fn get_mem_line(self: &Sim, addr: u16) -> UniquePtr<CxxString>;
+        fn get_pc(self: &Sim) -> u16;
+        fn get_psr(self: &Sim) -> u16;
+        fn get_mcr(self: &Sim) -> u16;
+        /// Gets the condition codes (`'N'`, `'Z'` or `'P'`).
+        fn get_cc(self: &Sim) -> u8;
+
+        /// Sets a general purpose register (`R0` to `R7`).
+        fn set_reg(self: Pin<&mut Sim>, id: u16, value: u16);
+        /// Sets the value of a memory address.
+        fn set_mem(self: Pin<&mut Sim>, addr: u16, value: u16);
+        fn set_pc(self: Pin<&mut Sim>, value: u16);
+
+        fn set_breakpoint(self: Pin<&mut Sim>, addr: u16);
+        /// Removes the breakpoint at an address; returns whether there was
+        /// one.
+        fn remove_breakpoint(self: Pin<&mut Sim>, addr: u16) -> bool;
+
+        /// Sets the maximum number of instructions a run may execute (0 for
+        /// no limit).
+        fn set_run_inst_limit(self: Pin<&mut Sim>, limit: u64);
+        fn get_inst_exec_count(self: &Sim) -> u64;
+        fn did_exceed_inst_limit(self: &Sim) -> bool;
+
+        /// An `lc3::as` along with its printer.
+        type Assembler;
+
+        fn new_assembler(
+            printer: Box<PrinterBox>,
+            print_level: u32,
+            liberal: bool,
+        ) -> UniquePtr<Assembler>;
+
+        /// Assembles the file at `asm_path`, writing an object file next to
+        /// it; returns the object file's path (null if assembly failed).
+        fn assemble(
+            self: Pin<&mut Assembler>,
+            asm_path: &str,
+        ) -> UniquePtr<CxxString>;
+    }
+}
+
+/// Receives what a [`Sim`] or [`Assembler`] prints (`lc3::utils::IPrinter`).
+pub trait Printer {
+    /// Called when the color of the output changes; does nothing by default.
+    fn set_color(&mut self, _color: PrintColor) {}
+
+    /// Called with the bytes that are printed.
+    fn print(&mut self, bytes: &[u8]);
+
+    /// Called for a newline; prints `\n` by default.
+    fn newline(&mut self) {
+        self.print(b"\n")
+    }
+}
+
+impl<F: FnMut(&[u8])> Printer for F {
+    fn print(&mut self, bytes: &[u8]) {
+        self(bytes)
+    }
+}
+
+/// Provides input to a [`Sim`] (`lc3::utils::IInputter`).
+pub trait Inputter {
+    /// Called when the machine starts running; does nothing by default.
+    fn begin_input(&mut self) {}
+
+    /// Called (about once per instruction) for the next byte of input, if
+    /// there is one.
+    fn get_char(&mut self) -> Option<u8>;
+
+    /// Called when the machine stops running; does nothing by default.
+    fn end_input(&mut self) {}
+}
+
+impl<F: FnMut() -> Option<u8>> Inputter for F {
+    fn get_char(&mut self) -> Option<u8> {
+        self()
+    }
+}
+
+/// A [`Printer`] on its way to C++.
+pub struct PrinterBox(Box<dyn Printer>);
+
+impl PrinterBox {
+    fn set_color(&mut self, color: i32) {
+        if let Ok(color) = PrintColor::try_from(color) {
+            self.0.set_color(color)
+        }
+    }
+
+    fn print(&mut self, text: &[u8]) {
+        self.0.print(text)
+    }
+
+    fn newline(&mut self) {
+        self.0.newline()
+    }
+}
+
+/// An [`Inputter`] on its way to C++.
+pub struct InputterBox(Box<dyn Inputter>);
+
+impl InputterBox {
+    fn begin_input(&mut self) {
+        self.0.begin_input()
+    }
+
+    fn get_char(&mut self, c: &mut u8) -> bool {
+        match self.0.get_char() {
+            Some(byte) => {
+                *c = byte;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn end_input(&mut self) {
+        self.0.end_input()
+    }
+}
+
+fn print_level(print_level: PrintType) -> u32 {
+    i32::from(print_level) as u32
+}
+
+impl Sim {
+    /// Makes a simulator that prints with `printer` and gets input from
+    /// `inputter`.
+    pub fn new(
+        printer: impl Printer + 'static,
+        inputter: impl Inputter + 'static,
+        level: PrintType,
+    ) -> UniquePtr<Sim> {
+        ffi::new_sim(
+            Box::new(PrinterBox(Box::new(printer))),
+            Box::new(InputterBox(Box::new(inputter))),
+            print_level(level),
+        )
+    }
+}
+
+impl Assembler {
+    /// Makes an assembler that reports diagnostics to `printer`.
+    pub fn new(
+        printer: impl Printer + 'static,
+        level: PrintType,
+        liberal: bool,
+    ) -> UniquePtr<Assembler> {
+        ffi::new_assembler(
+            Box::new(PrinterBox(Box::new(printer))),
+            print_level(level),
+            liberal,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    use crate::asm::TempDir;
+
+    /// Collects what's printed (and the colors it was printed in).
+    #[derive(Debug, Default, Clone)]
+    struct Recorder {
+        text: Rc<RefCell<Vec<u8>>>,
+        colors: Rc<RefCell<Vec<PrintColor>>>,
+    }
+
+    impl Recorder {
+        fn text(&self) -> String {
+            String::from_utf8_lossy(&self.text.borrow()).into_owned()
+        }
+    }
+
+    impl Printer for Recorder {
+        fn set_color(&mut self, color: PrintColor) {
+            self.colors.borrow_mut().push(color);
+        }
+
+        fn print(&mut self, bytes: &[u8]) {
+            self.text.borrow_mut().extend_from_slice(bytes);
+        }
+    }
+
+    /// Hands out the bytes of a string, one at a time.
+    fn input(text: &str) -> impl Inputter {
+        let mut bytes = text.as_bytes().to_vec().into_iter();
+        move || bytes.next()
+    }
+
+    #[test]
+    fn printer_and_inputter_boxes() {
+        let recorder = Recorder::default();
+        let mut printer = PrinterBox(Box::new(recorder.clone()));
+
+        printer.set_color(i32::from(PrintColor::Red));
+        printer.print(b"hi");
+        printer.newline();
+        printer.set_color(-1);
+        printer.set_color(i32::from(PrintColor::Reset));
+
+        assert_eq!(recorder.text(), "hi\n");
+        assert_eq!(
+            *recorder.colors.borrow(),
+            [PrintColor::Red, PrintColor::Reset]
+        );
+
+        let mut inputter = InputterBox(Box::new(input("ab")));
+        let mut c = 0;
+        inputter.begin_input();
+        assert!(inputter.get_char(&mut c) && c == b'a');
+        assert!(inputter.get_char(&mut c) && c == b'b');
+        assert!(!inputter.get_char(&mut c));
+        assert_eq!(c, b'b');
+        inputter.end_input();
+    }
+
+    #[test]
+    fn assemble_and_run() {
+        let dir = TempDir::new().unwrap();
+        let asm = dir.path().join("echo.asm");
+        fs::write(
+            &asm,
+            ".ORIG x3000
+            GETC
+            OUT
+            LEA R0, MSG
+            PUTS
+            HALT
+    MSG     .STRINGZ \"!\"
+            .END",
+        )
+        .unwrap();
+
+        let diagnostics = Recorder::default();
+        let mut assembler =
+            Assembler::new(diagnostics.clone(), PrintType::Error, false);
+        let obj = assembler.pin_mut().assemble(asm.to_str().unwrap());
+        assert!(!obj.is_null(), "{}", diagnostics.text());
+
+        let output = Recorder::default();
+        let mut sim =
+            Sim::new(output.clone(), input("a"), PrintType::FatalError);
+        assert!(sim.pin_mut().load_obj_file(&obj.to_string_lossy()));
+        assert_eq!(sim.get_mem(0x3000), 0xF020);
+        assert!(sim.get_mem_line(0x3000).to_string_lossy().contains("GETC"));
+
+        sim.pin_mut().set_pc(0x3000);
+        assert!(sim.pin_mut().run_until_halt());
+        assert!(output.text().contains("a!"), "{:?}", output.text());
+        assert!(sim.get_inst_exec_count() > 0);
+        assert!(!sim.did_exceed_inst_limit());
+    }
+
+    #[test]
+    fn assembly_errors() {
+        let dir = TempDir::new().unwrap();
+        let asm = dir.path().join("bad.asm");
+        fs::write(&asm, ".ORIG x3000\nADD R9, R0, #1\n.END\n").unwrap();
+
+        let diagnostics = Recorder::default();
+        let mut assembler =
+            Assembler::new(diagnostics.clone(), PrintType::Error, false);
+        let obj = assembler.pin_mut().assemble(asm.to_str().unwrap());
+
+        assert!(obj.is_null());
+        assert!(!diagnostics.text().is_empty());
+    }
+
+    #[test]
+    fn stepping_and_limits() {
+        let mut sim = Sim::new(|_: &[u8]| {}, || None, PrintType::FatalError);
+
+        // ADD R1, R1, #1; BRnzp #-2 (back to the ADD).
+        sim.pin_mut().set_mem(0x3000, 0x1261);
+        sim.pin_mut().set_mem(0x3001, 0x0FFE);
+        sim.pin_mut().set_pc(0x3000);
+
+        assert!(sim.pin_mut().step_in());
+        assert_eq!((sim.get_pc(), sim.get_reg(1)), (0x3001, 1));
+        assert!(sim.pin_mut().step_in());
+        assert_eq!(sim.get_pc(), 0x3000);
+
+        sim.pin_mut().set_breakpoint(0x3001);
+        let _ = sim.pin_mut().run_until_halt();
+        assert_eq!((sim.get_pc(), sim.get_reg(1)), (0x3001, 2));
+        assert!(sim.pin_mut().remove_breakpoint(0x3001));
+        assert!(!sim.pin_mut().remove_breakpoint(0x3001));
+
+        sim.pin_mut().set_run_inst_limit(1000);
+        let _ = sim.pin_mut().run_until_halt();
+        assert!(sim.did_exceed_inst_limit());
+        assert_ne!(sim.get_reg(1), 2);
+    }
+}
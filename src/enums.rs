@@ -0,0 +1,101 @@
+//! Typed versions of the enums in the `LC3Tools` API.
+//!
+//! `bindgen` represents C++ enums as integer type aliases and a set of
+//! constants (i.e. [`PrintType_P_NONE`]) which makes it very easy to pass the
+//! wrong magic number to a function. The enums in this module mirror their
+//! `LC3Tools` counterparts and can be converted to and from those raw values.
+//!
+//! The variants in this module are generated by `build.rs` from the `LC3Tools`
+//! headers and their discriminants come from the constants in the
+//! [bindings](crate::root); if the two ever disagree (i.e. a variant was added
+//! to the headers but the bindings weren't regenerated) this module will fail
+//! to compile.
+//!
+//! [`PrintType_P_NONE`]: crate::root::lc3::utils::PrintType_P_NONE
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// The error produced when a raw value doesn't correspond to any variant of
+/// one of the enums in this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InvalidDiscriminant {
+    /// The name of the enum we tried to convert to.
+    pub ty: &'static str,
+    /// The raw value that we were given.
+    pub value: i32,
+}
+
+impl Display for InvalidDiscriminant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is not a valid `{}`", self.value, self.ty)
+    }
+}
+
+impl Error for InvalidDiscriminant {}
+
+macro_rules! ffi_enum {
+    ($(
+        $(#[$meta:meta])*
+        $name:ident ($cpp_name:literal): $raw:ty {
+            $(
+                $(#[$v_meta:meta])*
+                $variant:ident ($cpp_variant:literal) = $value:path,
+            )*
+        }
+    )*) => {$(
+        $(#[$meta])*
+        #[repr(i32)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum $name {
+            $(
+                $(#[$v_meta])*
+                $variant = $value,
+            )*
+        }
+
+        impl $name {
+            /// All the variants of this enum, in declaration order.
+            pub const VARIANTS: &'static [$name] = &[$($name::$variant,)*];
+
+            /// The name of the corresponding `LC3Tools` enumerator.
+            pub fn cpp_name(self) -> &'static str {
+                match self {
+                    $($name::$variant => $cpp_variant,)*
+                }
+            }
+        }
+
+        impl From<$name> for $raw {
+            fn from(val: $name) -> $raw {
+                val as $raw
+            }
+        }
+
+        impl TryFrom<$raw> for $name {
+            type Error = InvalidDiscriminant;
+
+            fn try_from(value: $raw) -> Result<Self, InvalidDiscriminant> {
+                $(
+                    if value == $value {
+                        return Ok($name::$variant);
+                    }
+                )*
+
+                Err(InvalidDiscriminant {
+                    ty: $cpp_name,
+                    value,
+                })
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}::{}", $cpp_name, self.cpp_name())
+            }
+        }
+    )*};
+}
+
+include!(concat!(env!("OUT_DIR"), "/enums.rs"));
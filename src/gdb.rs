@@ -0,0 +1,729 @@
+//! A [GDB remote serial protocol][rsp] stub for the [`Simulator`].
+//!
+//! This lets debugger front-ends (GDB itself, editor integrations, etc.)
+//! drive a simulator over a TCP connection or a Unix socket:
+//!
+//! ```rust,no_run
+//! use std::net::TcpListener;
+//! use lc3tools_sys::{asm::assemble, gdb::GdbStub, sim::Simulator};
+//!
+//! let program = assemble(".ORIG x3000\nAND R0, R0, #0\nHALT\n.END").unwrap();
+//! let mut sim = Simulator::builder().build();
+//! sim.load_program(program.iter());
+//! sim.set_pc(0x3000);
+//!
+//! let (stream, _) = TcpListener::bind("127.0.0.1:1234").unwrap().accept().unwrap();
+//! GdbStub::new(&mut sim).serve(stream).unwrap();
+//! ```
+//!
+//! GDB addresses memory in bytes but the LC-3 is word addressed, so the memory
+//! packets (`m`, `M`) take byte addresses and lengths: the word at `x3000` is
+//! at byte address `0x6000`, sent little endian. Requests that start or end
+//! halfway through a word are rejected. Everything else deals in words: the
+//! registers are `R0` to `R7`, the PC and the PSR, in that order (see
+//! [`TARGET_XML`]), and the PC as well as breakpoint and resume addresses are
+//! word addresses (as the program sees them). To look at the memory the PC
+//! points to in GDB, double it (`x/4xh $pc * 2`).
+//!
+//! Supported packets: `?`, `g`, `G`, `p`, `P`, `m`, `M`, `s`, `c`, `Z0`/`Z1`,
+//! `z0`/`z1`, `D`, `k`, `qSupported`, `qXfer:features:read`, `qAttached`,
+//! `QStartNoAckMode` and the thread queries GDB insists on. Everything else
+//! gets the empty ("unsupported") reply.
+//!
+//! Runs are synchronous: the stub doesn't read from the connection while the
+//! machine is running, so interrupts (`^C`) aren't supported; use an
+//! [instruction limit](Simulator::set_instruction_limit) to keep runaway
+//! programs in check.
+//!
+//! [rsp]: https://sourceware.org/gdb/current/onlinedocs/gdb/Remote-Protocol.html
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+
+use crate::root::State;
+use crate::sim::{RunError, Simulator};
+
+/// The target description the stub hands out (as `target.xml`).
+///
+/// GDB doesn't know the LC-3 so there's no `<architecture>`; this only
+/// describes the registers. GDB can read and write registers and memory, set
+/// breakpoints and step with it but it can't disassemble, and since the
+/// registers aren't byte addresses none of them are typed as pointers.
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.lc3tools.lc3">
+    <reg name="r0" bitsize="16" type="int16" regnum="0"/>
+    <reg name="r1" bitsize="16" type="int16"/>
+    <reg name="r2" bitsize="16" type="int16"/>
+    <reg name="r3" bitsize="16" type="int16"/>
+    <reg name="r4" bitsize="16" type="int16"/>
+    <reg name="r5" bitsize="16" type="int16"/>
+    <reg name="r6" bitsize="16" type="uint16"/>
+    <reg name="r7" bitsize="16" type="uint16"/>
+    <reg name="pc" bitsize="16" type="uint16"/>
+    <reg name="psr" bitsize="16" type="uint16"/>
+  </feature>
+</target>
+"#;
+
+/// `R0` to `R7`, the PC and the PSR.
+const NUM_REGS: usize = 10;
+const PC: usize = 8;
+const PSR: usize = 9;
+
+/// The largest packet we accept (and advertise).
+const PACKET_SIZE: usize = 0x4000;
+
+/// Signals we report stops with.
+const SIGTRAP: u8 = 5;
+const SIGILL: u8 = 4;
+
+/// Serves a single GDB connection for a [`Simulator`].
+#[derive(Debug)]
+pub struct GdbStub<'s> {
+    sim: &'s mut Simulator,
+    no_ack: bool,
+}
+
+/// What to do after handling a packet.
+enum Next {
+    Reply(String),
+    /// Send the reply and hang up.
+    Close(String),
+    /// Hang up without replying (`k`).
+    Kill,
+}
+
+fn hex_word(out: &mut String, word: u16) {
+    let [lo, hi] = word.to_le_bytes();
+    let _ = write!(out, "{:02x}{:02x}", lo, hi);
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s, 16).ok()
+}
+
+/// Parses little endian words from a string of hex digits.
+fn parse_words(s: &str) -> Option<Vec<u16>> {
+    if s.len() % 4 != 0 || !s.is_ascii() {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(4)
+        .map(|i| {
+            let lo = u8::from_str_radix(&s[i..i + 2], 16).ok()?;
+            let hi = u8::from_str_radix(&s[i + 2..i + 4], 16).ok()?;
+            Some(u16::from_le_bytes([lo, hi]))
+        })
+        .collect()
+}
+
+/// Parses `addr,len`.
+fn parse_range(s: &str) -> Option<(u16, usize)> {
+    let mut it = s.splitn(2, ',');
+    let addr = parse_hex(it.next()?)?;
+    let len = parse_hex(it.next()?)? as usize;
+
+    if addr <= u32::from(u16::max_value()) {
+        Some((addr as u16, len))
+    } else {
+        None
+    }
+}
+
+/// Parses the `addr,len` of a memory packet (in bytes) into a word address
+/// and a number of words; both have to be even.
+fn parse_mem_range(s: &str) -> Option<(u16, usize)> {
+    let mut it = s.splitn(2, ',');
+    let addr = parse_hex(it.next()?)?;
+    let len = parse_hex(it.next()?)?;
+
+    if addr % 2 == 0 && len % 2 == 0 && addr < 0x2_0000 {
+        Some(((addr / 2) as u16, len as usize / 2))
+    } else {
+        None
+    }
+}
+
+fn error(code: u8) -> String {
+    format!("E{:02x}", code)
+}
+
+fn ok() -> String {
+    "OK".to_string()
+}
+
+/// A connection with a little read buffer; the protocol is read a byte at a
+/// time.
+struct Conn<S> {
+    stream: S,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<S: Read + Write> Conn<S> {
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        if self.pos == self.buf.len() {
+            self.buf.resize(PACKET_SIZE, 0);
+            let read = self.stream.read(&mut self.buf)?;
+            self.buf.truncate(read);
+            self.pos = 0;
+
+            if read == 0 {
+                return Ok(None);
+            }
+        }
+
+        self.pos += 1;
+        Ok(Some(self.buf[self.pos - 1]))
+    }
+
+    /// Reads the next packet, acknowledging it unless `no_ack` is set.
+    /// Returns `None` when the connection is closed.
+    fn packet(&mut self, no_ack: bool) -> io::Result<Option<String>> {
+        loop {
+            // Skip acks and anything else (i.e. `^C`) until a packet starts.
+            loop {
+                match self.byte()? {
+                    Some(b'$') => break,
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            }
+
+            // The checksum covers the packet as sent, escapes and all.
+            let mut data = Vec::new();
+            let mut actual = 0u8;
+            loop {
+                match self.byte()? {
+                    Some(b'#') => break,
+                    Some(b'}') => match self.byte()? {
+                        Some(b) => {
+                            actual = actual.wrapping_add(b'}').wrapping_add(b);
+                            data.push(b ^ 0x20);
+                        }
+                        None => return Ok(None),
+                    },
+                    Some(b) => {
+                        actual = actual.wrapping_add(b);
+                        data.push(b);
+                    }
+                    None => return Ok(None),
+                }
+            }
+
+            let mut checksum = [0; 2];
+            for c in checksum.iter_mut() {
+                *c = match self.byte()? {
+                    Some(b) => b,
+                    None => return Ok(None),
+                };
+            }
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok());
+
+            if !no_ack {
+                let good = expected == Some(actual);
+                self.stream.write_all(if good { b"+" } else { b"-" })?;
+                if !good {
+                    continue;
+                }
+            }
+
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, reply: &str) -> io::Result<()> {
+        let checksum = reply.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", reply, checksum)?;
+        self.stream.flush()
+    }
+}
+
+impl<'s> GdbStub<'s> {
+    /// Makes a stub for a simulator.
+    ///
+    /// The simulator should already have a program loaded and its PC pointing
+    /// at the first instruction to run.
+    pub fn new(sim: &'s mut Simulator) -> Self {
+        Self { sim, no_ack: false }
+    }
+
+    /// Serves a connection until the debugger detaches or kills the target, or
+    /// the connection is closed.
+    pub fn serve<S: Read + Write>(&mut self, stream: S) -> io::Result<()> {
+        let mut conn = Conn {
+            stream,
+            buf: Vec::new(),
+            pos: 0,
+        };
+
+        while let Some(packet) = conn.packet(self.no_ack)? {
+            match self.handle(&packet) {
+                Next::Reply(reply) => conn.send(&reply)?,
+                Next::Close(reply) => return conn.send(&reply),
+                Next::Kill => return Ok(()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn regs(&self) -> [u16; NUM_REGS] {
+        let state = self.sim.state();
+        let mut regs = [0; NUM_REGS];
+        for (r, &v) in regs.iter_mut().zip(state.regs.iter()) {
+            *r = v as u16;
+        }
+        regs[PC] = state.pc as u16;
+        regs[PSR] = state.psr as u16;
+
+        regs
+    }
+
+    /// Sets a register; the PSR can't be set.
+    fn set_reg(&mut self, reg: usize, value: u16) -> bool {
+        match reg {
+            0..=7 => self.sim.set_reg(reg as u8, value),
+            PC => self.sim.set_pc(value),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// The reply for a stop.
+    fn stop(&self, res: Result<State, RunError>) -> String {
+        match res {
+            Err(_) => format!("S{:02x}", SIGILL),
+            Ok(_) if self.sim.halted() => "W00".to_string(),
+            Ok(_) => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    fn handle(&mut self, packet: &str) -> Next {
+        let (cmd, args) = match packet.char_indices().nth(1) {
+            Some((i, _)) => packet.split_at(i),
+            None => (packet, ""),
+        };
+
+        let reply = match cmd {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => {
+                let mut out = String::new();
+                for &r in self.regs().iter() {
+                    hex_word(&mut out, r);
+                }
+
+                out
+            }
+            "G" => match parse_words(args) {
+                Some(values) if values.len() <= NUM_REGS => {
+                    for (reg, &v) in values.iter().enumerate().take(PSR) {
+                        let _ = self.set_reg(reg, v);
+                    }
+
+                    ok()
+                }
+                _ => error(1),
+            },
+            "p" => match parse_hex(args).map(|r| r as usize) {
+                Some(reg) if reg < NUM_REGS => {
+                    let mut out = String::new();
+                    hex_word(&mut out, self.regs()[reg]);
+                    out
+                }
+                _ => error(1),
+            },
+            "P" => {
+                let mut it = args.splitn(2, '=');
+                let reg = it.next().and_then(parse_hex);
+                let value = it.next().and_then(parse_words);
+
+                match (reg, value.as_deref()) {
+                    (Some(reg), Some(&[v]))
+                        if self.set_reg(reg as usize, v) =>
+                    {
+                        ok()
+                    }
+                    _ => error(1),
+                }
+            }
+            "m" => match parse_mem_range(args) {
+                Some((addr, len))
+                    if len
+                        .checked_mul(4)
+                        .map_or(false, |l| l < PACKET_SIZE) =>
+                {
+                    let mut out = String::new();
+                    for i in 0..len {
+                        hex_word(
+                            &mut out,
+                            self.sim.mem(addr.wrapping_add(i as u16)),
+                        );
+                    }
+
+                    out
+                }
+                _ => error(1),
+            },
+            "M" => {
+                let mut it = args.splitn(2, ':');
+                let range = it.next().and_then(parse_mem_range);
+                let data = it.next().and_then(parse_words);
+
+                match (range, data) {
+                    (Some((addr, len)), Some(words)) if words.len() == len => {
+                        for (i, &w) in words.iter().enumerate() {
+                            self.sim.set_mem(addr.wrapping_add(i as u16), w);
+                        }
+
+                        ok()
+                    }
+                    _ => error(1),
+                }
+            }
+            "s" | "c" => {
+                if !args.is_empty() {
+                    match parse_hex(args) {
+                        Some(pc) if pc <= 0xFFFF => self.sim.set_pc(pc as u16),
+                        _ => return Next::Reply(error(1)),
+                    }
+                }
+
+                if self.sim.halted() {
+                    "W00".to_string()
+                } else if cmd == "s" {
+                    let res = self.sim.step_in();
+                    self.stop(res)
+                } else {
+                    let res = self.sim.resume();
+                    self.stop(res)
+                }
+            }
+            "Z" | "z" => {
+                let mut it = args.splitn(3, ',');
+                let kind = it.next();
+                let addr = it.next().and_then(parse_hex);
+
+                match (kind, addr) {
+                    (Some("0"), Some(addr)) | (Some("1"), Some(addr))
+                        if addr <= 0xFFFF =>
+                    {
+                        if cmd == "Z" {
+                            let _ = self.sim.set_breakpoint(addr as u16);
+                        } else {
+                            let _ = self.sim.remove_breakpoint(addr as u16);
+                        }
+
+                        ok()
+                    }
+                    (Some("0"), _) | (Some("1"), _) => error(1),
+                    // Watchpoints aren't supported.
+                    _ => String::new(),
+                }
+            }
+            "D" => return Next::Close(ok()),
+            "k" => return Next::Kill,
+            "H" | "T" => ok(),
+            _ => self.query(packet),
+        };
+
+        Next::Reply(reply)
+    }
+
+    /// Handles the `q`/`Q` packets (and anything else we don't know).
+    fn query(&mut self, packet: &str) -> String {
+        const FEATURES: &str = "qXfer:features:read:target.xml:";
+
+        if packet.starts_with("qSupported") {
+            format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+                PACKET_SIZE
+            )
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            ok()
+        } else if packet.starts_with(FEATURES) {
+            match parse_range(&packet[FEATURES.len()..]) {
+                Some((off, len)) => {
+                    let off = usize::from(off).min(TARGET_XML.len());
+                    let end = off.saturating_add(len).min(TARGET_XML.len());
+                    let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+
+                    format!("{}{}", more, &TARGET_XML[off..end])
+                }
+                None => error(1),
+            }
+        } else {
+            match packet {
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::asm::assemble;
+
+    /// An in-memory connection: reads come from `input` and writes go to
+    /// `output`.
+    struct Pipe {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Pipe {
+        fn new(input: impl Into<Vec<u8>>) -> Self {
+            Self {
+                input: Cursor::new(input.into()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn frame(packet: &str) -> String {
+        let checksum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        format!("${}#{:02x}", packet, checksum)
+    }
+
+    /// Splits what the stub sent into acks (`+`/`-`) and packets, checking
+    /// the packets' checksums.
+    fn replies(output: &[u8]) -> Vec<String> {
+        let output = std::str::from_utf8(output).unwrap();
+        let mut replies = Vec::new();
+
+        let mut rest = output;
+        while !rest.is_empty() {
+            if rest.starts_with('$') {
+                let end = rest.find('#').unwrap();
+                let packet = &rest[1..end];
+                assert_eq!(frame(packet), &rest[..end + 3]);
+
+                replies.push(packet.to_string());
+                rest = &rest[end + 3..];
+            } else {
+                replies.push(rest[..1].to_string());
+                rest = &rest[1..];
+            }
+        }
+
+        replies
+    }
+
+    /// Serves the given (raw) input and returns the replies, without acks.
+    fn serve_raw(sim: &mut Simulator, input: &str) -> Vec<String> {
+        let mut pipe = Pipe::new(input);
+        GdbStub::new(sim).serve(&mut pipe).unwrap();
+
+        replies(&pipe.output)
+            .into_iter()
+            .filter(|r| r != "+")
+            .collect()
+    }
+
+    fn serve(sim: &mut Simulator, packets: &[&str]) -> Vec<String> {
+        let input = packets.iter().map(|p| frame(p)).collect::<String>();
+        serve_raw(sim, &input)
+    }
+
+    /// A machine with a program that increments R1 twice and halts loaded,
+    /// ready to run from x3000.
+    fn sim() -> Simulator {
+        let program =
+            assemble(".ORIG x3000\nADD R1, R1, #1\nADD R1, R1, #1\nHALT\n.END")
+                .unwrap();
+
+        let mut sim = Simulator::builder().instruction_limit(100_000).build();
+        sim.load_program(program.iter());
+        sim.set_pc(0x3000);
+        sim
+    }
+
+    #[test]
+    fn read_registers() {
+        let mut sim = sim();
+        sim.set_reg(3, 0x1234);
+
+        let regs = serve(&mut sim, &["g"]).remove(0);
+        assert_eq!(regs.len(), NUM_REGS * 4);
+        assert_eq!(&regs[12..16], "3412");
+        assert_eq!(&regs[32..36], "0030");
+    }
+
+    #[test]
+    fn write_registers() {
+        let mut sim = sim();
+        let psr = sim.state().psr;
+
+        let mut regs = String::from("G");
+        for v in 1..=8 {
+            hex_word(&mut regs, v);
+        }
+        hex_word(&mut regs, 0x3002);
+        hex_word(&mut regs, 0xFFFF);
+
+        assert_eq!(serve(&mut sim, &[&regs, "G123"]), ["OK", "E01"]);
+
+        let state = sim.state();
+        assert_eq!(state.regs, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(state.pc, 0x3002);
+        assert_eq!(state.psr, psr);
+    }
+
+    #[test]
+    fn memory() {
+        let mut sim = sim();
+
+        assert_eq!(
+            serve(
+                &mut sim,
+                &["M8000,4:3412cdab", "m8000,6", "M8000,4:3412", "m8000,0"],
+            ),
+            ["OK", "3412cdab0000", "E01", ""]
+        );
+        assert_eq!(sim.mem(0x4001), 0xABCD);
+
+        // x4001 is at 0x8002.
+        assert_eq!(serve(&mut sim, &["m8002,2", "m8004,2"]), ["cdab", "0000"]);
+    }
+
+    #[test]
+    fn memory_requests_are_word_aligned() {
+        let mut sim = sim();
+
+        assert_eq!(
+            serve(
+                &mut sim,
+                &[
+                    "m8001,2",
+                    "m8000,3",
+                    "M8001,2:3412",
+                    "M8000,1:34",
+                    "m20000,2"
+                ],
+            ),
+            ["E01", "E01", "E01", "E01", "E01"]
+        );
+        assert_eq!(sim.mem(0x4000), 0);
+    }
+
+    #[test]
+    fn memory_reads_are_bounded() {
+        let mut sim = sim();
+        let too_long = format!("m0,{:x}", PACKET_SIZE / 2);
+
+        assert_eq!(
+            serve(&mut sim, &[&too_long, "m0,40000000", "m0,ffffffff"]),
+            ["E01", "E01", "E01"]
+        );
+    }
+
+    #[test]
+    fn step() {
+        let mut sim = sim();
+
+        assert_eq!(
+            serve(&mut sim, &["s", "p1", "p8"]),
+            ["S05", "0100", "0130"]
+        );
+    }
+
+    #[test]
+    fn breakpoint_and_continue() {
+        let mut sim = sim();
+
+        assert_eq!(
+            serve(&mut sim, &["Z0,3001,2", "c", "p8", "p1", "z0,3001,2", "c"]),
+            ["OK", "S05", "0130", "0100", "OK", "W00"]
+        );
+        assert_eq!(sim.state().regs[1], 2);
+        assert_eq!(sim.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn bad_checksum_is_nacked() {
+        let mut pipe = Pipe::new(format!("$g#00{}", frame("qAttached")));
+        GdbStub::new(&mut sim()).serve(&mut pipe).unwrap();
+
+        assert_eq!(replies(&pipe.output), ["-", "+", "1"]);
+    }
+
+    #[test]
+    fn escaped_payload() {
+        let mut sim = sim();
+
+        // `}` escapes the next byte (XOR 0x20); here it's the `3` in the data
+        // and the checksum covers the escape as sent.
+        let packet = "M8000,2:}\x13412";
+        assert_eq!(serve_raw(&mut sim, &frame(packet)), ["OK"]);
+        assert_eq!(sim.mem(0x4000), 0x1234);
+    }
+
+    #[test]
+    fn packets() {
+        let mut conn = Conn {
+            stream: Pipe::new(format!(
+                "+{}$g#00{}{}",
+                frame("m0,1"),
+                frame("X}\x03}]"),
+                "$c#6"
+            )),
+            buf: Vec::new(),
+            pos: 0,
+        };
+
+        assert_eq!(conn.packet(false).unwrap().as_deref(), Some("m0,1"));
+        // The bad packet is skipped.
+        assert_eq!(conn.packet(false).unwrap().as_deref(), Some("X#}"));
+        // Cut off mid-checksum.
+        assert_eq!(conn.packet(false).unwrap(), None);
+
+        assert_eq!(replies(&conn.stream.output), ["+", "-", "+"]);
+    }
+
+    #[test]
+    fn no_ack_mode() {
+        let mut conn = Conn {
+            stream: Pipe::new(format!("$g#00{}", frame("?"))),
+            buf: Vec::new(),
+            pos: 0,
+        };
+
+        assert_eq!(conn.packet(true).unwrap().as_deref(), Some("g"));
+        assert_eq!(conn.packet(true).unwrap().as_deref(), Some("?"));
+        assert_eq!(conn.stream.output, b"");
+    }
+}
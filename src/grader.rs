@@ -0,0 +1,426 @@
+//! A Rust version of the `LC3Tools` grader framework
+//! (`frontend/grader/framework.cpp`).
+//!
+//! The C++ framework's test cases hold their bodies in `std::function`s and
+//! live in a global `std::vector` (see [`TestCase`](crate::root::TestCase) and
+//! [`tests`](crate::root::tests)), neither of which can be used from Rust. This
+//! module offers the same model — named test cases worth some number of
+//! points, each of which runs against a freshly loaded machine and makes a
+//! series of verifications — with closures as test bodies.
+//!
+//! ```rust,no_run
+//! use lc3tools_sys::{asm::assemble, grader::TestSuite};
+//!
+//! let program = assemble(".ORIG x3000\nAND R0, R0, #0\nHALT\n.END").unwrap();
+//!
+//! let report = TestSuite::new(program)
+//!     .test("clears R0", 10, |sim, grader| {
+//!         let state = sim.run(0x3000);
+//!         grader.verify("halts", state.is_ok());
+//!         grader.verify("R0 is 0", state.map(|s| s.regs[0] == 0).unwrap_or(false));
+//!     })
+//!     .run();
+//!
+//! println!("{}", report);
+//! ```
+//!
+//! To grade many submissions against the same test cases at once, use a
+//! [`ParallelRunner`].
+
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+use crate::asm::Program;
+use crate::sim::{pick_seed, Simulator, SimulatorBuilder};
+
+mod output;
+mod report;
+mod runner;
+pub use output::{OutputMatcher, OutputMismatch, Whitespace};
+pub use report::{GradeReport, TestReport, Verification};
+pub use runner::{ParallelRunner, Submission, SubmissionReport};
+
+/// The instruction limit a [`TestSuite`], a [`ParallelRunner`] (and
+/// `lc3-grade`) run programs with unless told otherwise, so that a program
+/// that never halts can't hang grading.
+pub const DEFAULT_INSTRUCTION_LIMIT: u64 = 5_000_000;
+
+/// Records the verifications a test case makes.
+///
+/// This is the equivalent of the `VERIFY` family of macros in the C++
+/// framework.
+#[derive(Debug, Default)]
+pub struct Grader {
+    verifications: Vec<Verification>,
+}
+
+impl Grader {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a check; returns whether it passed.
+    pub fn verify(&mut self, label: impl Into<String>, passed: bool) -> bool {
+        self.verifications.push(Verification {
+            label: label.into(),
+            passed,
+            expected: None,
+            actual: None,
+        });
+
+        passed
+    }
+
+    /// Records a check that two values are equal; returns whether they were.
+    ///
+    /// Unlike [`verify`](Grader::verify) this keeps both values (as their
+    /// [`Debug`] representations) so they can be shown in reports.
+    pub fn verify_eq<T: PartialEq + Debug>(
+        &mut self,
+        label: impl Into<String>,
+        expected: T,
+        actual: T,
+    ) -> bool {
+        let passed = expected == actual;
+        self.verifications.push(Verification {
+            label: label.into(),
+            passed,
+            expected: Some(format!("{:?}", expected)),
+            actual: Some(format!("{:?}", actual)),
+        });
+
+        passed
+    }
+
+    /// Records a check that some output (i.e. [`Simulator::output`]) matches;
+    /// returns whether it did.
+    ///
+    /// The report has the normalized expected and actual output.
+    pub fn verify_output(
+        &mut self,
+        label: impl Into<String>,
+        matcher: &OutputMatcher,
+        output: &str,
+    ) -> bool {
+        let (passed, expected, actual) = match matcher.check(output) {
+            Ok(()) => {
+                (true, matcher.expected().to_string(), output.to_string())
+            }
+            Err(m) => (false, m.expected, m.actual),
+        };
+
+        self.verifications.push(Verification {
+            label: label.into(),
+            passed,
+            expected: Some(format!("{:?}", expected)),
+            actual: Some(format!("{:?}", actual)),
+        });
+
+        passed
+    }
+
+    /// The verifications made so far.
+    pub fn verifications(&self) -> &[Verification] {
+        &self.verifications
+    }
+}
+
+type TestFunc<'t> = Box<dyn FnMut(&mut Simulator, &mut Grader) + 't>;
+
+/// A named test case worth some number of points.
+pub struct TestCase<'t> {
+    name: String,
+    points: u32,
+    randomize: bool,
+    func: TestFunc<'t>,
+}
+
+impl Debug for TestCase<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestCase")
+            .field("name", &self.name)
+            .field("points", &self.points)
+            .field("randomize", &self.randomize)
+            .finish()
+    }
+}
+
+/// A program and the test cases to grade it with.
+#[derive(Debug)]
+pub struct TestSuite<'t> {
+    program: Program,
+    simulator: SimulatorBuilder,
+    tests: Vec<TestCase<'t>>,
+}
+
+impl<'t> TestSuite<'t> {
+    /// Makes an empty test suite for the given program; test cases run on
+    /// the default [`SimulatorBuilder`] (with an instruction limit of
+    /// [`DEFAULT_INSTRUCTION_LIMIT`]).
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            simulator: SimulatorBuilder::new()
+                .instruction_limit(DEFAULT_INSTRUCTION_LIMIT),
+            tests: Vec::new(),
+        }
+    }
+
+    /// Sets how the simulator each test case runs on is made (i.e. its OS
+    /// and instruction limit); a builder without an instruction limit gets
+    /// [`DEFAULT_INSTRUCTION_LIMIT`].
+    pub fn simulator(mut self, builder: SimulatorBuilder) -> Self {
+        self.simulator = match builder.instruction_limit {
+            0 => builder.instruction_limit(DEFAULT_INSTRUCTION_LIMIT),
+            _ => builder,
+        };
+        self
+    }
+
+    /// Adds a test case.
+    ///
+    /// The function is given a machine that has the program loaded and that
+    /// hasn't been run yet.
+    pub fn test<F>(self, name: impl Into<String>, points: u32, func: F) -> Self
+    where
+        F: FnMut(&mut Simulator, &mut Grader) + 't,
+    {
+        self.add(name.into(), points, false, Box::new(func))
+    }
+
+    /// Adds a test case that runs on a [randomized](Simulator::randomize)
+    /// machine.
+    ///
+    /// The seed used is picked with [`pick_seed`] and is recorded in the
+    /// test's report.
+    pub fn randomized_test<F>(
+        self,
+        name: impl Into<String>,
+        points: u32,
+        func: F,
+    ) -> Self
+    where
+        F: FnMut(&mut Simulator, &mut Grader) + 't,
+    {
+        self.add(name.into(), points, true, Box::new(func))
+    }
+
+    fn add(
+        mut self,
+        name: String,
+        points: u32,
+        randomize: bool,
+        func: TestFunc<'t>,
+    ) -> Self {
+        self.tests.push(TestCase {
+            name,
+            points,
+            randomize,
+            func,
+        });
+
+        self
+    }
+
+    /// Runs all the test cases, in the order they were added.
+    pub fn run(&mut self) -> GradeReport {
+        let program = &self.program;
+        let builder = &self.simulator;
+
+        let tests = self
+            .tests
+            .iter_mut()
+            .map(|test| {
+                let func = &mut test.func;
+                run_test(
+                    builder,
+                    program,
+                    &test.name,
+                    test.points,
+                    test.randomize,
+                    |sim, grader| func(sim, grader),
+                )
+            })
+            .collect();
+
+        GradeReport { tests }
+    }
+}
+
+/// Runs one test case on a fresh simulator that has `program` loaded.
+fn run_test(
+    builder: &SimulatorBuilder,
+    program: &Program,
+    name: &str,
+    points: u32,
+    randomize: bool,
+    func: impl FnOnce(&mut Simulator, &mut Grader),
+) -> TestReport {
+    let seed = if randomize { Some(pick_seed()) } else { None };
+    let builder = match seed {
+        Some(seed) => builder.clone().randomize(seed),
+        None => builder.clone(),
+    };
+
+    let mut sim = builder.build();
+    sim.load_program(program.iter());
+
+    let mut grader = Grader::new();
+    let start = Instant::now();
+    let res =
+        panic::catch_unwind(AssertUnwindSafe(|| func(&mut sim, &mut grader)));
+    let runtime = start.elapsed();
+
+    if let Err(payload) = res {
+        let _ = grader.verify(
+            format!("test panicked: {}", panic_message(&*payload)),
+            false,
+        );
+    }
+
+    TestReport::new(
+        name.to_string(),
+        points,
+        seed,
+        runtime,
+        sim.instructions_executed(),
+        sim.output().into_owned(),
+        grader.verifications,
+    )
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "<unknown>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asm::assemble;
+
+    const DOUBLE: &str = "\
+        .ORIG x3000
+        ADD R1, R0, R0
+        HALT
+        .END
+";
+
+    fn double(input: u16) -> impl FnMut(&mut Simulator, &mut Grader) {
+        move |sim, grader| {
+            sim.set_reg(0, input);
+            let state = sim.run(0x3000);
+            let _ = grader.verify("halts", state.is_ok());
+            let _ = grader.verify_eq(
+                "R1",
+                u32::from(input) * 2,
+                state.unwrap().regs[1],
+            );
+            let _ = grader.verify("R1 is odd", state.unwrap().regs[1] % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn scoring() {
+        let report = TestSuite::new(assemble(DOUBLE).unwrap())
+            .test("doubles 3", 9, double(3))
+            .test("doubles 4", 3, double(4))
+            .test("checks nothing", 5, |_, _| {})
+            .run();
+
+        let names = report.tests.iter().map(|t| &*t.name).collect::<Vec<_>>();
+        assert_eq!(names, ["doubles 3", "doubles 4", "checks nothing"]);
+
+        // Points are the fraction of verifications that passed.
+        let earned = report.tests.iter().map(|t| t.points_earned);
+        assert_eq!(earned.collect::<Vec<_>>(), [6.0, 2.0, 0.0]);
+        assert_eq!(report.points_earned(), 8.0);
+        assert_eq!(report.points_possible(), 17);
+        assert!(report.tests.iter().all(|t| !t.passed()));
+        assert!(report.tests[0].instructions > 0);
+        assert_eq!(report.tests[0].seed, None);
+    }
+
+    #[test]
+    fn verifications() {
+        let mut grader = Grader::new();
+
+        assert!(grader.verify("yes", true));
+        assert!(!grader.verify_eq("eq", 1u16, 2));
+        assert!(grader.verify_eq(String::from("strs"), "a", "a"));
+
+        let matcher = OutputMatcher::exact("hi\n");
+        assert!(!grader.verify_output("output", &matcher, "ho\n"));
+
+        let v = grader.verifications();
+        assert_eq!(
+            v[0],
+            Verification {
+                label: "yes".to_string(),
+                passed: true,
+                expected: None,
+                actual: None,
+            }
+        );
+        assert_eq!(
+            (
+                v[1].passed,
+                v[1].expected.as_deref(),
+                v[1].actual.as_deref()
+            ),
+            (false, Some("1"), Some("2"))
+        );
+        assert_eq!(
+            (v[2].expected.as_deref(), v[2].actual.as_deref()),
+            (Some("\"a\""), Some("\"a\""))
+        );
+        assert_eq!(v[3].label, "output");
+        assert!(!v[3].passed);
+    }
+
+    #[test]
+    fn non_halting_programs_fail() {
+        let program = assemble(".ORIG x3000\nBR #-1\n.END\n").unwrap();
+        let check = |sim: &mut Simulator, grader: &mut Grader| {
+            let res = sim.run(0x3000);
+            let limited = res.err().map(|e| e.exceeded_instruction_limit);
+            let _ = grader.verify("halts", limited.is_none());
+            let _ = grader.verify_eq("hit the limit", Some(true), limited);
+        };
+
+        let default = TestSuite::new(program.clone()).test("spins", 1, check);
+        // A builder without a limit gets the default one too.
+        let unlimited = TestSuite::new(program)
+            .simulator(SimulatorBuilder::new())
+            .test("spins", 1, check);
+
+        for mut suite in vec![default, unlimited] {
+            let test = &suite.run().tests[0];
+            assert!(!test.passed());
+            assert_eq!(test.points_earned, 0.5);
+            assert!(test.instructions > 0);
+        }
+    }
+
+    #[test]
+    fn panics_fail() {
+        let report = TestSuite::new(assemble(DOUBLE).unwrap())
+            .test("panics", 2, |_, grader| {
+                let _ = grader.verify("before", true);
+                panic!("oh no");
+            })
+            .run();
+
+        let test = &report.tests[0];
+        assert_eq!(test.points_earned, 1.0);
+        assert_eq!(test.verifications[1].label, "test panicked: oh no");
+    }
+}
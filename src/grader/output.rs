@@ -0,0 +1,468 @@
+//! Checking a program's [output](crate::sim::Simulator::output).
+//!
+//! This is the Rust counterpart to the C++ framework's `outputCompare`, which
+//! only does exact and substring matching. Here the output and the expected
+//! output can be normalized first (i.e. to forgive a missing trailing newline)
+//! and mismatches come with a diff.
+
+use std::error::Error;
+use std::fmt::{self, Display, Write};
+
+use regex::{Regex, RegexBuilder};
+
+/// How whitespace is treated when comparing output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Whitespace must match exactly.
+    Exact,
+    /// Trailing whitespace on each line and trailing newlines are ignored;
+    /// `\r\n` is treated as `\n`.
+    Trailing,
+    /// Runs of whitespace (including newlines) are treated as a single space
+    /// and leading and trailing whitespace is ignored.
+    Collapse,
+    /// All whitespace is ignored.
+    Ignore,
+}
+
+impl Default for Whitespace {
+    fn default() -> Self {
+        Whitespace::Exact
+    }
+}
+
+impl Whitespace {
+    fn normalize(self, s: &str) -> String {
+        use Whitespace::*;
+        match self {
+            Exact => s.to_string(),
+            Trailing => s
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim_end_matches('\n')
+                .to_string(),
+            Collapse => s.split_whitespace().collect::<Vec<_>>().join(" "),
+            Ignore => s.chars().filter(|c| !c.is_whitespace()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Comparison {
+    Exact(String),
+    Substring(String),
+    /// Compiled with the matcher's case sensitivity.
+    Regex(Regex),
+    Lines(String),
+}
+
+// `Regex` isn't `PartialEq`; patterns are compared as written.
+impl PartialEq for Comparison {
+    fn eq(&self, other: &Self) -> bool {
+        use Comparison::*;
+        match (self, other) {
+            (Exact(a), Exact(b))
+            | (Substring(a), Substring(b))
+            | (Lines(a), Lines(b)) => a == b,
+            (Regex(a), Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Comparison {}
+
+fn compile(pattern: &str, ignore_case: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .multi_line(true)
+        .build()
+}
+
+/// Checks a program's output against what's expected.
+///
+/// ```rust
+/// use lc3tools_sys::grader::{OutputMatcher, Whitespace};
+///
+/// let matcher = OutputMatcher::exact("Hello, World!\n")
+///     .whitespace(Whitespace::Trailing)
+///     .ignore_case(true);
+///
+/// assert!(matcher.matches("hello, world!"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputMatcher {
+    comparison: Comparison,
+    ignore_case: bool,
+    whitespace: Whitespace,
+}
+
+impl OutputMatcher {
+    fn new(comparison: Comparison) -> Self {
+        Self {
+            comparison,
+            ignore_case: false,
+            whitespace: Whitespace::Exact,
+        }
+    }
+
+    /// Output must be exactly the given string (after normalization).
+    pub fn exact(expected: impl Into<String>) -> Self {
+        Self::new(Comparison::Exact(expected.into()))
+    }
+
+    /// Output must contain the given string (after normalization).
+    pub fn substring(expected: impl Into<String>) -> Self {
+        Self::new(Comparison::Substring(expected.into()))
+    }
+
+    /// Output must match the given regular expression somewhere.
+    ///
+    /// Whitespace normalization is applied to the output but not to the
+    /// pattern. The pattern is compiled in multi-line mode (`^` and `$` match
+    /// at the start and end of lines).
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self::new(Comparison::Regex(compile(pattern, false)?)))
+    }
+
+    /// Output must have the same lines as the given string.
+    ///
+    /// Unlike [`exact`](OutputMatcher::exact), normalization is applied to
+    /// each line on its own so [`Whitespace::Collapse`] and
+    /// [`Whitespace::Ignore`] won't join lines together. Line endings (`\n`
+    /// or `\r\n`) and trailing blank lines never matter.
+    pub fn lines(expected: impl Into<String>) -> Self {
+        Self::new(Comparison::Lines(expected.into()))
+    }
+
+    /// Makes the comparison case-insensitive.
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        if let Comparison::Regex(re) = &mut self.comparison {
+            *re = compile(re.as_str(), ignore_case)
+                .expect("the pattern to have been validated");
+        }
+
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Sets how whitespace is treated.
+    pub fn whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    fn normalize(&self, s: &str) -> String {
+        let s = if self.ignore_case {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        };
+
+        match self.comparison {
+            Comparison::Lines(_) => s
+                .lines()
+                .map(|l| self.whitespace.normalize(l))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim_end_matches('\n')
+                .to_string(),
+            _ => self.whitespace.normalize(&s),
+        }
+    }
+
+    /// What's expected, for reports: the expected output or the pattern.
+    pub fn expected(&self) -> &str {
+        use Comparison::*;
+        match &self.comparison {
+            Exact(s) | Substring(s) | Lines(s) => s,
+            Regex(re) => re.as_str(),
+        }
+    }
+
+    /// Checks some output; on a mismatch this returns what was compared and a
+    /// diff.
+    pub fn check(&self, output: &str) -> Result<(), OutputMismatch> {
+        let actual = self.normalize(output);
+
+        let (matched, expected) = match &self.comparison {
+            Comparison::Exact(exp) | Comparison::Lines(exp) => {
+                let exp = self.normalize(exp);
+                (exp == actual, exp)
+            }
+            Comparison::Substring(exp) => {
+                let exp = self.normalize(exp);
+                (actual.contains(&exp), exp)
+            }
+            Comparison::Regex(re) => {
+                (re.is_match(&actual), re.as_str().to_string())
+            }
+        };
+
+        if matched {
+            return Ok(());
+        }
+
+        let diff = match self.comparison {
+            Comparison::Exact(_) | Comparison::Lines(_) => {
+                line_diff(&expected, &actual)
+            }
+            Comparison::Substring(_) => format!(
+                "expected output containing {:?}, got:\n{}",
+                expected,
+                quoted_lines(&actual)
+            ),
+            Comparison::Regex(_) => format!(
+                "expected output matching /{}/, got:\n{}",
+                expected,
+                quoted_lines(&actual)
+            ),
+        };
+
+        Err(OutputMismatch {
+            expected,
+            actual,
+            diff,
+        })
+    }
+
+    /// Whether some output matches.
+    pub fn matches(&self, output: &str) -> bool {
+        self.check(output).is_ok()
+    }
+}
+
+/// Output that didn't match an [`OutputMatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputMismatch {
+    /// The normalized expected output (or the pattern, for regexes).
+    pub expected: String,
+    /// The normalized output.
+    pub actual: String,
+    /// A human readable explanation of the mismatch; for exact and
+    /// line-by-line comparisons this is a line diff.
+    pub diff: String,
+}
+
+impl Display for OutputMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "output did not match:\n{}", self.diff)
+    }
+}
+
+impl Error for OutputMismatch {}
+
+/// Each line of a string, `Debug` formatted so that whitespace is visible.
+fn quoted_lines(s: &str) -> String {
+    let mut out = String::new();
+    for line in s.split('\n') {
+        let _ = writeln!(out, "  {:?}", line);
+    }
+
+    out
+}
+
+/// The most cells [`line_diff`]'s LCS table may have (16 MiB worth); when
+/// the lines that differ need more than this, they're listed as removed and
+/// added wholesale instead of being diffed.
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+/// A diff of the lines in two strings (based on their longest common
+/// subsequence); lines are `Debug` formatted so that whitespace is visible.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let exp = expected.split('\n').collect::<Vec<_>>();
+    let act = actual.split('\n').collect::<Vec<_>>();
+
+    // Lines both start or end with don't need to go in the table.
+    let prefix = exp.iter().zip(&act).take_while(|(e, a)| e == a).count();
+    let suffix = exp[prefix..]
+        .iter()
+        .rev()
+        .zip(act[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    for line in &exp[..prefix] {
+        let _ = writeln!(out, "  {:?}", line);
+    }
+    diff_middle(
+        &exp[prefix..exp.len() - suffix],
+        &act[prefix..act.len() - suffix],
+        &mut out,
+    );
+    for line in &exp[exp.len() - suffix..] {
+        let _ = writeln!(out, "  {:?}", line);
+    }
+
+    out
+}
+
+fn diff_middle(exp: &[&str], act: &[&str], out: &mut String) {
+    let (n, m) = (exp.len(), act.len());
+
+    if (n + 1).saturating_mul(m + 1) > MAX_DIFF_CELLS {
+        for line in exp {
+            let _ = writeln!(out, "- {:?}", line);
+        }
+        for line in act {
+            let _ = writeln!(out, "+ {:?}", line);
+        }
+
+        return;
+    }
+
+    // `lcs[i * (m + 1) + j]` is the length of the LCS of `exp[i..]` and
+    // `act[j..]`.
+    let idx = |i: usize, j: usize| i * (m + 1) + j;
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[idx(i, j)] = if exp[i] == act[j] {
+                lcs[idx(i + 1, j + 1)] + 1
+            } else {
+                lcs[idx(i + 1, j)].max(lcs[idx(i, j + 1)])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && exp[i] == act[j] {
+            let _ = writeln!(out, "  {:?}", exp[i]);
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[idx(i + 1, j)] >= lcs[idx(i, j + 1)])
+        {
+            let _ = writeln!(out, "- {:?}", exp[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {:?}", act[j]);
+            j += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn whitespace_exact() {
+        assert_eq!(Whitespace::Exact.normalize(" a \r\n b\n"), " a \r\n b\n");
+    }
+
+    #[test]
+    fn whitespace_trailing() {
+        assert_eq!(
+            Whitespace::Trailing.normalize("  a \t\r\nb  \n\n\n"),
+            "  a\nb"
+        );
+        assert_eq!(Whitespace::Trailing.normalize("\n\na"), "\n\na");
+    }
+
+    #[test]
+    fn whitespace_collapse() {
+        assert_eq!(
+            Whitespace::Collapse.normalize(" \t a  b\r\n\n c \n"),
+            "a b c"
+        );
+    }
+
+    #[test]
+    fn whitespace_ignore() {
+        assert_eq!(Whitespace::Ignore.normalize(" a b\r\n\tc "), "abc");
+    }
+
+    #[test]
+    fn exact_with_normalization() {
+        let matcher = OutputMatcher::exact("Hello,  World!\n")
+            .whitespace(Whitespace::Collapse)
+            .ignore_case(true);
+
+        assert!(matcher.matches("hello, world!"));
+        assert!(matcher.matches("\nHELLO,\nWORLD!   "));
+        assert!(!matcher.matches("hello, world"));
+    }
+
+    #[test]
+    fn lines_are_normalized_separately() {
+        let matcher =
+            OutputMatcher::lines("a b\nc\n").whitespace(Whitespace::Ignore);
+
+        assert!(matcher.matches("ab \r\n c\r\n\n"));
+        assert!(!matcher.matches("a b c"));
+    }
+
+    #[test]
+    fn substring() {
+        let matcher =
+            OutputMatcher::substring("42").whitespace(Whitespace::Ignore);
+
+        assert!(matcher.matches("the answer is 4 2\n"));
+        assert!(!matcher.matches("the answer is 41"));
+    }
+
+    #[test]
+    fn regex_case_follows_the_matcher() {
+        let matcher = OutputMatcher::regex("^x = [0-9]+$").unwrap();
+        assert!(matcher.matches("y\nx = 12\n"));
+        assert!(!matcher.matches("X = 12"));
+
+        let matcher = matcher.ignore_case(true);
+        assert!(matcher.matches("X = 12"));
+        assert_eq!(matcher.expected(), "^x = [0-9]+$");
+
+        assert!(OutputMatcher::regex("(").is_err());
+    }
+
+    #[test]
+    fn diff() {
+        assert_eq!(
+            line_diff("a\nb\nc\nd", "a\nc\nx\nd"),
+            "--- expected\n+++ actual\n  \"a\"\n- \"b\"\n  \"c\"\n+ \"x\"\n  \"d\"\n"
+        );
+    }
+
+    #[test]
+    fn diff_shows_whitespace() {
+        assert_eq!(
+            line_diff("a\n", "a \n"),
+            "--- expected\n+++ actual\n- \"a\"\n+ \"a \"\n  \"\"\n"
+        );
+    }
+
+    #[test]
+    fn mismatch_has_a_diff() {
+        let err = OutputMatcher::exact("1\n2\n").check("1\n3\n").unwrap_err();
+
+        assert_eq!(err.expected, "1\n2\n");
+        assert_eq!(err.actual, "1\n3\n");
+        assert_eq!(
+            err.diff,
+            "--- expected\n+++ actual\n  \"1\"\n- \"2\"\n+ \"3\"\n  \"\"\n"
+        );
+    }
+
+    #[test]
+    fn large_diffs_are_not_tabulated() {
+        let exp = (0..3000).map(|i| format!("e{}\n", i)).collect::<String>();
+        let act = (0..3000).map(|i| format!("a{}\n", i)).collect::<String>();
+
+        let diff = line_diff(
+            &format!("same\n{}end", exp),
+            &format!("same\n{}end", act),
+        );
+        let lines = diff.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2 + 1 + 3000 + 3000 + 1);
+        assert_eq!(lines[2], "  \"same\"");
+        assert_eq!(lines[3], "- \"e0\"");
+        assert_eq!(lines[3 + 3000], "+ \"a0\"");
+        assert_eq!(lines.last(), Some(&"  \"end\""));
+    }
+}
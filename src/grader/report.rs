@@ -0,0 +1,504 @@
+//! The results of running a [`TestSuite`](super::TestSuite) and serializers
+//! for them.
+//!
+//! Besides the human readable [`Display`] impls (which mimic the output of the
+//! C++ framework), reports can be turned into JSON (for gradebook importers;
+//! this needs the `grader-json` feature) and JUnit XML (for CI systems).
+
+use std::fmt::{self, Display, Write};
+use std::time::Duration;
+
+#[cfg(feature = "grader-json")]
+use serde::{Serialize, Serializer};
+
+use crate::sim::SEED_ENV_VAR;
+
+/// The outcome of a single check within a test case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "grader-json", derive(Serialize))]
+pub struct Verification {
+    /// What was checked.
+    pub label: String,
+    /// Whether the check passed.
+    pub passed: bool,
+    /// The value that was expected, for comparisons.
+    pub expected: Option<String>,
+    /// The value that was actually found, for comparisons.
+    pub actual: Option<String>,
+}
+
+/// The results of a single test case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestReport {
+    /// The name of the test case.
+    pub name: String,
+    /// How many points the test case is worth.
+    pub points_possible: u32,
+    /// How many points were earned; like the C++ framework, this is the
+    /// fraction of verifications that passed times the points the test case is
+    /// worth.
+    pub points_earned: f64,
+    /// The seed the machine was randomized with, for randomized test cases.
+    pub seed: Option<u64>,
+    /// How long the test case took to run.
+    pub runtime: Duration,
+    /// How many instructions the machine executed during the test case.
+    pub instructions: u64,
+    /// What the program printed.
+    pub output: String,
+    /// The verifications that were made, in order.
+    pub verifications: Vec<Verification>,
+}
+
+impl TestReport {
+    pub(super) fn new(
+        name: String,
+        points_possible: u32,
+        seed: Option<u64>,
+        runtime: Duration,
+        instructions: u64,
+        output: String,
+        verifications: Vec<Verification>,
+    ) -> Self {
+        let passed = verifications.iter().filter(|v| v.passed).count();
+        let points_earned = if verifications.is_empty() {
+            0.0
+        } else {
+            f64::from(points_possible) * passed as f64
+                / verifications.len() as f64
+        };
+
+        Self {
+            name,
+            points_possible,
+            points_earned,
+            seed,
+            runtime,
+            instructions,
+            output,
+            verifications,
+        }
+    }
+
+    /// Whether every verification passed.
+    pub fn passed(&self) -> bool {
+        !self.verifications.is_empty()
+            && self.verifications.iter().all(|v| v.passed)
+    }
+
+    fn failures(&self) -> impl Iterator<Item = &Verification> {
+        self.verifications.iter().filter(|v| !v.passed)
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Test: {}", self.name)?;
+        if let Some(seed) = self.seed {
+            write!(f, " (randomized; {}={:#x})", SEED_ENV_VAR, seed)?;
+        }
+        writeln!(f)?;
+
+        for v in &self.verifications {
+            let status = if v.passed { "Pass" } else { "Fail" };
+            write!(f, "  {} => {}", v.label, status)?;
+
+            if let (false, Some(exp), Some(act)) =
+                (v.passed, &v.expected, &v.actual)
+            {
+                write!(f, " (expected {}, got {})", exp, act)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(
+            f,
+            "Test points earned: {:.2}/{} ({} instructions in {:?})",
+            self.points_earned,
+            self.points_possible,
+            self.instructions,
+            self.runtime
+        )
+    }
+}
+
+/// The results of running a [`TestSuite`](super::TestSuite).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradeReport {
+    /// The results of each test case, in the order they were run.
+    pub tests: Vec<TestReport>,
+}
+
+impl GradeReport {
+    /// The total number of points earned.
+    pub fn points_earned(&self) -> f64 {
+        self.tests.iter().map(|t| t.points_earned).sum()
+    }
+
+    /// The total number of points available.
+    pub fn points_possible(&self) -> u32 {
+        self.tests.iter().map(|t| t.points_possible).sum()
+    }
+
+    /// How long all the test cases took to run.
+    pub fn runtime(&self) -> Duration {
+        self.tests.iter().map(|t| t.runtime).sum()
+    }
+
+    /// Serializes the report as JSON.
+    ///
+    /// The output looks like this (seeds are strings since they don't
+    /// necessarily fit in a double):
+    /// ```json
+    /// {
+    ///   "points_earned": 7.5,
+    ///   "points_possible": 10,
+    ///   "tests": [
+    ///     {
+    ///       "name": "multiplies",
+    ///       "points_earned": 7.5,
+    ///       "points_possible": 10,
+    ///       "passed": false,
+    ///       "seed": "0x2a",
+    ///       "runtime_secs": 0.0012,
+    ///       "instructions": 1045,
+    ///       "output": "6 * 7 = 41\n",
+    ///       "verifications": [
+    ///         {
+    ///           "label": "R0",
+    ///           "passed": false,
+    ///           "expected": "42",
+    ///           "actual": "41"
+    ///         }
+    ///       ]
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    #[cfg(feature = "grader-json")]
+    #[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "grader-json")))]
+    pub fn to_json(&self) -> String {
+        let report = JsonReport {
+            points_earned: self.points_earned(),
+            points_possible: self.points_possible(),
+            tests: self.tests.iter().map(JsonTest::from).collect(),
+        };
+
+        serde_json::to_string_pretty(&report)
+            .expect("reports to serialize to JSON")
+    }
+
+    /// Serializes the report as JUnit XML; each test case becomes a
+    /// `<testcase>` in a `<testsuite>` with the given name.
+    ///
+    /// Test cases where any verification failed get a `<failure>` listing
+    /// the failed verifications. Points are reported as `<properties>` on the
+    /// test suite and in each test case's `<system-out>` (along with the
+    /// program's output).
+    pub fn to_junit_xml(&self, suite_name: &str) -> String {
+        let mut out = String::new();
+        let _ = self.write_junit_xml(&mut out, suite_name);
+        out
+    }
+
+    fn write_junit_xml(&self, out: &mut String, suite: &str) -> fmt::Result {
+        let failures = self.tests.iter().filter(|t| !t.passed()).count();
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<testsuite name="{}" tests="{}" failures="{}" errors="0" time="{}">"#,
+            Xml(suite),
+            self.tests.len(),
+            failures,
+            self.runtime().as_secs_f64(),
+        )?;
+        writeln!(out, "  <properties>")?;
+        writeln!(
+            out,
+            r#"    <property name="points_earned" value="{}"/>"#,
+            self.points_earned()
+        )?;
+        writeln!(
+            out,
+            r#"    <property name="points_possible" value="{}"/>"#,
+            self.points_possible()
+        )?;
+        writeln!(out, "  </properties>")?;
+
+        for t in &self.tests {
+            writeln!(
+                out,
+                r#"  <testcase name="{}" classname="{}" time="{}">"#,
+                Xml(&t.name),
+                Xml(suite),
+                t.runtime.as_secs_f64(),
+            )?;
+
+            if !t.passed() {
+                let failed = t.failures().count();
+                if t.verifications.is_empty() {
+                    writeln!(
+                        out,
+                        r#"    <failure message="no verifications were made">"#
+                    )?;
+                } else {
+                    writeln!(
+                        out,
+                        r#"    <failure message="{} of {} verification(s) failed">"#,
+                        failed,
+                        t.verifications.len(),
+                    )?;
+                }
+
+                for v in t.failures() {
+                    write!(out, "{}", Xml(&v.label))?;
+                    if let (Some(exp), Some(act)) = (&v.expected, &v.actual) {
+                        write!(
+                            out,
+                            ": expected {}, got {}",
+                            Xml(exp),
+                            Xml(act)
+                        )?;
+                    }
+                    writeln!(out)?;
+                }
+
+                writeln!(out, "    </failure>")?;
+            }
+
+            write!(
+                out,
+                "    <system-out>points: {}/{}; instructions: {}",
+                t.points_earned, t.points_possible, t.instructions,
+            )?;
+            if let Some(seed) = t.seed {
+                write!(out, "; {}={:#x}", SEED_ENV_VAR, seed)?;
+            }
+            if !t.output.is_empty() {
+                write!(out, "\n{}", Xml(&t.output))?;
+            }
+            writeln!(out, "</system-out>")?;
+
+            writeln!(out, "  </testcase>")?;
+        }
+
+        writeln!(out, "</testsuite>")
+    }
+}
+
+impl Display for GradeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for test in &self.tests {
+            writeln!(f, "==========")?;
+            write!(f, "{}", test)?;
+        }
+
+        writeln!(f, "==========")?;
+        writeln!(
+            f,
+            "Total points earned: {:.2}/{}",
+            self.points_earned(),
+            self.points_possible()
+        )
+    }
+}
+
+/// The shape of [`GradeReport::to_json`]'s output.
+#[cfg(feature = "grader-json")]
+#[derive(Serialize)]
+struct JsonReport<'r> {
+    points_earned: f64,
+    points_possible: u32,
+    tests: Vec<JsonTest<'r>>,
+}
+
+#[cfg(feature = "grader-json")]
+#[derive(Serialize)]
+struct JsonTest<'r> {
+    name: &'r str,
+    points_earned: f64,
+    points_possible: u32,
+    passed: bool,
+    #[serde(serialize_with = "hex_seed")]
+    seed: Option<u64>,
+    runtime_secs: f64,
+    instructions: u64,
+    output: &'r str,
+    verifications: &'r [Verification],
+}
+
+#[cfg(feature = "grader-json")]
+impl<'r> From<&'r TestReport> for JsonTest<'r> {
+    fn from(t: &'r TestReport) -> Self {
+        Self {
+            name: &t.name,
+            points_earned: t.points_earned,
+            points_possible: t.points_possible,
+            passed: t.passed(),
+            seed: t.seed,
+            runtime_secs: t.runtime.as_secs_f64(),
+            instructions: t.instructions,
+            output: &t.output,
+            verifications: &t.verifications,
+        }
+    }
+}
+
+/// Seeds are written as hex strings since they don't necessarily fit in a
+/// double.
+#[cfg(feature = "grader-json")]
+fn hex_seed<S: Serializer>(
+    seed: &Option<u64>,
+    ser: S,
+) -> Result<S::Ok, S::Error> {
+    match seed {
+        Some(s) => ser.collect_str(&format_args!("{:#x}", s)),
+        None => ser.serialize_none(),
+    }
+}
+
+/// Displays a string with the XML special characters escaped.
+struct Xml<'s>(&'s str);
+
+impl Display for Xml<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&apos;")?,
+                // Control characters other than whitespace aren't allowed in
+                // XML 1.0 at all.
+                c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => {
+                    write!(f, "\\x{:02x}", c as u32)?
+                }
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn report(output: &str, seed: Option<u64>) -> GradeReport {
+        let verifications = vec![
+            Verification {
+                label: "R0".into(),
+                passed: true,
+                expected: None,
+                actual: None,
+            },
+            Verification {
+                label: "output <\"a\" & 'b'>".into(),
+                passed: false,
+                expected: Some("]]>".into()),
+                actual: Some("\u{1}\t".into()),
+            },
+        ];
+
+        GradeReport {
+            tests: vec![TestReport::new(
+                "t".into(),
+                10,
+                seed,
+                Duration::from_millis(500),
+                42,
+                output.into(),
+                verifications,
+            )],
+        }
+    }
+
+    #[test]
+    fn xml_escapes_markup() {
+        assert_eq!(
+            Xml(r#"<a href="x">'&'</a>"#).to_string(),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn xml_escapes_cdata_end() {
+        assert_eq!(Xml("]]>").to_string(), "]]&gt;");
+    }
+
+    #[test]
+    fn xml_replaces_control_chars() {
+        assert_eq!(
+            Xml("a\u{0}b\u{1b}c\n\r\td").to_string(),
+            "a\\x00b\\x1bc\n\r\td"
+        );
+    }
+
+    #[test]
+    fn junit_xml_escapes_everything() {
+        let xml = report("]]>\u{7}", None).to_junit_xml("\"suite\"");
+
+        assert!(xml.contains(r#"<testsuite name="&quot;suite&quot;""#));
+        assert!(xml.contains(
+            "output &lt;&quot;a&quot; &amp; &apos;b&apos;&gt;: \
+            expected ]]&gt;, got \\x01\t\n"
+        ));
+        assert!(xml.contains("\n]]&gt;\\x07</system-out>"));
+        assert!(!xml.contains("]]>"));
+    }
+
+    #[cfg(feature = "grader-json")]
+    #[test]
+    fn json() {
+        let json: serde_json::Value = serde_json::from_str(
+            &report("6 * 7 = \"41\"\n", Some(42)).to_json(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "points_earned": 5.0,
+                "points_possible": 10,
+                "tests": [{
+                    "name": "t",
+                    "points_earned": 5.0,
+                    "points_possible": 10,
+                    "passed": false,
+                    "seed": "0x2a",
+                    "runtime_secs": 0.5,
+                    "instructions": 42,
+                    "output": "6 * 7 = \"41\"\n",
+                    "verifications": [
+                        {
+                            "label": "R0",
+                            "passed": true,
+                            "expected": null,
+                            "actual": null
+                        },
+                        {
+                            "label": "output <\"a\" & 'b'>",
+                            "passed": false,
+                            "expected": "]]>",
+                            "actual": "\u{1}\t"
+                        }
+                    ]
+                }]
+            })
+        );
+    }
+
+    #[cfg(feature = "grader-json")]
+    #[test]
+    fn json_without_seed() {
+        let json: serde_json::Value =
+            serde_json::from_str(&report("", None).to_json()).unwrap();
+
+        assert_eq!(json["tests"][0]["seed"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,475 @@
+//! Grading many submissions against the same test cases in parallel.
+
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use super::{run_test, GradeReport, Grader, DEFAULT_INSTRUCTION_LIMIT};
+use crate::asm::{AsmError, Assembler, Program};
+use crate::sim::{Simulator, SimulatorBuilder};
+
+/// How many threads a [`ParallelRunner`] uses unless told otherwise.
+const DEFAULT_THREADS: usize = 4;
+
+#[derive(Debug, Clone)]
+enum Source {
+    Program(Program),
+    Asm(PathBuf),
+    Obj(PathBuf),
+}
+
+/// A program to grade.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// What to call the submission in reports.
+    pub name: String,
+    source: Source,
+    instruction_limit: u64,
+}
+
+impl Submission {
+    /// A submission that's already been assembled.
+    pub fn new(name: impl Into<String>, program: Program) -> Self {
+        Self {
+            name: name.into(),
+            source: Source::Program(program),
+            instruction_limit: 0,
+        }
+    }
+
+    /// A submission in a file; files ending in `.obj` are read as
+    /// `LC3Tools` object files and everything else is assembled.
+    ///
+    /// The submission is named after the file (without its extension).
+    pub fn from_file(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .unwrap_or_else(|| path.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+
+        let source = if path.extension() == Some(OsStr::new("obj")) {
+            Source::Obj(path.to_path_buf())
+        } else {
+            Source::Asm(path.to_path_buf())
+        };
+
+        Self {
+            name,
+            source,
+            instruction_limit: 0,
+        }
+    }
+
+    /// Runs this submission's test cases with a different instruction limit
+    /// than the [`ParallelRunner`]'s (i.e. for a submission that's known to be
+    /// slow); 0 means the runner's limit.
+    pub fn instruction_limit(mut self, limit: u64) -> Self {
+        self.instruction_limit = limit;
+        self
+    }
+
+    fn load(self, assembler: &Assembler) -> Result<Program, AsmError> {
+        match self.source {
+            Source::Program(program) => Ok(program),
+            Source::Asm(path) => assembler.assemble_file(path),
+            Source::Obj(path) => Program::from_obj_file(path),
+        }
+    }
+}
+
+/// The results of grading a [`Submission`].
+#[derive(Debug)]
+pub struct SubmissionReport {
+    /// The name of the submission.
+    pub name: String,
+    /// The grade report or, if the submission couldn't be assembled or read,
+    /// why.
+    pub result: Result<GradeReport, AsmError>,
+}
+
+type SharedTestFunc = Box<dyn Fn(&mut Simulator, &mut Grader) + Send + Sync>;
+
+struct SharedTestCase {
+    name: String,
+    points: u32,
+    randomize: bool,
+    func: SharedTestFunc,
+}
+
+/// Runs a set of test cases against many submissions using a pool of
+/// threads.
+///
+/// Every (submission, test case) pair is a separate job that runs on its own
+/// freshly made [`Simulator`], so jobs don't share any `LC3Tools` state. Runs
+/// are always limited (to [`DEFAULT_INSTRUCTION_LIMIT`] instructions unless
+/// the [`SimulatorBuilder`] or the [`Submission`] sets a limit) so that
+/// submissions that never halt can't tie up a thread. Submissions are
+/// assembled (each with its own assembler instance) on the same pool before
+/// their test cases run.
+///
+/// Test cases have to be `Send + Sync + 'static` since they're shared between
+/// the threads; unlike with [`TestSuite`](super::TestSuite) they can't hold
+/// on to mutable state.
+pub struct ParallelRunner {
+    simulator: SimulatorBuilder,
+    assembler: Assembler,
+    threads: usize,
+    tests: Vec<Arc<SharedTestCase>>,
+}
+
+impl fmt::Debug for ParallelRunner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParallelRunner")
+            .field("simulator", &self.simulator)
+            .field("assembler", &self.assembler)
+            .field("threads", &self.threads)
+            .field(
+                "tests",
+                &self.tests.iter().map(|t| &t.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Default for ParallelRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParallelRunner {
+    /// A runner with no test cases that uses 4 threads, the default
+    /// [`Assembler`] and the default [`SimulatorBuilder`] (with an instruction
+    /// limit of [`DEFAULT_INSTRUCTION_LIMIT`]).
+    pub fn new() -> Self {
+        Self {
+            simulator: SimulatorBuilder::new()
+                .instruction_limit(DEFAULT_INSTRUCTION_LIMIT),
+            assembler: Assembler::new(),
+            threads: DEFAULT_THREADS,
+            tests: Vec::new(),
+        }
+    }
+
+    /// Sets how many threads to use (at least 1).
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets how the simulator each job runs on is made (i.e. its OS and
+    /// instruction limit); a builder without an instruction limit gets
+    /// [`DEFAULT_INSTRUCTION_LIMIT`].
+    pub fn simulator(mut self, builder: SimulatorBuilder) -> Self {
+        self.simulator = match builder.instruction_limit {
+            0 => builder.instruction_limit(DEFAULT_INSTRUCTION_LIMIT),
+            _ => builder,
+        };
+        self
+    }
+
+    /// Sets the assembler used for submissions that are assembly files.
+    pub fn assembler(mut self, assembler: Assembler) -> Self {
+        self.assembler = assembler;
+        self
+    }
+
+    /// Adds a test case; see [`TestSuite::test`](super::TestSuite::test).
+    pub fn test<F>(self, name: impl Into<String>, points: u32, func: F) -> Self
+    where
+        F: Fn(&mut Simulator, &mut Grader) + Send + Sync + 'static,
+    {
+        self.add(name.into(), points, false, Box::new(func))
+    }
+
+    /// Adds a test case that runs on a randomized machine; see
+    /// [`TestSuite::randomized_test`](super::TestSuite::randomized_test).
+    pub fn randomized_test<F>(
+        self,
+        name: impl Into<String>,
+        points: u32,
+        func: F,
+    ) -> Self
+    where
+        F: Fn(&mut Simulator, &mut Grader) + Send + Sync + 'static,
+    {
+        self.add(name.into(), points, true, Box::new(func))
+    }
+
+    fn add(
+        mut self,
+        name: String,
+        points: u32,
+        randomize: bool,
+        func: SharedTestFunc,
+    ) -> Self {
+        self.tests.push(Arc::new(SharedTestCase {
+            name,
+            points,
+            randomize,
+            func,
+        }));
+
+        self
+    }
+
+    /// Grades the given submissions; reports are in the same order as the
+    /// submissions.
+    pub fn run<S>(&self, submissions: S) -> Vec<SubmissionReport>
+    where
+        S: IntoIterator<Item = Submission>,
+    {
+        let submissions = submissions.into_iter().collect::<Vec<_>>();
+        let (names, limits): (Vec<_>, Vec<_>) = submissions
+            .iter()
+            .map(|s| (s.name.clone(), s.instruction_limit))
+            .unzip();
+
+        let assembler = self.assembler;
+        let programs = parallel_map(self.threads, submissions, move |s| {
+            s.load(&assembler).map(Arc::new)
+        });
+
+        let jobs = programs
+            .iter()
+            .zip(&limits)
+            .filter_map(|(p, l)| p.as_ref().ok().map(|p| (p, *l)))
+            .flat_map(|(program, limit)| {
+                let builder = Arc::new(match limit {
+                    0 => self.simulator.clone(),
+                    l => self.simulator.clone().instruction_limit(l),
+                });
+
+                self.tests
+                    .iter()
+                    .map(move |t| (builder.clone(), program.clone(), t.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut reports = parallel_map(self.threads, jobs, |(b, p, t)| {
+            run_test(&b, &p, &t.name, t.points, t.randomize, |s, g| {
+                (t.func)(s, g)
+            })
+        })
+        .into_iter();
+
+        names
+            .into_iter()
+            .zip(programs)
+            .map(|(name, program)| SubmissionReport {
+                name,
+                result: program.map(|_| GradeReport {
+                    tests: reports.by_ref().take(self.tests.len()).collect(),
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Applies `func` to every item on a pool of `threads` threads; the results
+/// are in the same order as the items.
+fn parallel_map<T, R, F>(threads: usize, items: Vec<T>, func: F) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    let len = items.len();
+    let queue = Arc::new(Mutex::new(
+        items.into_iter().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let func = Arc::new(func);
+    let (tx, rx) = mpsc::channel();
+
+    let workers = (0..threads.min(len))
+        .map(|_| {
+            let (queue, func, tx) = (queue.clone(), func.clone(), tx.clone());
+            thread::spawn(move || loop {
+                let next = queue.lock().unwrap().pop_front();
+                match next {
+                    Some((idx, item)) => {
+                        let _ = tx.send((idx, func(item)));
+                    }
+                    None => break,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
+
+    let mut results = rx.iter().collect::<Vec<_>>();
+    for worker in workers {
+        worker.join().expect("grading threads not to panic");
+    }
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, res)| res).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::time::Duration;
+
+    use crate::asm::{assemble, TempDir};
+
+    /// A program that counts R1 down from `n` and halts.
+    fn countdown(n: u16) -> Program {
+        assemble(&format!(
+            ".ORIG x3000
+            LD R1, N
+    LOOP    ADD R1, R1, #-1
+            BRp LOOP
+            HALT
+    N       .FILL #{}
+            .END",
+            n
+        ))
+        .unwrap()
+    }
+
+    fn halts(sim: &mut Simulator, grader: &mut Grader) {
+        let res = sim.run(0x3000);
+        let _ = grader.verify("halts", res.is_ok());
+    }
+
+    #[test]
+    fn parallel_map_keeps_order() {
+        let slow_evens = |i: u64| {
+            if i % 2 == 0 {
+                thread::sleep(Duration::from_millis(i % 7));
+            }
+            i * 2
+        };
+
+        let items = (0..50).collect::<Vec<_>>();
+        let expected = items.iter().map(|i| i * 2).collect::<Vec<_>>();
+        for &threads in &[1, 3, 64] {
+            assert_eq!(
+                parallel_map(threads, items.clone(), slow_evens),
+                expected
+            );
+        }
+
+        assert_eq!(parallel_map(4, vec![], slow_evens), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn reports_are_in_submission_order() {
+        let runner = ParallelRunner::new()
+            .threads(3)
+            .test("halts", 1, halts)
+            .test("R1 is 0", 1, |sim, grader| {
+                let res = sim.run(0x3000);
+                let r1 = res.map(|s| s.regs[1]).unwrap_or(u32::max_value());
+                let _ = grader.verify_eq("R1", 0, r1);
+            });
+
+        let mut submissions = (1..=6)
+            .map(|n| Submission::new(format!("s{}", n), countdown(n)))
+            .collect::<Vec<_>>();
+        submissions.insert(2, Submission::from_file("missing.obj"));
+
+        let reports = runner.run(submissions);
+        let names = reports.iter().map(|r| &*r.name).collect::<Vec<_>>();
+        assert_eq!(names, ["s1", "s2", "missing", "s3", "s4", "s5", "s6"]);
+
+        for report in &reports {
+            match &report.result {
+                Ok(grade) => {
+                    let tests = grade.tests.iter().map(|t| &*t.name);
+                    assert_eq!(tests.collect::<Vec<_>>(), ["halts", "R1 is 0"]);
+                    assert_eq!(grade.points_earned(), 2.0);
+                }
+                Err(err) => {
+                    assert_eq!(report.name, "missing");
+                    assert!(matches!(err, AsmError::InvalidObjectFile));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn panics_only_fail_their_test() {
+        let runner = ParallelRunner::new()
+            .test("panics on 2", 1, |sim, grader| {
+                halts(sim, grader);
+                assert_ne!(sim.mem(0x3004), 2, "it's 2");
+            })
+            .test("halts", 1, halts);
+
+        let reports = runner.run(vec![
+            Submission::new("1", countdown(1)),
+            Submission::new("2", countdown(2)),
+        ]);
+        let grades = reports
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(grades[0].points_earned(), 2.0);
+        assert_eq!(grades[1].points_earned(), 1.5);
+
+        let failed = &grades[1].tests[0].verifications[1];
+        assert!(!failed.passed);
+        assert!(
+            failed.label.starts_with("test panicked"),
+            "{}",
+            failed.label
+        );
+        assert!(failed.label.contains("it's 2"), "{}", failed.label);
+    }
+
+    #[test]
+    fn instruction_limits() {
+        // Counting down from 1000 takes more than 2000 instructions.
+        let runner = ParallelRunner::new()
+            .simulator(SimulatorBuilder::new().instruction_limit(1000))
+            .test("halts", 1, halts);
+
+        let reports = runner.run(vec![
+            Submission::new("limited", countdown(1000)),
+            Submission::new("raised", countdown(1000)).instruction_limit(5000),
+            Submission::new("runner's", countdown(1000)).instruction_limit(0),
+        ]);
+        let passed = reports
+            .iter()
+            .map(|r| r.result.as_ref().unwrap().tests[0].passed())
+            .collect::<Vec<_>>();
+        assert_eq!(passed, [false, true, false]);
+
+        // Builders without a limit get the default one.
+        let spin = assemble(".ORIG x3000\nBR #-1\n.END\n").unwrap();
+        let reports = ParallelRunner::new()
+            .simulator(SimulatorBuilder::new())
+            .test("halts", 1, halts)
+            .run(vec![Submission::new("spins", spin)]);
+        let grade = reports[0].result.as_ref().unwrap();
+        assert!(!grade.tests[0].passed());
+    }
+
+    #[test]
+    fn submissions_from_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("student.asm");
+        fs::write(&path, ".ORIG x3000\nHALT\n.END\n").unwrap();
+
+        let submission = Submission::from_file(&path);
+        assert_eq!(submission.name, "student");
+
+        let reports = ParallelRunner::new()
+            .test("halts", 1, halts)
+            .run(vec![submission]);
+        assert!(reports[0].result.as_ref().unwrap().tests[0].passed());
+
+        // Submissions are assembled without writing an object file.
+        assert!(!dir.path().join("student.obj").exists());
+    }
+}
@@ -65,3 +65,92 @@
 #![warn(unknown_lints)]
 
 include!("../generated/bindings.rs");
+
+pub mod asm;
+#[cfg(feature = "cxx-bridge")]
+#[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "cxx-bridge")))]
+pub mod bridge;
+pub mod enums;
+#[cfg(feature = "gdb")]
+#[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "gdb")))]
+pub mod gdb;
+#[cfg(feature = "grader")]
+#[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "grader")))]
+pub mod grader;
+pub mod sim;
+
+#[cfg(test)]
+mod tests {
+    /// The functions and callback types declared in the `extern "C"` block of
+    /// one of our headers.
+    fn declared(header: &str) -> Vec<String> {
+        let src = header
+            .lines()
+            .map(|l| l.split("//").next().unwrap())
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let block = src.find("extern \"C\" {").unwrap() + 12;
+        let block = &src[block..src.rfind('}').unwrap()];
+
+        let name = |decl: &str| {
+            let decl = decl.trim();
+            let name = if decl.contains('{') {
+                return None;
+            } else if decl.starts_with("typedef") {
+                let ptr = decl.find("(*")? + 2;
+                &decl[ptr..ptr + decl[ptr..].find(')')?]
+            } else {
+                decl[..decl.find('(')?]
+                    .rsplit(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .next()?
+            };
+
+            Some(name.trim().to_string())
+        };
+
+        let (mut names, mut depth, mut start) = (Vec::new(), 0, 0);
+        for (i, c) in block.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ';' if depth == 0 => {
+                    names.extend(name(&block[start..i]));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+
+        names
+    }
+
+    /// The bundled bindings are only regenerated with `generate-fresh` (which
+    /// needs `libclang` and the `LC3Tools` sources); make sure they haven't
+    /// fallen behind the C bindings.
+    #[test]
+    fn bindings_match_headers() {
+        let bindings = include_str!("../generated/bindings.rs");
+        let headers = [
+            include_str!("../extra/bindings.h"),
+            include_str!("../extra/frontend/frontend_bindings.h"),
+        ];
+
+        let names =
+            headers.iter().flat_map(|h| declared(h)).collect::<Vec<_>>();
+        assert!(names.iter().any(|n| n == "ProgramWordCallback"));
+        assert!(names.iter().any(|n| n == "console_printer"));
+
+        for name in names {
+            let decl = format!("pub fn {}(", name);
+            let callback = format!("pub type {} =", name);
+
+            assert!(
+                bindings.contains(&decl) || bindings.contains(&callback),
+                "`{}` is missing from `generated/bindings.rs`",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,1078 @@
+//! A safe wrapper around the [C bindings](crate::root::new_sim) for the
+//! simulator.
+//!
+//! This only exposes what the C bindings do; for anything else you'll have to
+//! reach for the raw [`sim`](crate::root::lc3::sim) (with the caveats
+//! mentioned in the README).
+
+use std::borrow::Cow;
+use std::collections::{hash_map::RandomState, BTreeSet, HashMap};
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Range;
+use std::os::raw::{c_char, c_uchar, c_void};
+use std::path::Path;
+use std::ptr::NonNull;
+use std::time::SystemTime;
+
+use crate::asm::{self, AsmError, Program};
+use crate::enums::PrintType;
+use crate::root::{self, lc3, State};
+
+/// System space: the trap vector table, the interrupt vector table and the
+/// OS itself.
+const SYSTEM_SPACE: Range<u16> = 0x0000..0x3000;
+
+/// The instruction limit a machine with no OS ([`OsImage::Bare`]) runs with
+/// when it isn't given one: without an OS, `HALT` doesn't stop the machine so
+/// a run with no limit would never return.
+pub const BARE_INSTRUCTION_LIMIT: u64 = 10_000_000;
+
+/// The environment variable [`pick_seed`] checks for a seed to use.
+pub const SEED_ENV_VAR: &str = "LC3TOOLS_SEED";
+
+/// Picks a seed to [randomize](Simulator::randomize) a machine with.
+///
+/// If [`LC3TOOLS_SEED`](SEED_ENV_VAR) is set (in decimal or with a `0x`
+/// prefix, in hex) we use it; this is how you replay a failing randomized
+/// run. Otherwise we grab a fresh seed.
+pub fn pick_seed() -> u64 {
+    if let Ok(seed) = env::var(SEED_ENV_VAR) {
+        let seed = seed.trim();
+        let parsed = if seed.starts_with("0x") {
+            u64::from_str_radix(&seed[2..], 16)
+        } else {
+            seed.parse()
+        };
+
+        match parsed {
+            Ok(s) => return s,
+            Err(e) => panic!("Invalid `{}` (`{}`): {}", SEED_ENV_VAR, seed, e),
+        }
+    }
+
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(d) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(d.as_nanos());
+    }
+
+    hasher.finish()
+}
+
+/// The error produced when a program doesn't halt cleanly.
+#[derive(Debug, Clone, Copy)]
+pub struct RunError {
+    /// The state of the machine when it stopped.
+    pub state: State,
+    /// Whether the run was stopped because it hit the
+    /// [instruction limit](Simulator::set_instruction_limit).
+    pub exceeded_instruction_limit: bool,
+    /// The seed the machine was randomized with, if it was randomized.
+    pub seed: Option<u64>,
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the program did not halt cleanly (PC = {:#06x})",
+            self.state.pc
+        )?;
+
+        if self.exceeded_instruction_limit {
+            write!(f, "; the instruction limit was exceeded")?;
+        }
+
+        if let Some(seed) = self.seed {
+            write!(
+                f,
+                "; the machine was randomized with seed {:#x} \
+                (set `{}={:#x}` to replay this run)",
+                seed, SEED_ENV_VAR, seed
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for RunError {}
+
+/// The error produced when [loading](Simulator::load_overlay) a program
+/// overwrites words that were previously loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlapError {
+    /// The addresses that were overwritten, in ascending order.
+    pub addrs: Vec<u16>,
+}
+
+impl Display for OverlapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the program overwrote {} loaded word(s) at:",
+            self.addrs.len()
+        )?;
+
+        for addr in &self.addrs {
+            write!(f, " {:#06x}", addr)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for OverlapError {}
+
+/// The operating system a [`Simulator`] runs with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OsImage {
+    /// The OS that ships with `LC3Tools` (`lc3::core::getOSSrc`).
+    Stock,
+    /// A custom OS. System space (`x0000` to `x2FFF`) is cleared before this
+    /// is loaded so anything the image doesn't fill in will be zero.
+    Custom(Program),
+    /// No OS at all: system space, including the trap and interrupt vector
+    /// tables, is zeroed.
+    ///
+    /// Note that `HALT` won't actually stop the machine without an OS, so
+    /// runs are always limited: with no
+    /// [instruction limit](Simulator::set_instruction_limit),
+    /// [`BARE_INSTRUCTION_LIMIT`] is used.
+    Bare,
+}
+
+impl Default for OsImage {
+    fn default() -> Self {
+        OsImage::Stock
+    }
+}
+
+impl OsImage {
+    /// Assembles a custom OS.
+    pub fn from_source(source: &str) -> Result<Self, AsmError> {
+        asm::assemble(source).map(OsImage::Custom)
+    }
+
+    /// Loads a custom OS from an `LC3Tools` object file.
+    pub fn from_obj_file(path: impl AsRef<Path>) -> Result<Self, AsmError> {
+        Program::from_obj_file(path).map(OsImage::Custom)
+    }
+}
+
+/// Configures and makes a [`Simulator`].
+#[derive(Debug, Clone)]
+pub struct SimulatorBuilder {
+    print_level: PrintType,
+    os: OsImage,
+    seed: Option<u64>,
+    input: Vec<(u64, Vec<u8>)>,
+    pub(crate) instruction_limit: u64,
+    #[cfg(feature = "frontend")]
+    console_output: bool,
+    #[cfg(feature = "frontend")]
+    console_input: bool,
+}
+
+impl Default for SimulatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatorBuilder {
+    /// A builder for a simulator that only reports fatal errors, runs the
+    /// stock OS and isn't randomized.
+    pub fn new() -> Self {
+        Self {
+            print_level: PrintType::FatalError,
+            os: OsImage::Stock,
+            seed: None,
+            input: Vec::new(),
+            instruction_limit: 0,
+            #[cfg(feature = "frontend")]
+            console_output: false,
+            #[cfg(feature = "frontend")]
+            console_input: false,
+        }
+    }
+
+    /// Sets what the simulator reports.
+    ///
+    /// `LC3Tools` drops the program's output below
+    /// [`PrintType::FatalError`], so [`Simulator::output`] stays empty at
+    /// [`PrintType::None`] and [`PrintType::SimOutput`]. Whatever else the
+    /// simulator reports is captured along with the output.
+    pub fn print_level(mut self, print_level: PrintType) -> Self {
+        self.print_level = print_level;
+        self
+    }
+
+    /// Sets the OS the simulator runs with.
+    pub fn os(mut self, os: OsImage) -> Self {
+        self.os = os;
+        self
+    }
+
+    /// Randomizes the machine with the given seed; see
+    /// [`Simulator::randomize`].
+    pub fn randomize(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Schedules input; see [`Simulator::schedule_input`].
+    ///
+    /// Unlike input scheduled on a [`Simulator`], this input is scheduled
+    /// again every time the machine is [reset](Simulator::reset).
+    pub fn input_after(mut self, after: u64, bytes: impl AsRef<[u8]>) -> Self {
+        self.input.push((after, bytes.as_ref().to_vec()));
+        self
+    }
+
+    /// Limits how many instructions a run may execute; see
+    /// [`Simulator::set_instruction_limit`].
+    pub fn instruction_limit(mut self, limit: u64) -> Self {
+        self.instruction_limit = limit;
+        self
+    }
+
+    /// Sends output to the console (`lc3::ConsolePrinter`) instead of
+    /// [capturing](Simulator::output) it.
+    #[cfg(feature = "frontend")]
+    #[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "frontend")))]
+    pub fn console_output(mut self, console_output: bool) -> Self {
+        self.console_output = console_output;
+        self
+    }
+
+    /// Reads input from the console (`lc3::ConsoleInputter`) instead of a
+    /// [schedule](Simulator::schedule_input); scheduled input is ignored.
+    #[cfg(feature = "frontend")]
+    #[cfg_attr(all(docs, not(doctest)), doc(cfg(feature = "frontend")))]
+    pub fn console_input(mut self, console_input: bool) -> Self {
+        self.console_input = console_input;
+        self
+    }
+
+    fn printer(&self, io: *mut Io) -> *mut lc3::utils::IPrinter {
+        #[cfg(feature = "frontend")]
+        {
+            if self.console_output {
+                return unsafe { root::console_printer() };
+            }
+        }
+
+        unsafe { root::context_printer(io as *mut c_void, Some(push_output)) }
+    }
+
+    fn inputter(&self) -> *mut lc3::utils::IInputter {
+        #[cfg(feature = "frontend")]
+        {
+            if self.console_input {
+                return unsafe { root::console_inputter() };
+            }
+        }
+
+        unsafe { root::scheduled_inputter() }
+    }
+
+    /// Makes the simulator.
+    pub fn build(self) -> Simulator {
+        let io = Box::into_raw(Box::new(Io::default()));
+        let (printer, inputter) = (self.printer(io), self.inputter());
+        let sim = unsafe {
+            let sim = root::new_sim(printer, inputter, self.print_level.into());
+            root::scheduled_inputter_attach(inputter, sim);
+            sim
+        };
+
+        let mut sim = Simulator {
+            sim: NonNull::new(sim).expect("simulator allocation to succeed"),
+            io: NonNull::new(io).unwrap(),
+            printer,
+            inputter,
+            os: self.os,
+            seed: self.seed,
+            input: self.input,
+            instruction_limit: self.instruction_limit,
+            loaded: HashMap::new(),
+            breakpoints: BTreeSet::new(),
+        };
+
+        sim.reset();
+        sim
+    }
+}
+
+/// The state a [`Simulator`]'s `Printer` (and `Inputter`) hold on to.
+#[derive(Debug, Default)]
+struct Io {
+    output: Vec<u8>,
+}
+
+unsafe extern "C" fn push_output(ctx: *mut c_void, c: c_uchar) {
+    let io = &mut *(ctx as *mut Io);
+    io.output.push(c);
+}
+
+/// An LC-3 simulator.
+///
+/// Output is [captured](Simulator::output) (along with anything the simulator
+/// itself reports at its print level) and input comes from a
+/// [schedule](Simulator::schedule_input).
+///
+/// ## Thread Safety
+///
+/// Simulators are [`Send`] but not [`Sync`]: each one owns its `lc3::sim`
+/// and the `Printer` and `Inputter` it uses, and separate `lc3::sim`s don't
+/// share any mutable state, so a simulator can be moved to (or made on)
+/// another thread. A single simulator must not be used from several threads
+/// at once.
+///
+/// What this rests on:
+///   - The `LC3Tools` headers declare no global or static member variables
+///     in the `lc3` namespace (there are no `static`s in
+///     [`lc3`](crate::root::lc3)); an `lc3::sim`'s machine state,
+///     memory, instruction handlers and logger all live in the instance.
+///   - The only globals `LC3Tools` does declare are the grader's
+///     (`tests`, `verify_count` and `verify_valid` in `framework.h`),
+///     which only `framework.cpp`'s functions touch; simulators don't call
+///     them.
+///   - Our C bindings and shims (`extra/`) keep no static state (the
+///     generator [`randomize`](Simulator::randomize) uses is a local).
+///   - The console printer and inputter (`frontend` feature) do share the
+///     process's terminal: simulators using them on different threads will
+///     interleave their output and race each other for input.
+///
+/// Statics local to functions in `LC3Tools`' sources don't show up in its
+/// headers; the `simulators_on_many_threads` test runs simulators (and the
+/// assembler) on many threads at once and checks that they behave just like
+/// they do on one.
+#[derive(Debug)]
+pub struct Simulator {
+    sim: NonNull<lc3::sim>,
+    /// Owned by this struct; the `Printer` holds a pointer to it so we don't
+    /// keep it in a `Box`.
+    io: NonNull<Io>,
+    printer: *mut lc3::utils::IPrinter,
+    inputter: *mut lc3::utils::IInputter,
+    os: OsImage,
+    seed: Option<u64>,
+    /// Input that's scheduled on every reset.
+    input: Vec<(u64, Vec<u8>)>,
+    instruction_limit: u64,
+    /// The words that were loaded since the last reset, by address.
+    loaded: HashMap<u16, u16>,
+    breakpoints: BTreeSet<u16>,
+}
+
+// See the "Thread Safety" section above.
+unsafe impl Send for Simulator {}
+
+impl Simulator {
+    /// Makes a new simulator with the stock OS loaded.
+    ///
+    /// Use [`Simulator::builder`] for more options.
+    pub fn new(print_level: PrintType) -> Self {
+        SimulatorBuilder::new().print_level(print_level).build()
+    }
+
+    /// Shorthand for [`SimulatorBuilder::new`].
+    pub fn builder() -> SimulatorBuilder {
+        SimulatorBuilder::new()
+    }
+
+    fn ptr(&self) -> *mut lc3::sim {
+        self.sim.as_ptr()
+    }
+
+    fn io(&self) -> &Io {
+        unsafe { self.io.as_ref() }
+    }
+
+    fn io_mut(&mut self) -> &mut Io {
+        unsafe { self.io.as_mut() }
+    }
+
+    /// Randomizes user memory (`x3000` to `xFDFF`) and the general purpose
+    /// registers using the given seed.
+    ///
+    /// Words that were [loaded](Simulator::load_overlay) since the last
+    /// [reset](Simulator::reset) are loaded again afterwards so a program
+    /// survives being randomized. The seed is remembered:
+    /// [`reset`](Simulator::reset) (and
+    /// [`load_program`](Simulator::load_program)) re-randomizes the machine
+    /// with it so that the order in which you call these doesn't matter. It's
+    /// also reported in [`RunError`]s.
+    ///
+    /// See [`pick_seed`] for a way to get a seed.
+    pub fn randomize(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        unsafe { root::randomize_sim(self.ptr(), seed) };
+
+        for (&addr, &word) in &self.loaded {
+            unsafe { root::set_mem(self.ptr(), addr, word) };
+        }
+    }
+
+    /// The seed this machine was last [randomized](Simulator::randomize) with.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Resets the machine: memory, [output](Simulator::output) and
+    /// [scheduled input](Simulator::schedule_input) are cleared, the
+    /// [OS](OsImage) is loaded and, if this machine was
+    /// [randomized](Simulator::randomize), it's randomized again with the same
+    /// seed.
+    ///
+    /// Input given to [`SimulatorBuilder::input_after`] is scheduled again.
+    pub fn reset(&mut self) {
+        let sim = self.ptr();
+        self.loaded.clear();
+        self.clear_output();
+
+        unsafe {
+            root::set_run_inst_limit(sim, self.effective_instruction_limit());
+            root::scheduled_inputter_clear(self.inputter);
+        }
+        for (after, bytes) in &self.input {
+            unsafe {
+                root::scheduled_inputter_add(
+                    self.inputter,
+                    *after,
+                    bytes.len() as root::size_t,
+                    bytes.as_ptr(),
+                )
+            };
+        }
+
+        // This also loads the stock OS.
+        unsafe { root::reinitialize_sim(sim) };
+
+        match &self.os {
+            OsImage::Stock => {}
+            OsImage::Custom(_) | OsImage::Bare => {
+                for addr in SYSTEM_SPACE {
+                    unsafe { root::set_mem(sim, addr, 0) };
+                }
+            }
+        }
+
+        if let OsImage::Custom(image) = &self.os {
+            for (addr, word) in image.iter() {
+                unsafe { root::set_mem(sim, addr, word) };
+            }
+        }
+
+        if let Some(seed) = self.seed {
+            unsafe { root::randomize_sim(sim, seed) };
+        }
+    }
+
+    /// Restarts the machine without clearing memory (`sim::restart`).
+    pub fn restart(&mut self) {
+        unsafe { root::restart_sim(self.ptr()) };
+    }
+
+    /// [Resets](Simulator::reset) the machine and loads the given
+    /// `(address, word)` pairs into memory.
+    pub fn load_program<P>(&mut self, program: P)
+    where
+        P: IntoIterator<Item = (u16, u16)>,
+    {
+        self.reset();
+
+        // Nothing else is loaded, so the only overlaps can come from the
+        // program itself.
+        let _ = self.load_overlay(program);
+    }
+
+    /// Loads the given `(address, word)` pairs into memory *without*
+    /// resetting the machine first.
+    ///
+    /// This is useful for loading several images into one machine (i.e. a
+    /// subroutine and a test driver for it). The program is loaded either
+    /// way; if it overwrote any words that were loaded since the last
+    /// [reset](Simulator::reset) the addresses of those words are returned as
+    /// an error. Words written with [`set_mem`](Simulator::set_mem) and the
+    /// OS don't count.
+    pub fn load_overlay<P>(&mut self, program: P) -> Result<(), OverlapError>
+    where
+        P: IntoIterator<Item = (u16, u16)>,
+    {
+        let (addrs, words): (Vec<u16>, Vec<u16>) = program.into_iter().unzip();
+
+        let mut overwritten = addrs
+            .iter()
+            .zip(&words)
+            .filter(|(a, w)| self.loaded.insert(**a, **w).is_some())
+            .map(|(a, _)| *a)
+            .collect::<Vec<_>>();
+
+        for (addrs, words) in addrs
+            .chunks(u16::max_value() as usize)
+            .zip(words.chunks(u16::max_value() as usize))
+        {
+            unsafe {
+                root::load_program_overlay(
+                    self.ptr(),
+                    addrs.len() as u16,
+                    addrs.as_ptr(),
+                    words.as_ptr(),
+                )
+            };
+        }
+
+        if overwritten.is_empty() {
+            Ok(())
+        } else {
+            overwritten.sort_unstable();
+            overwritten.dedup();
+
+            Err(OverlapError { addrs: overwritten })
+        }
+    }
+
+    /// Gets the value of a memory address.
+    pub fn mem(&self, addr: u16) -> u16 {
+        unsafe { root::get_mem(self.ptr(), addr) }
+    }
+
+    /// The source line the word at an address came from.
+    ///
+    /// The machine only knows this for words it loaded from an object file
+    /// (i.e. the OS); for programs loaded with
+    /// [`load_program`](Simulator::load_program) use the program's
+    /// [`SourceMap`](crate::asm::SourceMap) and [`Word::line`] instead.
+    ///
+    /// [`Word::line`]: crate::asm::Word::line
+    pub fn mem_line(&self, addr: u16) -> String {
+        let mut buffer = vec![0u8; 128];
+        loop {
+            let len = unsafe {
+                root::get_mem_line(
+                    self.ptr(),
+                    addr,
+                    buffer.len() as root::size_t,
+                    buffer.as_mut_ptr() as *mut c_char,
+                )
+            } as usize;
+
+            if len < buffer.len() {
+                buffer.truncate(len);
+                return String::from_utf8_lossy(&buffer).into_owned();
+            }
+
+            buffer.resize(len + 1, 0);
+        }
+    }
+
+    /// Sets the value of a memory address.
+    pub fn set_mem(&mut self, addr: u16, value: u16) {
+        unsafe { root::set_mem(self.ptr(), addr, value) };
+    }
+
+    /// Sets the value of a general purpose register.
+    ///
+    /// # Panics
+    /// If `reg` isn't between 0 and 7.
+    pub fn set_reg(&mut self, reg: u8, value: u16) {
+        assert!(reg < 8, "there are only 8 general purpose registers");
+        unsafe { root::set_reg(self.ptr(), reg.into(), value) };
+    }
+
+    /// Everything the machine has printed since the last
+    /// [reset](Simulator::reset) or [`clear_output`](Simulator::clear_output).
+    ///
+    /// Bytes that aren't valid UTF-8 are replaced with `U+FFFD`.
+    pub fn output(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.output_bytes())
+    }
+
+    /// Like [`output`](Simulator::output) but without the UTF-8 conversion.
+    pub fn output_bytes(&self) -> &[u8] {
+        &self.io().output
+    }
+
+    /// Discards the output captured so far.
+    pub fn clear_output(&mut self) {
+        self.io_mut().output.clear();
+    }
+
+    /// Makes `bytes` available as input once the machine has executed `after`
+    /// instructions (counting from the last [reset](Simulator::reset)).
+    ///
+    /// Input is consumed in order; input that's due is available until the
+    /// program reads it. This mirrors `StringInputter::setStringAfter` in the
+    /// C++ grader framework and makes it possible to test polling and
+    /// interrupt-driven programs deterministically.
+    pub fn schedule_input(&mut self, after: u64, bytes: impl AsRef<[u8]>) {
+        let bytes = bytes.as_ref();
+        unsafe {
+            root::scheduled_inputter_add(
+                self.inputter,
+                after,
+                bytes.len() as root::size_t,
+                bytes.as_ptr(),
+            )
+        };
+    }
+
+    /// Makes `bytes` available as input right away.
+    pub fn input(&mut self, bytes: impl AsRef<[u8]>) {
+        self.schedule_input(0, bytes);
+    }
+
+    /// The number of instructions the machine has executed.
+    pub fn instructions_executed(&self) -> u64 {
+        unsafe { root::get_inst_exec_count(self.ptr()) }
+    }
+
+    /// Limits how many instructions a single [run](Simulator::run) may
+    /// execute; runs that hit the limit fail. 0 means no limit (the default),
+    /// except on machines with no OS ([`OsImage::Bare`]) where it means
+    /// [`BARE_INSTRUCTION_LIMIT`].
+    ///
+    /// The limit persists across [resets](Simulator::reset).
+    pub fn set_instruction_limit(&mut self, limit: u64) {
+        self.instruction_limit = limit;
+        let limit = self.effective_instruction_limit();
+        unsafe { root::set_run_inst_limit(self.ptr(), limit) };
+    }
+
+    fn effective_instruction_limit(&self) -> u64 {
+        match (&self.os, self.instruction_limit) {
+            (OsImage::Bare, 0) => BARE_INSTRUCTION_LIMIT,
+            (_, limit) => limit,
+        }
+    }
+
+    /// Runs the loaded program starting at the given PC until it halts.
+    ///
+    /// Runs also stop at [breakpoints](Simulator::set_breakpoint); check
+    /// [`halted`](Simulator::halted) to tell the two apart.
+    pub fn run(&mut self, pc: u16) -> Result<State, RunError> {
+        let state = unsafe { root::run_program(self.ptr(), pc) };
+        self.check(state)
+    }
+
+    /// Continues running from the current PC until the program halts or hits
+    /// a [breakpoint](Simulator::set_breakpoint).
+    ///
+    /// A breakpoint at the current PC doesn't stop the machine right away so
+    /// that calling this again after hitting a breakpoint makes progress.
+    pub fn resume(&mut self) -> Result<State, RunError> {
+        let state = self.off_breakpoint(root::step_in);
+        let at_breakpoint = self.breakpoints.contains(&(state.pc as u16));
+        if !state.success || at_breakpoint || self.halted() {
+            return self.check(state);
+        }
+
+        let state = unsafe { root::resume_program(self.ptr()) };
+        self.check(state)
+    }
+
+    /// Executes a single instruction.
+    pub fn step_in(&mut self) -> Result<State, RunError> {
+        let state = self.off_breakpoint(root::step_in);
+        self.check(state)
+    }
+
+    /// Executes a single instruction; subroutine calls are run to completion
+    /// (unless they hit a [breakpoint](Simulator::set_breakpoint)).
+    pub fn step_over(&mut self) -> Result<State, RunError> {
+        let state = self.off_breakpoint(root::step_over);
+        self.check(state)
+    }
+
+    /// Runs until the current subroutine returns (or the machine hits a
+    /// [breakpoint](Simulator::set_breakpoint)).
+    pub fn step_out(&mut self) -> Result<State, RunError> {
+        let state = self.off_breakpoint(root::step_out);
+        self.check(state)
+    }
+
+    /// The current state of the machine.
+    pub fn state(&self) -> State {
+        unsafe { root::get_state(self.ptr()) }
+    }
+
+    /// Sets the PC, i.e. for [`resume`](Simulator::resume).
+    pub fn set_pc(&mut self, pc: u16) {
+        unsafe { root::set_pc(self.ptr(), pc) };
+    }
+
+    /// Whether the machine has halted (the clock enable bit in the MCR is
+    /// clear).
+    pub fn halted(&self) -> bool {
+        self.state().mcr & 0x8000 == 0
+    }
+
+    /// Sets a breakpoint; returns `false` if there already was one at `addr`.
+    ///
+    /// Breakpoints persist across [resets](Simulator::reset).
+    pub fn set_breakpoint(&mut self, addr: u16) -> bool {
+        let new = self.breakpoints.insert(addr);
+        if new {
+            unsafe { root::set_breakpoint(self.ptr(), addr) };
+        }
+
+        new
+    }
+
+    /// Removes a breakpoint; returns `false` if there wasn't one at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        let removed = self.breakpoints.remove(&addr);
+        if removed {
+            let _ = unsafe { root::remove_breakpoint(self.ptr(), addr) };
+        }
+
+        removed
+    }
+
+    /// The addresses that have breakpoints, in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Calls `step` with the breakpoint at the current PC (if there is one)
+    /// lifted so that the machine doesn't stop before it's gone anywhere.
+    fn off_breakpoint(
+        &mut self,
+        step: unsafe extern "C" fn(*mut lc3::sim) -> State,
+    ) -> State {
+        let pc = self.state().pc as u16;
+        let lifted = self.breakpoints.contains(&pc);
+
+        unsafe {
+            if lifted {
+                let _ = root::remove_breakpoint(self.ptr(), pc);
+            }
+            let state = step(self.ptr());
+            if lifted {
+                root::set_breakpoint(self.ptr(), pc);
+            }
+
+            state
+        }
+    }
+
+    fn check(&self, state: State) -> Result<State, RunError> {
+        let exceeded = unsafe { root::did_exceed_inst_limit(self.ptr()) };
+
+        if state.success && !exceeded {
+            Ok(state)
+        } else {
+            Err(RunError {
+                state,
+                exceeded_instruction_limit: exceeded,
+                seed: self.seed,
+            })
+        }
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        unsafe {
+            root::free_sim(self.ptr());
+            root::free_printer(self.printer);
+            root::free_inputter(self.inputter);
+            drop(Box::from_raw(self.io.as_ptr()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    const PROGRAM: &str = "\
+        .ORIG x3000
+        LEA R0, MSG
+        PUTS
+        AND R1, R1, #0
+        LD R2, COUNT
+LOOP    ADD R1, R1, R2
+        ADD R2, R2, #-1
+        BRp LOOP
+        HALT
+MSG     .STRINGZ \"sum\"
+COUNT   .FILL #100
+        .END
+";
+
+    /// The registers, output and instruction count after running
+    /// [`PROGRAM`] on a machine randomized with `seed`.
+    fn run(sim: &mut Simulator, seed: u64) -> ([u32; 8], String, u64) {
+        let program = asm::assemble(PROGRAM).unwrap();
+
+        sim.randomize(seed);
+        sim.load_program(program.iter());
+        let state = sim.run(0x3000).unwrap();
+
+        (
+            state.regs,
+            sim.output().into_owned(),
+            sim.instructions_executed(),
+        )
+    }
+
+    fn sim() -> Simulator {
+        Simulator::builder().instruction_limit(100_000).build()
+    }
+
+    #[test]
+    fn simulators_on_many_threads() {
+        const THREADS: u64 = 8;
+        const RUNS: usize = 32;
+
+        let expected = (0..THREADS)
+            .map(|seed| run(&mut sim(), seed))
+            .collect::<Vec<_>>();
+        assert_eq!(expected[0].0[1], 5050);
+
+        // Half the simulators are made here and moved to their thread; the
+        // rest are made on the thread they run on.
+        let handles = (0..THREADS)
+            .map(|seed| {
+                let moved = if seed % 2 == 0 { Some(sim()) } else { None };
+
+                thread::spawn(move || {
+                    let mut sim = moved.unwrap_or_else(sim);
+                    (0..RUNS).map(|_| run(&mut sim, seed)).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for (handle, expected) in handles.into_iter().zip(expected) {
+            for res in handle.join().unwrap() {
+                assert_eq!(res, expected);
+            }
+        }
+    }
+
+    const SEVEN: &str = "\
+        .ORIG x3000
+        AND R1, R1, #0
+        ADD R1, R1, #7
+        HALT
+        .END
+";
+
+    #[test]
+    fn custom_os() {
+        // A `HALT` handler at x0400 that just clears the MCR.
+        let os = vec![
+            (0x0025, 0x0400),
+            (0x0400, 0x5020), // AND R0, R0, #0
+            (0x0401, 0xB001), // STI R0, MCR
+            (0x0402, 0x0FFF), // BRnzp #-1
+            (0x0403, 0xFFFE), // MCR .FILL xFFFE
+        ];
+        let mut sim = Simulator::builder()
+            .os(OsImage::Custom(os.into_iter().collect()))
+            .instruction_limit(1000)
+            .build();
+
+        // The rest of system space is cleared (the stock OS has a vector for
+        // `OUT` here).
+        assert_eq!(sim.mem(0x0021), 0);
+        assert_eq!(sim.mem(0x0025), 0x0400);
+
+        sim.load_program(asm::assemble(SEVEN).unwrap().iter());
+        let state = sim.run(0x3000).unwrap();
+
+        assert_eq!(state.regs[1], 7);
+        assert!(sim.halted());
+        assert!(sim.instructions_executed() < 10);
+
+        // Resets load the image again.
+        sim.set_mem(0x0400, 0);
+        sim.reset();
+        assert_eq!(sim.mem(0x0400), 0x5020);
+    }
+
+    #[test]
+    fn bare_os() {
+        let mut sim = Simulator::builder().os(OsImage::Bare).build();
+        assert_eq!(sim.effective_instruction_limit(), BARE_INSTRUCTION_LIMIT);
+        assert_eq!(sim.mem(0x0025), 0);
+
+        // `HALT` jumps to x0000 and runs off through the (zeroed) system
+        // space back into the program so the run has to hit the limit.
+        sim.set_instruction_limit(1000);
+        sim.load_program(asm::assemble(SEVEN).unwrap().iter());
+        let err = sim.run(0x3000).unwrap_err();
+
+        assert!(err.exceeded_instruction_limit);
+
+        sim.set_instruction_limit(0);
+        assert_eq!(sim.effective_instruction_limit(), BARE_INSTRUCTION_LIMIT);
+    }
+
+    #[test]
+    fn overlay_overlaps() {
+        let mut sim = sim();
+        sim.load_program(vec![(0x3000, 1), (0x3001, 2), (0x3002, 3)]);
+
+        // Words from `set_mem` don't count.
+        sim.set_mem(0x4000, 4);
+        assert_eq!(sim.load_overlay(vec![(0x4000, 5), (0x4001, 6)]), Ok(()));
+
+        let err = sim
+            .load_overlay(vec![(0x3002, 7), (0x3001, 8), (0x3003, 9)])
+            .unwrap_err();
+        assert_eq!(err.addrs, [0x3001, 0x3002]);
+        assert_eq!(
+            err.to_string(),
+            "the program overwrote 2 loaded word(s) at: 0x3001 0x3002"
+        );
+
+        // The overlay is loaded anyway.
+        let mem = (0x3000..0x3004).map(|a| sim.mem(a)).collect::<Vec<_>>();
+        assert_eq!(mem, [1, 8, 7, 9]);
+        assert_eq!(sim.mem(0x4000), 5);
+
+        // Resets forget what was loaded.
+        sim.reset();
+        assert_eq!(sim.load_overlay(vec![(0x3001, 1)]), Ok(()));
+    }
+
+    #[test]
+    fn randomize_and_replay() {
+        let user_mem = |sim: &Simulator| {
+            (0x3000..0x3100).map(|a| sim.mem(a)).collect::<Vec<_>>()
+        };
+
+        let (mut a, mut b) = (sim(), sim());
+        a.randomize(42);
+        b.randomize(42);
+        assert_eq!(user_mem(&a), user_mem(&b));
+        assert_eq!(a.state().regs, b.state().regs);
+
+        b.randomize(43);
+        assert_ne!(user_mem(&a), user_mem(&b));
+
+        // Loaded programs survive randomizing and resets randomize again.
+        let program = asm::assemble(SEVEN).unwrap();
+        let randomized = user_mem(&a);
+        a.load_program(program.iter());
+        a.randomize(42);
+        assert_eq!(a.mem(0x3001), program.words[1].value);
+        assert_eq!(a.seed(), Some(42));
+
+        a.reset();
+        assert_eq!(user_mem(&a), randomized);
+
+        // Failed runs report the seed so they can be replayed with
+        // `LC3TOOLS_SEED`.
+        let spin = asm::assemble(".ORIG x3000\nBR #-1\n.END\n").unwrap();
+        a.load_program(spin.iter());
+        a.set_instruction_limit(100);
+        let err = a.run(0x3000).unwrap_err();
+        assert_eq!(err.seed, Some(42));
+        assert!(err.to_string().contains("`LC3TOOLS_SEED=0x2a`"));
+
+        for (var, seed) in [("0x2a", 42), ("42", 42), (" 7 ", 7)].iter() {
+            env::set_var(SEED_ENV_VAR, var);
+            assert_eq!(pick_seed(), *seed);
+        }
+        env::remove_var(SEED_ENV_VAR);
+    }
+
+    #[test]
+    fn scheduled_input() {
+        let program = asm::assemble(".ORIG x3000\nGETC\nHALT\n.END\n").unwrap();
+        let mut sim = Simulator::builder().input_after(1000, "a").build();
+        sim.load_program(program.iter());
+
+        // `GETC` spins until the input shows up...
+        sim.set_instruction_limit(900);
+        let err = sim.run(0x3000).unwrap_err();
+        assert!(err.exceeded_instruction_limit);
+        assert_ne!(err.state.regs[0], u32::from(b'a'));
+
+        // ...which is once the machine has executed 1000 instructions.
+        sim.set_instruction_limit(100_000);
+        let state = sim.resume().unwrap();
+        assert_eq!(state.regs[0], u32::from(b'a'));
+        assert!(sim.instructions_executed() >= 1000);
+
+        // Input that's already due is released right away.
+        sim.load_program(program.iter());
+        sim.schedule_input(0, "b");
+        assert_eq!(sim.run(0x3000).unwrap().regs[0], u32::from(b'b'));
+        assert!(sim.instructions_executed() < 1000);
+    }
+
+    const SUBROUTINE: &str = "\
+        .ORIG x3000
+        AND R1, R1, #0
+        JSR SUB
+        ADD R1, R1, #1
+        HALT
+SUB     ADD R1, R1, #2
+        RET
+        .END
+";
+
+    #[test]
+    fn breakpoints() {
+        let mut sim = sim();
+        sim.load_program(asm::assemble(SUBROUTINE).unwrap().iter());
+
+        assert!(sim.set_breakpoint(0x3002));
+        assert!(!sim.set_breakpoint(0x3002));
+        assert!(sim.set_breakpoint(0x3005));
+        assert_eq!(sim.breakpoints().collect::<Vec<_>>(), [0x3002, 0x3005]);
+
+        let state = sim.run(0x3000).unwrap();
+        assert_eq!((state.pc, state.regs[1]), (0x3005, 2));
+        assert!(!sim.halted());
+
+        // Resuming from a breakpoint makes progress.
+        let state = sim.resume().unwrap();
+        assert_eq!((state.pc, state.regs[1]), (0x3002, 2));
+
+        assert!(sim.remove_breakpoint(0x3005));
+        assert!(!sim.remove_breakpoint(0x3005));
+
+        // Breakpoints persist across resets.
+        sim.load_program(asm::assemble(SUBROUTINE).unwrap().iter());
+        assert_eq!(sim.breakpoints().collect::<Vec<_>>(), [0x3002]);
+        assert_eq!(sim.run(0x3000).unwrap().pc, 0x3002);
+
+        let state = sim.resume().unwrap();
+        assert!(sim.halted());
+        assert_eq!(state.regs[1], 3);
+    }
+
+    #[test]
+    fn stepping() {
+        let mut sim = sim();
+        sim.load_program(asm::assemble(SUBROUTINE).unwrap().iter());
+        sim.set_pc(0x3000);
+
+        assert_eq!(sim.step_in().unwrap().pc, 0x3001);
+        assert_eq!(sim.step_in().unwrap().pc, 0x3004);
+
+        let state = sim.step_out().unwrap();
+        assert_eq!((state.pc, state.regs[1]), (0x3002, 2));
+
+        sim.set_pc(0x3001);
+        let state = sim.step_over().unwrap();
+        assert_eq!((state.pc, state.regs[1]), (0x3002, 4));
+
+        // Stepping off of a breakpoint doesn't stop on it again.
+        assert!(sim.set_breakpoint(0x3002));
+        let state = sim.step_in().unwrap();
+        assert_eq!((state.pc, state.regs[1]), (0x3003, 5));
+    }
+}
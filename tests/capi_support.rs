@@ -0,0 +1,291 @@
+//! Runs the tests for the `shared-lib` support in `build.rs`; cargo doesn't
+//! run tests in build scripts.
+
+#[path = "../build/header_support.rs"]
+mod header_support;
+
+#[path = "../build/capi_support.rs"]
+mod capi_support;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+use pretty_assertions::assert_eq;
+
+use capi_support::{
+    def_file, exported_symbols_list, functions, header, version_script,
+    wrappers, Function, PREFIX,
+};
+
+const HEADER: &str = r#"#include "simulator.h"
+
+extern "C" {
+    typedef struct State {
+        uint32_t pc;
+    } State;
+
+    // Sim constructors:
+    /// Creates a new [`sim`] with the given `Printer`.
+    ///
+    /// [`sim`]: crate::root::lc3::sim
+    lc3::sim *new_sim(
+        lc3::utils::IPrinter *printer,
+        lc3::utils::PrintType print_level
+    );
+    /// Like [`new_sim`] but for `lc3::sim_ext`.
+    lc3::sim *new_sim_ext(void (*func)(unsigned char));
+
+    void set_mem(lc3::sim *sim, uint16_t const addr, uint8_t buf[/*len*/]);
+    State get_state(lc3::sim *sim); // The registers.
+}
+"#;
+
+fn print_types() -> Vec<String> {
+    vec!["P_NONE".to_string(), "P_SIM_OUTPUT".to_string()]
+}
+
+fn names(functions: &[Function]) -> Vec<&str> {
+    functions.iter().map(|f| &*f.name).collect()
+}
+
+#[test]
+fn parsing_headers() {
+    let functions = functions(HEADER);
+    assert_eq!(
+        names(&functions),
+        ["new_sim", "new_sim_ext", "set_mem", "get_state"]
+    );
+    assert_eq!(functions[0].exported(), format!("{}new_sim", PREFIX));
+
+    assert_eq!(
+        wrappers(&["bindings.h"], &functions[..3], &[]),
+        r#"// Generated by `build.rs` in `lc3tools-sys`.
+
+#include "bindings.h"
+
+#if defined(_WIN32)
+#define LC3CORE_EXPORT __declspec(dllexport)
+#else
+#define LC3CORE_EXPORT __attribute__((visibility("default")))
+#endif
+
+
+extern "C" {
+    LC3CORE_EXPORT lc3::sim * lc3core_v1_new_sim(lc3::utils::IPrinter *printer, lc3::utils::PrintType print_level) {
+        return ::new_sim(printer, print_level);
+    }
+    LC3CORE_EXPORT lc3::sim * lc3core_v1_new_sim_ext(void (*func)(unsigned char)) {
+        return ::new_sim_ext(func);
+    }
+    LC3CORE_EXPORT void lc3core_v1_set_mem(lc3::sim *sim, uint16_t const addr, uint8_t buf[]) {
+        return ::set_mem(sim, addr, buf);
+    }
+}
+"#
+    );
+}
+
+#[test]
+fn generated_header() {
+    let functions = functions(HEADER);
+    let h = header(&[HEADER.to_string()], &functions, &print_types());
+
+    assert_eq!(
+        h,
+        r#"/* Generated by `build.rs` in `lc3tools-sys`. */
+
+#ifndef LC3CORE_H
+#define LC3CORE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/// An `lc3::sim`.
+typedef struct lc3core_sim lc3core_sim;
+/// An `lc3::utils::IPrinter`.
+typedef struct lc3core_printer lc3core_printer;
+/// An `lc3::utils::IInputter`.
+typedef struct lc3core_inputter lc3core_inputter;
+
+/// An `lc3::utils::PrintType`.
+typedef int32_t lc3core_print_type;
+enum {
+    LC3CORE_PRINT_NONE = 0,
+    LC3CORE_PRINT_SIM_OUTPUT = 1,
+};
+
+typedef struct lc3core_state {
+    uint32_t pc;
+} lc3core_state;
+
+// Sim constructors:
+/// Creates a new `sim` with the given `Printer`.
+lc3core_sim *lc3core_v1_new_sim(
+    lc3core_printer *printer,
+    lc3core_print_type print_level
+);
+/// Like `lc3core_v1_new_sim` but for `lc3::sim_ext`.
+lc3core_sim *lc3core_v1_new_sim_ext(void (*func)(unsigned char));
+
+void lc3core_v1_set_mem(lc3core_sim *sim, uint16_t const addr, uint8_t buf[/*len*/]);
+lc3core_state lc3core_v1_get_state(lc3core_sim *sim); // The registers.
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LC3CORE_H */
+"#
+    );
+}
+
+#[test]
+fn exports() {
+    let functions = functions(HEADER);
+    let functions = &functions[..2];
+
+    assert_eq!(
+        version_script(functions),
+        "LC3CORE_1 {
+    global:
+        lc3core_v1_new_sim;
+        lc3core_v1_new_sim_ext;
+    local:
+        *;
+};
+"
+    );
+    assert_eq!(
+        exported_symbols_list(functions),
+        "_lc3core_v1_new_sim\n_lc3core_v1_new_sim_ext\n"
+    );
+    assert_eq!(
+        def_file(functions),
+        "LIBRARY lc3core
+EXPORTS
+    lc3core_v1_new_sim
+    lc3core_v1_new_sim_ext
+"
+    );
+}
+
+/// The C API headers, as `build.rs` reads them.
+fn c_api() -> Vec<String> {
+    ["extra/bindings.h", "extra/frontend/frontend_bindings.h"]
+        .iter()
+        .map(|h| fs::read_to_string(h).unwrap())
+        .collect()
+}
+
+#[test]
+fn every_function_is_exported() {
+    let headers = c_api();
+    let functions = headers
+        .iter()
+        .flat_map(|h| functions(h))
+        .collect::<Vec<_>>();
+    let h = header(&headers, &functions, &print_types());
+
+    for name in ["new_sim", "run_program", "free_sim", "console_printer"].iter()
+    {
+        assert!(names(&functions).contains(name), "`{}` is missing", name);
+    }
+    for f in &functions {
+        assert!(
+            h.contains(&format!(" *{}(", f.exported()))
+                || h.contains(&format!(" {}(", f.exported())),
+            "`{}` isn't declared in `lc3core.h`",
+            f.exported(),
+        );
+    }
+
+    // Everything from C++ should have been replaced.
+    for line in h.lines().filter(|l| !l.trim_start().starts_with("//")) {
+        assert!(!line.contains("lc3::"), "`{}` is C++", line);
+    }
+    assert!(!h.contains("crate::"));
+}
+
+/// Compiles `src` with the C (or C++) compiler, if there is one.
+fn compile(src: &str, ext: &str, flags: &[&str]) {
+    let var = if ext == "c" { "CC" } else { "CXX" };
+    let default = if ext == "c" { "cc" } else { "c++" };
+    let compiler = env::var(var).unwrap_or_else(|_| default.to_string());
+
+    let dir = env::temp_dir().join(format!(
+        "lc3tools-sys-capi-{}-{}",
+        ext,
+        process::id()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+
+    let headers = c_api();
+    let functions = headers
+        .iter()
+        .flat_map(|h| functions(h))
+        .collect::<Vec<_>>();
+    let print_types = ["P_NONE", "P_SIM_OUTPUT", "P_FATAL_ERROR"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>();
+    fs::write(
+        dir.join("lc3core.h"),
+        header(&headers, &functions, &print_types),
+    )
+    .unwrap();
+
+    let file: PathBuf = dir.join(format!("check.{}", ext));
+    fs::write(&file, src).unwrap();
+
+    let output = Command::new(&compiler)
+        .args(flags)
+        .arg("-fsyntax-only")
+        .arg("-Wall")
+        .arg("-Wextra")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(&dir)
+        .arg(&file)
+        .output();
+    fs::remove_dir_all(&dir).unwrap();
+
+    match output {
+        Ok(output) => assert!(
+            output.status.success(),
+            "`lc3core.h` doesn't compile:\n{}",
+            String::from_utf8_lossy(&output.stderr),
+        ),
+        Err(err) => eprintln!("skipping; couldn't run `{}`: {}", compiler, err),
+    }
+}
+
+const USES_THE_HEADER: &str = r#"#include "lc3core.h"
+
+lc3core_sim *make(void) {
+    lc3core_printer *printer = lc3core_v1_no_op_printer();
+    lc3core_inputter *inputter = lc3core_v1_no_op_inputter();
+    lc3core_sim *sim =
+        lc3core_v1_new_sim(printer, inputter, LC3CORE_PRINT_FATAL_ERROR);
+
+    lc3core_v1_set_mem(sim, 0x3000, 0xF025);
+    lc3core_state state = lc3core_v1_run_program(sim, 0x3000);
+    return state.success ? sim : NULL;
+}
+"#;
+
+#[test]
+fn header_compiles_as_c() {
+    compile(USES_THE_HEADER, "c", &["-std=c99", "-pedantic"]);
+}
+
+#[test]
+fn header_compiles_as_cpp() {
+    compile(USES_THE_HEADER, "cpp", &["-std=c++14", "-pedantic"]);
+}
@@ -0,0 +1,5 @@
+//! Runs the tests for the patch support in `build.rs`; cargo doesn't run
+//! tests in build scripts.
+
+#[path = "../build/patch_support.rs"]
+mod patch_support;
@@ -0,0 +1,85 @@
+//! Builds a small C program against the shared library (and `lc3core.h`)
+//! that the `shared-lib` feature makes and runs it.
+#![cfg(all(feature = "shared-lib", not(target_env = "msvc")))]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+const SMOKE_TEST: &str = r#"#include <stdio.h>
+
+#include "lc3core.h"
+
+int main(void) {
+    lc3core_printer *printer = lc3core_v1_no_op_printer();
+    lc3core_inputter *inputter = lc3core_v1_no_op_inputter();
+    lc3core_sim *sim =
+        lc3core_v1_new_sim(printer, inputter, LC3CORE_PRINT_NONE);
+    if (sim == NULL) {
+        fprintf(stderr, "`lc3core_v1_new_sim` failed\n");
+        return 1;
+    }
+
+    lc3core_v1_set_mem(sim, 0x3000, 0x1234);
+    if (lc3core_v1_get_mem(sim, 0x3000) != 0x1234) {
+        fprintf(stderr, "`lc3core_v1_get_mem` didn't see the write\n");
+        return 1;
+    }
+
+    lc3core_v1_set_pc(sim, 0x3000);
+    lc3core_state state = lc3core_v1_get_state(sim);
+    if (state.pc != 0x3000) {
+        fprintf(stderr, "the PC is x%04X, not x3000\n", (unsigned) state.pc);
+        return 1;
+    }
+
+    lc3core_v1_free_sim(sim);
+    lc3core_v1_free_printer(printer);
+    lc3core_v1_free_inputter(inputter);
+    return 0;
+}
+"#;
+
+#[test]
+fn c_programs_can_use_the_library() {
+    let shared = Path::new(env!("LC3CORE_SHARED_DIR"));
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let dir = env::temp_dir()
+        .join(format!("lc3tools-sys-shared-lib-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join("smoke.c");
+    let exe = dir.join("smoke");
+    fs::write(&src, SMOKE_TEST).unwrap();
+
+    let status = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(shared)
+        .arg("-o")
+        .arg(&exe)
+        .arg(&src)
+        .arg("-L")
+        .arg(shared)
+        .arg(format!("-Wl,-rpath,{}", shared.display()))
+        .arg("-llc3core")
+        .status()
+        .unwrap();
+    assert!(status.success(), "couldn't build the smoke test");
+
+    // (`-rpath` doesn't mean anything for DLLs.)
+    let path = env::var_os("PATH").unwrap_or_default();
+    let mut paths = vec![shared.to_path_buf()];
+    paths.extend(env::split_paths(&path));
+
+    let status = Command::new(&exe)
+        .env("PATH", env::join_paths(paths).unwrap())
+        .status()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(status.success(), "the smoke test failed");
+}
@@ -0,0 +1,228 @@
+//! Runs the tests for the `verify-bindings` support in `build.rs`; cargo
+//! doesn't run tests in build scripts.
+
+#[path = "../build/symbol_support.rs"]
+mod symbol_support;
+
+use std::collections::HashSet;
+use std::fs;
+use std::process;
+
+use pretty_assertions::assert_eq;
+
+use symbol_support::{archive_symbols, functions, index_symbols};
+
+/// An archive member: a header and the data, padded to an even length.
+fn member(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+        name,
+        0,
+        0,
+        0,
+        644,
+        data.len()
+    )
+    .into_bytes();
+    assert_eq!(out.len(), 60);
+
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(b'\n');
+    }
+
+    out
+}
+
+fn archive(members: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = b"!<arch>\n".to_vec();
+    for m in members {
+        out.extend_from_slice(m);
+    }
+
+    out
+}
+
+/// An object file member for the index to point at.
+fn object() -> Vec<u8> {
+    member("sim.o/", b"\x7fELF")
+}
+
+fn word(value: usize, word: usize, big_endian: bool) -> Vec<u8> {
+    let bytes = if big_endian {
+        (value as u64).to_be_bytes()
+    } else {
+        (value as u64).to_le_bytes()
+    };
+
+    if big_endian {
+        bytes[8 - word..].to_vec()
+    } else {
+        bytes[..word].to_vec()
+    }
+}
+
+/// A System V/GNU index: the count, the member offsets and the names.
+fn gnu_index(symbols: &[&str], size: usize) -> Vec<u8> {
+    let mut out = word(symbols.len(), size, true);
+    for _ in symbols {
+        out.extend(word(8, size, true));
+    }
+    for s in symbols {
+        out.extend_from_slice(s.as_bytes());
+        out.push(0);
+    }
+
+    out
+}
+
+/// A BSD index: the size of the `(name, member)` offset table, the table,
+/// the size of the string table and the string table.
+fn bsd_index(symbols: &[&str], size: usize) -> Vec<u8> {
+    let (mut table, mut strings) = (Vec::new(), Vec::new());
+    for s in symbols {
+        table.extend(word(strings.len(), size, false));
+        table.extend(word(8, size, false));
+        strings.extend_from_slice(s.as_bytes());
+        strings.push(0);
+    }
+
+    let mut out = word(table.len(), size, false);
+    out.extend(table);
+    out.extend(word(strings.len(), size, false));
+    out.extend(strings);
+
+    out
+}
+
+fn set(symbols: &[&str]) -> HashSet<String> {
+    symbols.iter().map(|s| s.to_string()).collect()
+}
+
+const SYMBOLS: &[&str] = &["new_sim", "_ZN3lc33sim6getMemEt", "run_program"];
+
+#[test]
+fn gnu_archives() {
+    let index = member("/", &gnu_index(SYMBOLS, 4));
+    let data = archive(&[index, object()]);
+    assert_eq!(index_symbols(&data).unwrap(), set(SYMBOLS));
+
+    let index = member("/SYM64/", &gnu_index(SYMBOLS, 8));
+    let data = archive(&[index, object()]);
+    assert_eq!(index_symbols(&data).unwrap(), set(SYMBOLS));
+}
+
+#[test]
+fn bsd_archives() {
+    let index = member("__.SYMDEF", &bsd_index(SYMBOLS, 4));
+    let data = archive(&[index, object()]);
+    assert_eq!(index_symbols(&data).unwrap(), set(SYMBOLS));
+
+    // macOS's `ar` puts the name after the header (`#1/<len>`).
+    let mut body = b"__.SYMDEF_64 SORTED\0\0\0\0\0".to_vec();
+    body.extend(bsd_index(SYMBOLS, 8));
+    let data = archive(&[member("#1/24", &body), object()]);
+    assert_eq!(index_symbols(&data).unwrap(), set(SYMBOLS));
+}
+
+#[test]
+fn empty_indexes() {
+    let data = archive(&[member("/", &gnu_index(&[], 4))]);
+    assert_eq!(index_symbols(&data).unwrap(), set(&[]));
+
+    let data = archive(&[member("__.SYMDEF", &bsd_index(&[], 4))]);
+    assert_eq!(index_symbols(&data).unwrap(), set(&[]));
+}
+
+#[test]
+fn bad_archives() {
+    let err = |data: &[u8]| index_symbols(data).unwrap_err().to_string();
+
+    assert_eq!(err(b"!<arch>\n"), "the archive is empty");
+    assert_eq!(err(b"\x7fELF"), "not an `ar` archive");
+    assert_eq!(
+        err(&archive(&[object()])),
+        "the archive has no symbol index"
+    );
+
+    let mut truncated = archive(&[member("/", &gnu_index(SYMBOLS, 4))]);
+    truncated.truncate(truncated.len() - 10);
+    assert_eq!(err(&truncated), "truncated archive");
+
+    // More symbols than there's room for.
+    let mut index = gnu_index(SYMBOLS, 4);
+    index[..4].copy_from_slice(&1000u32.to_be_bytes());
+    assert_eq!(
+        err(&archive(&[member("/", &index)])),
+        "malformed symbol index"
+    );
+}
+
+#[test]
+fn archive_files() {
+    let path = std::env::temp_dir()
+        .join(format!("lc3tools-sys-symbols-{}.a", process::id()));
+    let index = member("/", &gnu_index(SYMBOLS, 4));
+    fs::write(&path, archive(&[index, object()])).unwrap();
+
+    let symbols = archive_symbols(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(symbols.unwrap(), set(SYMBOLS));
+
+    // i.e. a prebuilt shared library instead of a static one.
+    assert!(archive_symbols(&path).is_err());
+}
+
+const BINDINGS: &str = r#"
+pub mod root {
+    extern "C" {
+        pub fn new_sim(print_level: u32) -> *mut root::lc3::sim;
+        pub fn strlen(s: *const u8) -> usize;
+    }
+    #[cfg(feature = "grader")]
+    extern "C" {
+        pub fn grader_only();
+    }
+    pub mod lc3 {
+        extern "C" {
+            #[link_name = "\u{1}_ZN3lc33sim6getMemEt"]
+            pub fn sim_getMem(this: *const root::lc3::sim, addr: u16) -> u16;
+        }
+    }
+}
+"#;
+
+const HEADERS: &str = "
+void * new_sim(uint32_t print_level);
+void grader_only(void);
+";
+
+fn found(enabled: bool, prefix: &str) -> Vec<(String, String)> {
+    functions(BINDINGS, HEADERS, |f| enabled && f == "grader", prefix)
+        .into_iter()
+        .map(|f| (f.path, f.symbol))
+        .collect()
+}
+
+#[test]
+fn functions_in_the_bindings() {
+    let pair = |p: &str, s: &str| (p.to_string(), s.to_string());
+
+    assert_eq!(
+        found(false, ""),
+        [
+            pair("root::new_sim", "new_sim"),
+            pair("root::lc3::sim_getMem", "_ZN3lc33sim6getMemEt"),
+        ]
+    );
+
+    // Names that bindgen marks as final (`\u{1}`) don't get the prefix.
+    assert_eq!(
+        found(true, "_"),
+        [
+            pair("root::new_sim", "_new_sim"),
+            pair("root::grader_only", "_grader_only"),
+            pair("root::lc3::sim_getMem", "_ZN3lc33sim6getMemEt"),
+        ]
+    );
+}